@@ -54,6 +54,21 @@ fn binary_candidates(bin: &str) -> Vec<PathBuf> {
     paths
 }
 
+/// Human-readable list of the places `resolve_binary` looks in, for error messages.
+pub fn searched_locations(name: &str) -> Vec<String> {
+    let bin = if cfg!(target_os = "windows") {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    };
+    let mut locations: Vec<String> = binary_candidates(&bin)
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    locations.push("PATH".to_string());
+    locations
+}
+
 pub fn resolve_binary(name: &str) -> Option<String> {
     let bin = if cfg!(target_os = "windows") {
         format!("{name}.exe")
@@ -1,14 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use tokio::sync::Mutex as TokioMutex;
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, LazyLock, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tauri::Emitter;
 use crate::binaries;
 use crate::path_utils;
+use crate::renderer::FrameRate;
 
 // Expose la dernière durée d'export terminée (en secondes)
 static LAST_EXPORT_TIME_S: Mutex<Option<f64>> = Mutex::new(None);
@@ -19,1424 +21,5584 @@ static LAST_EXPORT_TIME_S: Mutex<Option<f64>> = Mutex::new(None);
 #[cfg(debug_assertions)]
 const DEV_FORCE_CPU_ENCODING: bool = false;
 
+// Forçage CPU à l'exécution, aussi en release (diagnostic d'artefacts propres au GPU) :
+// variable d'environnement `QURANVID_FORCE_CPU=1` ou commande `set_force_cpu_encoding`
+const FORCE_CPU_ENV: &str = "QURANVID_FORCE_CPU";
+static FORCE_CPU_ENCODING: AtomicBool = AtomicBool::new(false);
+
 fn should_prefer_hw_encoding() -> bool {
     #[cfg(debug_assertions)]
     {
         if DEV_FORCE_CPU_ENCODING {
-            println!("[DEV] Forçage de l'encodage CPU activé (DEV_FORCE_CPU_ENCODING = true)");
+            log_out!("[DEV] Forçage de l'encodage CPU activé (DEV_FORCE_CPU_ENCODING = true)");
             return false;
         }
     }
+    if FORCE_CPU_ENCODING.load(Ordering::Relaxed) {
+        log_out!("[encoder] Forçage de l'encodage CPU activé (set_force_cpu_encoding)");
+        return false;
+    }
+    if std::env::var(FORCE_CPU_ENV).is_ok_and(|v| matches!(v.trim(), "1" | "true")) {
+        log_out!("[encoder] Forçage de l'encodage CPU activé ({})", FORCE_CPU_ENV);
+        return false;
+    }
     true
 }
 
-// Gestionnaire des processus actifs pour pouvoir les annuler
-static ACTIVE_EXPORTS: LazyLock<Mutex<HashMap<String, Arc<Mutex<Option<std::process::Child>>>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+/// Force l'encodage CPU pour les exports suivants, sans recompiler.
+#[tauri::command]
+pub fn set_force_cpu_encoding(enabled: bool) {
+    log_out!("[encoder] Forçage de l'encodage CPU : {}", enabled);
+    FORCE_CPU_ENCODING.store(enabled, Ordering::Relaxed);
+}
 
-// Gestionnaire des sessions de streaming actives
+/// Options avancées d'export. Tous les champs sont facultatifs côté frontend :
+/// leurs valeurs par défaut reproduisent le comportement historique.
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportOptions {
+    /// Encode la vidéo des chunks intermédiaires sans perte (libx264 -qp 0).
+    /// Les fichiers sont très lourds : à combiner avec `reencode_video` dans `concat_videos`.
+    pub lossless_chunks: bool,
+    /// Durée d'affichage du dernier sous-titre (ms). Par défaut `max(fade, 1000)`.
+    pub tail_ms: Option<i32>,
+    /// Fond seul (sans sous-titre) ajouté après la fin du dernier sous-titre (ms), ex. pour laisser
+    /// finir une musique de fin. Ignoré si une durée explicite est fournie.
+    pub end_padding_ms: Option<i32>,
+    /// Conserve le dossier de travail (ffconcat, `.ffgraph`) en fin d'export et journalise
+    /// les chemins des fichiers intermédiaires, pour le débogage.
+    pub keep_intermediates: bool,
+    /// Regroupe les `audios` en pistes audio distinctes (ex. récitation + traduction), chacune
+    /// sélectionnable dans le lecteur. Par défaut : une seule piste qui les enchaîne tous.
+    pub audio_tracks: Option<Vec<crate::renderer::AudioTrack>>,
+    /// Suffixe des PNG de la seconde couche de sous-titres (`{idx}{suffixe}.png`). Par défaut `_t` ;
+    /// une chaîne vide désactive la couche de traduction.
+    pub translation_suffix: Option<String>,
+    /// Unité du paramètre `blur` : sigma en pixels (défaut) ou fraction de la hauteur de l'image.
+    pub blur_unit: BlurUnit,
+    /// Assombrissement des bords du fond (0.0–1.0), appliqué après le flou. Exprimé en angle,
+    /// il ne dépend pas de la résolution.
+    pub vignette: Option<f64>,
+    /// Assombrissement uniforme du fond (0.0–1.0) : la luminance est multipliée par `1 - dim`.
+    pub dim: Option<f64>,
+    /// Désentrelacement des vidéos de fond. `None` : détection automatique (field_order ffprobe).
+    pub deinterlace: Option<bool>,
+    /// Exporte une séquence PNG au lieu d'une vidéo. Implicite si le chemin de sortie
+    /// est un motif du type `.../%06d.png`.
+    pub image_sequence: bool,
+    /// Arguments FFmpeg ajoutés tels quels juste avant le fichier de sortie (ex. `-x264-params ...`).
+    /// Non validés : à utiliser à vos risques, une valeur invalide fait échouer l'encodage.
+    pub extra_ffmpeg_args: Option<Vec<String>>,
+    /// Délai (s) sans progression avant de tuer FFmpeg. Par défaut 120 s, 0 désactive.
+    pub watchdog_timeout_s: Option<u64>,
+    /// Intervalle minimal (ms) entre deux événements `export-progress`, chemins FFmpeg et WGPU.
+    /// Par défaut 250 ms, quelle que soit la cadence.
+    pub progress_interval_ms: Option<u64>,
+    /// Pipeline d'`export_video` : `fidelity` (défaut, WGPU) ou `fast` (FFmpeg seul). Voir `ExportMode`.
+    pub mode: ExportMode,
+    /// Comportement quand le fond est plus court que l'export (chemin FFmpeg).
+    pub background_mode: BackgroundMode,
+    /// Fondu enchaîné (ms) entre la fin et le début du fond en mode `loop`. 0 = coupe franche.
+    pub loop_crossfade_ms: u32,
+    /// Rotation horaire du fond, en degrés (0, 90, 180 ou 270).
+    pub rotate: Option<i32>,
+    /// Miroir du fond : `h` (horizontal) ou `v` (vertical).
+    pub flip: Option<Flip>,
+    /// Vidéo de premier plan détourée (fond vert) placée entre le fond et les sous-titres.
+    pub chroma_key: Option<ChromaKey>,
+    /// Habillage animé (vidéo avec alpha, ex. lower-third) incrusté au-dessus des sous-titres.
+    pub overlay_video: Option<OverlayVideo>,
+    /// Texte surligné mot à mot (chemin WGPU) : un repère par sous-titre, dans l'ordre des images,
+    /// dont les `startMs` partent du début du sous-titre. Suit `segment_index` de `send_frame`.
+    pub karaoke: Option<Vec<crate::renderer::KaraokeCue>>,
+    /// Fond propre à certaines images (index de l'image → vidéo ou image). Le fond change
+    /// avec la fenêtre du sous-titre ; les index absents gardent le fond général.
+    pub ayah_backgrounds: Option<HashMap<usize, String>>,
+    /// Saute le silence en début de la première piste audio : l'export démarre à la première parole,
+    /// sous-titres et fond décalés d'autant (`export_video`).
+    pub trim_silence: bool,
+    /// Reboucle l'audio s'il est plus court que la vidéo (au lieu de finir en silence).
+    pub audio_loop: bool,
+    /// Décalage (ms) de chaque piste audio, dans l'ordre des fichiers. Les pistes sans valeur ne sont pas décalées.
+    pub audio_delays_ms: Option<Vec<u32>>,
+    /// Codec audio de l'export final. Par défaut : Opus pour WebM, AAC sinon.
+    pub audio_codec: Option<AudioCodec>,
+    /// Codec vidéo de l'export final. Par défaut : VP9 pour WebM, H.264 sinon (NVENC si disponible).
+    pub video_codec: Option<VideoCodec>,
+    /// Canaux audio de sortie : 1 (mono, débit par défaut divisé par deux) ou 2 (stéréo, par défaut).
+    pub audio_channels: Option<u32>,
+    /// Fréquence d'échantillonnage de sortie (44100 ou 48000). Par défaut 48000 ; Opus n'accepte que 48000.
+    pub audio_sample_rate: Option<u32>,
+    /// Émet les lignes stderr de FFmpeg en événements `export-log` (par lots) pour un journal en direct.
+    pub verbose: bool,
+    /// Niveau `-loglevel` de la commande d'export FFmpeg (par défaut `info`).
+    pub log_level: Option<String>,
+    /// Interpole les fonds à faible cadence jusqu'au fps d'export (`minterpolate`, CPU, coûteux).
+    pub smooth_motion: bool,
+    /// Débruitage des fonds vidéo (0–10) : `hqdn3d` jusqu'à 6, `nlmeans` (plus fort, plus lent) au-delà.
+    pub denoise: Option<f64>,
+    /// Intervalle entre images clés, en secondes (`-g = round(fps × intervalle)`). Par défaut 2 s.
+    pub keyframe_interval_s: Option<f64>,
+    /// Nombre de B-frames (`-bf`) de l'encodage final. Par défaut celui de l'encodeur (0 avec NVENC).
+    pub bframes: Option<u8>,
+    /// Réglage `-tune` de libx264/libx265 (`film`, `animation`, `zerolatency`…). Par défaut
+    /// aucun (`none`) : `zerolatency` dégrade la compression sans intérêt pour un fichier.
+    pub tune: Option<String>,
+    /// Déplace l'atome `moov` en tête des MP4/MOV (`+faststart`, seconde passe). Par défaut activé.
+    pub faststart: Option<bool>,
+    /// Écrit aussi `ffmpeg_failed_<ts>.json` (structuré, pour le rapport de crash) en cas d'échec.
+    pub json_failure_log: bool,
+    /// Écrit `<sortie>.export.json` après un export réussi : commande, codec, durées des étapes
+    /// et version de FFmpeg, pour savoir plus tard comment le fichier a été produit.
+    pub write_success_log: bool,
+    /// Mesure la sonie de l'audio final (passe `ebur128`) : `integratedLufs` et `truePeakDbtp`
+    /// dans le rapport d'export, pour vérifier les cibles des plateformes. En chunks, la mesure
+    /// porte sur la vidéo concaténée (`concat_videos`).
+    pub measure_loudness: bool,
+    /// Mode de fusion des sous-titres sur le fond : `normal` (défaut), `screen`, `multiply`, `add`.
+    pub blend_mode: crate::renderer::BlendMode,
+    /// Opacité globale des sous-titres (0–1, défaut 1) ; les fondus s'appliquent par-dessus.
+    pub subtitle_opacity: Option<f64>,
+    /// Animation d'apparition des sous-titres : `fade` (défaut), `slide_up`, `slide_left`.
+    pub enter_animation: crate::renderer::EnterAnimation,
+    /// Durée (ms) en dessous de laquelle un sous-titre passe en coupe franche, sans fondu :
+    /// évite le clignotement des timelines mot à mot. Les fondus de moins d'une image sont
+    /// toujours supprimés.
+    pub min_clip_ms: Option<u64>,
+    /// Révélation des sous-titres : `fade` (défaut, fondu uniforme) ou balayage `wipe_up`
+    /// (de bas en haut), `wipe_down`, `wipe_left`, `wipe_right`, sur la durée du fondu.
+    pub reveal: crate::renderer::Reveal,
+    /// Masque en niveaux de gris pour une révélation personnalisée : les zones sombres
+    /// apparaissent en premier. Prioritaire sur `reveal`.
+    pub reveal_mask: Option<String>,
+    /// Filtrage des sous-titres (rendu WGPU) : `linear` (défaut) ou `nearest`, net pour des PNG
+    /// déjà à la taille de la vidéo.
+    pub subtitle_sampling: crate::renderer::SamplerMode,
+    /// API graphique imposée au rendu WGPU : `vulkan`, `dx12`, `metal` ou `gl` (défaut : choix de wgpu).
+    pub backend: Option<crate::renderer::GpuBackend>,
+    /// Seek précis à l'image près dans les fonds (plus lent) au lieu du seek par keyframe.
+    pub accurate_seek: bool,
+    /// Texte fixe incrusté dans un coin (ex. « Ramadan 1446 »).
+    pub stamp: Option<Stamp>,
+    /// Fondu du fond depuis le noir en début d'export (ms), sans toucher aux sous-titres.
+    pub background_fade_in_ms: Option<u32>,
+    /// Fondu du fond vers le noir en fin d'export (ms), sans toucher aux sous-titres.
+    pub background_fade_out_ms: Option<u32>,
+    /// Nombre de sous-titres suivants décodés à l'avance (rendu WGPU, défaut 2, 0 pour désactiver).
+    pub prefetch_captions: Option<usize>,
+    /// Raccourcit l'export à la fin de l'audio / du fond quand la timeline les dépasse,
+    /// au lieu d'un silence ou d'un noir en fin de vidéo.
+    pub clamp_to_media: bool,
+    /// Refuse l'export (`INVALID_AUDIO`) si une piste audio a une durée nulle ou illisible,
+    /// au lieu de seulement avertir.
+    pub fail_on_invalid_audio: bool,
+    /// Modèle du nom de fichier (`{title}`, `{resolution}`, `{date}`, `{codec}`), ex.
+    /// `Surah_{title}_{resolution}_{date}.mp4` ; le chemin de sortie est alors le dossier.
+    pub output_name_template: Option<String>,
+    /// Titre utilisé pour `{title}` (défaut : `export`).
+    pub title: Option<String>,
+    /// Durée du fondu d'entrée des sous-titres (ms) ; par défaut `fade_duration`.
+    pub fade_in_ms: Option<u32>,
+    /// Durée du fondu de sortie des sous-titres (ms) ; par défaut `fade_duration`.
+    pub fade_out_ms: Option<u32>,
+    /// Exporte à la cadence du premier fond vidéo (`avg_frame_rate` gardé rationnel, 24–60 fps)
+    /// au lieu de `fps`, pour éviter toute conversion de cadence du fond ; la cadence retenue
+    /// est renvoyée dans `ExportReport::fps`.
+    pub match_source_fps: bool,
+    /// Taille de la vidéo encodée (ex. `[1280, 720]`) si différente du rendu : composition à
+    /// pleine taille, réduite sur le GPU avant la relecture (rendu WGPU).
+    pub output_size: Option<(u32, u32)>,
+    /// Plus grand côté accepté (px) pour la vidéo ; au-delà, l'export est refusé
+    /// (`RESOLUTION_TOO_LARGE`) ou réduit avec `downscale_to_max_dimension`.
+    pub max_dimension: Option<u32>,
+    /// Réduit la vidéo au plafond `max_dimension` (avec un avertissement) au lieu d'échouer.
+    pub downscale_to_max_dimension: bool,
+    /// Export d'une sélection d'images (ayat) par indices, bornes incluses (ex. `[5, 10]`) :
+    /// `start_time` et `duration` sont alors déduits des timestamps.
+    pub index_range: Option<(usize, usize)>,
+    /// Durées (ms) des fonds, dans l'ordre de `bg_videos`, connues de l'appelant : évite les
+    /// appels à ffprobe (scripts de traitement par lots). Les fonds ne sont alors plus vérifiés.
+    pub background_durations_ms: Option<Vec<i64>>,
+    /// Aperçu rapide (`export_preview`) : libx264 ultrafast, hauteur plafonnée. Non exposé au frontend.
+    #[serde(skip)]
+    preview: bool,
+}
 
-pub struct WgpuStreamingSession {
-    pub renderer: Arc<TokioMutex<crate::renderer::Renderer>>,
-    pub decoder: Arc<TokioMutex<crate::renderer::VideoDecoder>>,
-    pub encoder: Arc<TokioMutex<crate::renderer::VideoEncoder>>,
-    pub fade_duration_ms: u32,
-    pub fps: u32,
-    pub is_high_fidelity: bool,
-    pub overlay_enable: bool,
-    pub overlay_color: String,
-    pub overlay_opacity: f32,
+const INVALID_KEYFRAME_INTERVAL_ERROR: &str = "INVALID_KEYFRAME_INTERVAL";
+
+const INVALID_LOG_LEVEL_ERROR: &str = "INVALID_LOG_LEVEL";
+
+const INVALID_TUNE_ERROR: &str = "INVALID_TUNE";
+
+const INVALID_INDEX_RANGE_ERROR: &str = "INVALID_INDEX_RANGE";
+
+const DEFAULT_PREFETCH_CAPTIONS: usize = 2;
+// Chaque image décodée occupe w×h×4 octets : on borne la mémoire d'avance
+const MAX_PREFETCH_CAPTIONS: usize = 8;
+const X264_TUNES: [&str; 8] = ["film", "animation", "grain", "stillimage", "fastdecode", "zerolatency", "psnr", "ssim"];
+// Sous-ensemble accepté par libx265 (ni `film` ni `stillimage`)
+const X265_TUNES: [&str; 6] = ["animation", "grain", "fastdecode", "zerolatency", "psnr", "ssim"];
+const FFMPEG_LOG_LEVELS: [&str; 9] = ["quiet", "panic", "fatal", "error", "warning", "info", "verbose", "debug", "trace"];
+
+// Regroupement des événements `export-log` : au plus un envoi par intervalle, ou dès que le lot est plein
+const LOG_BATCH_INTERVAL: Duration = Duration::from_millis(250);
+const LOG_BATCH_MAX_LINES: usize = 50;
+
+const UNSUPPORTED_SAMPLE_RATE_ERROR: &str = "UNSUPPORTED_SAMPLE_RATE";
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+    Flac,
+    Alac,
 }
 
-static WGPU_STREAMS: LazyLock<Mutex<HashMap<String, Arc<WgpuStreamingSession>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
-// Fonction utilitaire pour configurer les commandes et cacher les fenêtres CMD sur Windows
-fn configure_command_no_window(cmd: &mut Command) {
-    #[cfg(target_os = "windows")]
-    {
-        use std::os::windows::process::CommandExt;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x00004000;
-        cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    // Nom tel qu'accepté dans les options (`h264`, `h265`, `vp9`, `av1`)
+    fn name(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::H265 => "h265",
+            VideoCodec::Vp9 => "vp9",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+
+    // Encodeur logiciel et réglages de qualité (codec, paramètres, preset)
+    fn software_encoder(self) -> (&'static str, Vec<String>, Option<String>) {
+        let (codec, params, preset): (&str, &[&str], Option<&str>) = match self {
+            VideoCodec::H264 => ("libx264", &["-crf", "23"], Some("medium")),
+            VideoCodec::H265 => ("libx265", &["-crf", "26", "-pix_fmt", "yuv420p"], Some("medium")),
+            VideoCodec::Vp9 => ("libvpx-vp9", &["-crf", "32", "-b:v", "0", "-row-mt", "1", "-pix_fmt", "yuv420p"], None),
+            VideoCodec::Av1 => ("libsvtav1", &["-crf", "35", "-pix_fmt", "yuv420p"], Some("8")),
+        };
+        (codec, params.iter().map(|p| p.to_string()).collect(), preset.map(str::to_string))
     }
 }
 
-fn resolve_ffmpeg_binary() -> Option<String> {
-    if let Some(path) = binaries::resolve_binary("ffmpeg") {
-        return Some(path);
+const UNSUPPORTED_AUDIO_CODEC_ERROR: &str = "UNSUPPORTED_AUDIO_CODEC";
+const INCOMPATIBLE_CODEC_CONTAINER_ERROR: &str = "INCOMPATIBLE_CODEC_CONTAINER";
+
+fn output_extension(out_path: &str) -> String {
+    Path::new(out_path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+// Table de compatibilité codec/conteneur. Extension inconnue : on laisse FFmpeg décider.
+fn video_codec_supported(ext: &str, codec: VideoCodec) -> bool {
+    match ext {
+        "webm" => matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1),
+        "mov" => matches!(codec, VideoCodec::H264 | VideoCodec::H265),
+        _ => true, // mp4/m4v/mkv acceptent les quatre
     }
+}
 
-    // En dernier recours, utiliser ffmpeg du PATH système
-    println!("[ffmpeg] Tentative d'utilisation de ffmpeg du système (PATH)");
-    if let Ok(_) = std::process::Command::new("ffmpeg").arg("-version").output() {
-        println!("[ffmpeg] ✓ FFmpeg trouvé dans le PATH système");
-        return Some("ffmpeg".to_string());
+fn audio_codec_supported(ext: &str, codec: AudioCodec) -> bool {
+    match ext {
+        "webm" => codec == AudioCodec::Opus,
+        "mov" => matches!(codec, AudioCodec::Aac | AudioCodec::Alac),
+        _ => true, // mp4/m4v/mkv acceptent les quatre
     }
+}
 
-    // Aucun binaire FFmpeg trouvé
-    None
+fn default_audio_codec(ext: &str) -> AudioCodec {
+    if ext == "webm" { AudioCodec::Opus } else { AudioCodec::Aac }
 }
 
-fn resolve_ffprobe_binary() -> String {
-    if let Some(path) = binaries::resolve_binary("ffprobe") {
-        return path;
+// Vérifie les codecs vidéo et audio choisis contre le conteneur, avant tout traitement
+fn check_codec_container(out_path: &str, options: &ExportOptions) -> Result<(), String> {
+    let ext = output_extension(out_path);
+    let video = options.video_codec(out_path);
+    if !video_codec_supported(&ext, video) {
+        return Err(format!("{}: la vidéo {:?} n'est pas supportée dans un conteneur .{}", INCOMPATIBLE_CODEC_CONTAINER_ERROR, video, ext));
     }
+    let audio = options.audio_codec.unwrap_or_else(|| default_audio_codec(&ext));
+    if !audio_codec_supported(&ext, audio) {
+        return Err(format!("{}: l'audio {:?} n'est pas supporté dans un conteneur .{}", INCOMPATIBLE_CODEC_CONTAINER_ERROR, audio, ext));
+    }
+    Ok(())
+}
 
-    // En dernier recours, utiliser ffprobe du PATH système
-    println!("[ffprobe] Tentative d'utilisation de ffprobe du système (PATH)");
-    if let Ok(_) = std::process::Command::new("ffprobe").arg("-version").output() {
-        println!("[ffprobe] ✓ FFprobe trouvé dans le PATH système");
-        return "ffprobe".to_string();
+// Arguments `-c:a` pour le conteneur de sortie, avec débit par défaut du codec.
+// Refuse les combinaisons codec/conteneur (ou codec/fréquence) que FFmpeg ne sait pas écrire.
+fn resolve_audio_codec_args(out_path: &str, codec: Option<AudioCodec>, channels: u32, sample_rate: u32) -> Result<Vec<String>, String> {
+    let ext = output_extension(out_path);
+    let codec = codec.unwrap_or_else(|| default_audio_codec(&ext));
+
+    if !audio_codec_supported(&ext, codec) {
+        return Err(format!("{}: {:?} n'est pas supporté dans un conteneur .{}", UNSUPPORTED_AUDIO_CODEC_ERROR, codec, ext));
+    }
+    // libopus n'encode pas à 44100 Hz
+    if codec == AudioCodec::Opus && sample_rate != 48000 {
+        return Err(format!("{}: {} Hz n'est pas supporté par Opus (48000 uniquement)", UNSUPPORTED_SAMPLE_RATE_ERROR, sample_rate));
     }
 
-    // Fallback vers le binaire système
-    "ffprobe".to_string()
+    // En mono, le débit par défaut est divisé par deux
+    let mono = channels == 1;
+    let args: &[&str] = match codec {
+        AudioCodec::Aac => &["-c:a", "aac", "-b:a", if mono { "160k" } else { "320k" }],
+        AudioCodec::Opus => &["-c:a", "libopus", "-b:a", if mono { "64k" } else { "128k" }],
+        AudioCodec::Flac => &["-c:a", "flac"],
+        AudioCodec::Alac => &["-c:a", "alac"],
+    };
+    let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    args.extend_from_slice(&["-ac".to_string(), channels.to_string()]);
+    Ok(args)
 }
 
-/// Teste si NVENC est réellement disponible en essayant un encodage rapide
-fn test_nvenc_availability(ffmpeg_path: Option<&str>) -> bool {
-    let exe = ffmpeg_path.unwrap_or("ffmpeg");
-    
-    println!("[nvenc_test] Test de disponibilité NVENC...");
-    
-    // Créer une entrée vidéo de test très courte (1 frame noir)
-    // NVENC nécessite une résolution minimale (généralement 128x128 ou plus)
-    let mut cmd = Command::new(exe);
-    cmd.args(&[
-        "-y",
-        "-hide_banner",
-        "-loglevel", "error",
-        "-f", "lavfi",
-        "-i", "color=c=black:s=128x128:r=1:d=0.04", // Résolution minimum NVENC, très courte
-        "-c:v", "h264_nvenc",
-        "-preset", "fast",
-        "-pix_fmt", "yuv420p",
-        "-frames:v", "1",
-        "-f", "null", // Sortie nulle pour éviter d'écrire un fichier
-        "-"
-    ]);
-    
-    configure_command_no_window(&mut cmd);
-    
-    match cmd.output() {
-        Ok(output) => {
-            let success = output.status.success();
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            
-            if success {
-                println!("[nvenc_test] ✓ NVENC disponible et fonctionnel");
-                true
-            } else {
-                // Analyser les erreurs pour distinguer "pas disponible" vs "erreur de config"
-                let stderr_lower = stderr.to_lowercase();
-                
-                if stderr_lower.contains("cannot load nvcuda.dll") || 
-                   stderr_lower.contains("no nvidia devices") ||
-                   stderr_lower.contains("cuda") ||
-                   stderr_lower.contains("driver") {
-                    println!("[nvenc_test] ✗ NVENC non disponible (pas de GPU NVIDIA ou drivers manquants)");
-                    false
-                } else if stderr_lower.contains("frame dimension") {
-                    // Si c'est juste un problème de dimensions, essayer avec une plus grande résolution
-                    println!("[nvenc_test] Retry avec résolution plus grande...");
-                    test_nvenc_with_larger_resolution(ffmpeg_path)
-                } else {
-                    println!("[nvenc_test] ✗ NVENC erreur: {}", stderr.trim());
-                    false
-                }
-            }
-        }
-        Err(e) => {
-            println!("[nvenc_test] ✗ Erreur lors du test NVENC: {}", e);
-            false
-        }
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChromaKey {
+    /// Vidéo de premier plan à détourer.
+    pub video: String,
+    /// Couleur à retirer (nom ou `0xRRGGBB`).
+    pub color: String,
+    /// Tolérance autour de la couleur (0.01–1.0).
+    pub similarity: f64,
+    /// Adoucissement du bord du masque (0.0–1.0).
+    pub blend: f64,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        ChromaKey { video: String::new(), color: "0x00FF00".to_string(), similarity: 0.1, blend: 0.0 }
     }
 }
 
-fn test_nvenc_with_larger_resolution(ffmpeg_path: Option<&str>) -> bool {
-    let exe = ffmpeg_path.unwrap_or("ffmpeg");
-    
-    let mut cmd = Command::new(exe);
-    cmd.args(&[
-        "-y",
-        "-hide_banner",
-        "-loglevel", "error",
-        "-f", "lavfi",
-        "-i", "color=c=black:s=256x256:r=1:d=0.04", // Résolution encore plus grande
-        "-c:v", "h264_nvenc",
-        "-preset", "fast",
-        "-pix_fmt", "yuv420p",
-        "-frames:v", "1",
-        "-f", "null",
-        "-"
-    ]);
-    
-    configure_command_no_window(&mut cmd);
-    
-    match cmd.output() {
-        Ok(output) => {
-            let success = output.status.success();
-            if success {
-                println!("[nvenc_test] ✓ NVENC disponible avec résolution 256x256");
-            } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                println!("[nvenc_test] ✗ NVENC toujours non disponible: {}", stderr.trim());
-            }
-            success
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Flip {
+    H,
+    V,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OverlayVideo {
+    /// Vidéo d'habillage ; l'alpha vient de son format de pixels (ProRes 4444, qtrle, VP9 alpha...).
+    pub video: String,
+    /// Rejoue la vidéo en boucle jusqu'à la fin de l'export (sinon elle s'arrête à sa fin).
+    pub repeat: bool,
+    /// Apparition dans la vidéo exportée, en ms.
+    pub start_ms: u32,
+}
+
+impl Default for OverlayVideo {
+    fn default() -> Self {
+        OverlayVideo { video: String::new(), repeat: true, start_ms: 0 }
+    }
+}
+
+impl OverlayVideo {
+    fn path(&self) -> String {
+        path_utils::normalize_existing_path(&self.video).to_string_lossy().to_string()
+    }
+
+    fn input_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.repeat {
+            args.extend(["-stream_loop".to_string(), "-1".to_string()]);
         }
-        Err(e) => {
-            println!("[nvenc_test] ✗ Erreur test résolution plus grande: {}", e);
-            false
+        // Le décodeur VP9 natif ignore l'alpha : libvpx le conserve
+        if self.video.to_lowercase().ends_with(".webm") {
+            args.extend(["-c:v".to_string(), "libvpx-vp9".to_string()]);
         }
+        args.extend(["-i".to_string(), self.path()]);
+        args
+    }
+
+    // Habillage décalé à `start_ms`, posé sur `base` ; le fond reste visible après sa fin
+    fn filter(&self, input_idx: i32, base: &str, w: i32, h: i32, fps: FrameRate, duration_s: f64) -> String {
+        let start_s = self.start_ms as f64 / 1000.0;
+        format!(
+            "[{}:v]scale={}:{}:force_original_aspect_ratio=decrease,format=rgba,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setsar=1,\
+             trim=duration={:.6},setpts=PTS-STARTPTS+{:.6}/TB[motion];{}[motion]overlay=x=0:y=0:eof_action=pass,format=yuv420p[vmotion]",
+            input_idx, w, h, w, h, fps, (duration_s - start_s).max(0.0), start_s, base
+        )
     }
 }
 
-fn choose_best_codec(prefer_hw: bool) -> (String, Vec<String>, HashMap<String, Option<String>>) {
-    let ffmpeg_exe = resolve_ffmpeg_binary();
-    let mut found_hw_encoders = Vec::new();
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Stamp {
+    pub text: String,
+    pub corner: Corner,
+    /// Taille de police en pixels (défaut : 1/30 de la hauteur).
+    pub size: Option<u32>,
+    /// Couleur FFmpeg (nom ou `0xRRGGBB`, `@alpha` accepté), blanc par défaut.
+    pub color: Option<String>,
+    /// Police TTF/OTF ; sans valeur, la première police système connue est utilisée.
+    pub font: Option<String>,
+}
 
-        if prefer_hw {
-            let exe = ffmpeg_exe.as_deref().unwrap_or("ffmpeg");
-            if let Ok(output) = Command::new(exe)
-                .args(&["-hide_banner", "-encoders"])
-                .output()
-            {
-                let txt = String::from_utf8_lossy(&output.stdout).to_lowercase();
-                if txt.contains("h264_nvenc") {
-                    found_hw_encoders.push("h264_nvenc".to_string());
-                }
-                if txt.contains("h264_qsv") {
-                    found_hw_encoders.push("h264_qsv".to_string());
-                }
-                if txt.contains("h264_amf") {
-                    found_hw_encoders.push("h264_amf".to_string());
-                }
-            }
-        }
-    
-    if !found_hw_encoders.is_empty() {
-        // Tester spécifiquement NVENC s'il est détecté
-        if found_hw_encoders[0] == "h264_nvenc" {
-            if test_nvenc_availability(ffmpeg_exe.as_deref()) {
-                println!("[codec] Utilisation de NVENC (accélération GPU NVIDIA)");
-                let codec = found_hw_encoders[0].clone();
-                let params = vec![
-                    "-pix_fmt".to_string(), "yuv420p".to_string(),
-                    "-bf".to_string(), "0".to_string(),
-                ];
-                let mut extra = HashMap::new();
-                extra.insert("preset".to_string(), Some("p4".to_string()));
-                return (codec, params, extra);
-            } else {
-                println!("[codec] NVENC détecté mais non fonctionnel, fallback vers libx264");
+const INVALID_STAMP_ERROR: &str = "INVALID_STAMP";
+
+/// Polices essayées pour `drawtext` quand le tampon n'en précise pas.
+const STAMP_FONT_CANDIDATES: [&str; 4] = [
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/System/Library/Fonts/Helvetica.ttc",
+];
+
+// Échappe une valeur d'option pour un filtergraph : d'abord au niveau de l'option (`\ ' :`),
+// puis au niveau du graphe (`\ ' [ ] , ;`)
+fn escape_filter_value(value: &str) -> String {
+    let escape = |s: &str, special: &[char]| {
+        s.chars().fold(String::new(), |mut out, c| {
+            if special.contains(&c) {
+                out.push('\\');
             }
-        } else {
-            // Pour les autres encodeurs hardware (QSV, AMF), utiliser directement
-            println!("[codec] Utilisation de l'encodeur hardware: {}", found_hw_encoders[0]);
-            let codec = found_hw_encoders[0].clone();
-            let params = vec!["-pix_fmt".to_string(), "yuv420p".to_string()];
-            let mut extra = HashMap::new();
-            extra.insert("preset".to_string(), None);
-            return (codec, params, extra);
+            out.push(c);
+            out
+        })
+    };
+    escape(&escape(value, &['\\', '\'', ':']), &['\\', '\'', '[', ']', ',', ';'])
+}
+
+impl Stamp {
+    /// Filtre `drawtext` plaçant le tampon dans son coin d'une vidéo `w`×`h`.
+    fn drawtext_filter(&self, w: i32, h: i32) -> Result<String, String> {
+        if self.text.trim().is_empty() {
+            return Err(format!("{}: texte vide", INVALID_STAMP_ERROR));
         }
+        let font = match &self.font {
+            Some(path) if Path::new(path).exists() => path.clone(),
+            Some(path) => return Err(format!("{}: police introuvable {}", INVALID_STAMP_ERROR, path)),
+            None => STAMP_FONT_CANDIDATES
+                .iter()
+                .find(|p| Path::new(p).exists())
+                .map(|p| p.to_string())
+                .ok_or_else(|| format!("{}: aucune police système trouvée, préciser `font`", INVALID_STAMP_ERROR))?,
+        };
+        let size = self.size.unwrap_or((h / 30).max(8) as u32);
+        let margin = (w.min(h) / 30).max(4);
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (format!("{}", margin), format!("{}", margin)),
+            Corner::TopRight => (format!("w-tw-{}", margin), format!("{}", margin)),
+            Corner::BottomLeft => (format!("{}", margin), format!("h-th-{}", margin)),
+            Corner::BottomRight => (format!("w-tw-{}", margin), format!("h-th-{}", margin)),
+        };
+        Ok(format!(
+            "drawtext=fontfile={}:text={}:expansion=none:fontsize={}:fontcolor={}:x={}:y={}",
+            escape_filter_value(&font),
+            escape_filter_value(&self.text),
+            size,
+            escape_filter_value(self.color.as_deref().unwrap_or("white")),
+            x, y
+        ))
     }
-    
-    // Fallback libx264
-    println!("[codec] Utilisation de libx264 (encodage logiciel)");
-    let codec = "libx264".to_string();
-    let params = vec![
-        "-pix_fmt".to_string(), "yuv420p".to_string(),
-        "-crf".to_string(), "22".to_string(),
-        "-tune".to_string(), "zerolatency".to_string(),
-        "-bf".to_string(), "0".to_string(),
-    ];
-    let mut extra = HashMap::new();
-    extra.insert("preset".to_string(), Some("ultrafast".to_string()));
-    
-    (codec, params, extra)
 }
 
-fn ffmpeg_preprocess_video(src: &str, dst: &str, w: i32, h: i32, fps: i32, prefer_hw: bool, start_ms: Option<i32>, duration_ms: Option<i32>, blur: Option<f64>) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let (codec, params, extra) = choose_best_codec(prefer_hw);
-    let exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+/// Pipeline de rendu d'`export_video`.
+///
+/// `fidelity` compose chaque frame sur le GPU (WGPU) : rendu identique à l'aperçu, animations,
+/// texte et habillages, mais exige un adaptateur graphique. `fast` construit un graphe FFmpeg
+/// (fondus linéaires par clip, aucun GPU requis) : adapté aux machines headless ou à GPU faible,
+/// sans les options propres au rendu WGPU (`output_size`, `subtitle_sampling`...).
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportMode {
+    #[default]
+    Fidelity,
+    Fast,
+}
 
-    // Construire le filtre vidéo avec blur optionnel
-    let mut vf_parts = vec![
-        format!("scale=w={}:h={}:force_original_aspect_ratio=decrease", w, h),
-        format!("pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black", w, h),
-    ];
-    
-    // Ajouter le flou si spécifié et > 0
-    if let Some(blur_value) = blur {
-        if blur_value > 0.0 {
-            vf_parts.push(format!("gblur=sigma={}", blur_value));
+/// `sigma` : flou en pixels (historique), `height` : fraction de la hauteur de l'image,
+/// convertie en sigma selon la taille cible pour garder le même rendu à toute résolution.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlurUnit {
+    #[default]
+    Sigma,
+    Height,
+}
+
+/// `pad` complète avec du noir (historique), `loop` reboucle le fond jusqu'à la fin.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundMode {
+    #[default]
+    Pad,
+    Loop,
+}
+
+impl ExportOptions {
+    fn watchdog_timeout(&self) -> Option<Duration> {
+        match self.watchdog_timeout_s.unwrap_or(DEFAULT_WATCHDOG_TIMEOUT_S) {
+            0 => None,
+            s => Some(Duration::from_secs(s)),
         }
     }
-    
-    vf_parts.push(format!("fps={}", fps));
-    vf_parts.push("setsar=1".to_string());
-    
-    let vf = vf_parts.join(",");
 
-    let mut cmd = Command::new(&exe);
-
-    // Si un offset de début est fourni, l'ajouter avant -i pour seek rapide
-    if let Some(sms) = start_ms {
-        let s = format!("{:.3}", (sms as f64) / 1000.0);
-        cmd.arg("-ss").arg(s);
+    fn progress_interval(&self) -> Duration {
+        Duration::from_millis(self.progress_interval_ms.unwrap_or(DEFAULT_PROGRESS_INTERVAL_MS))
     }
+}
 
-    cmd.arg("-y")
-        .arg("-hide_banner")
-        .arg("-loglevel").arg("error")
-        .arg("-fflags").arg("+genpts")
-        .arg("-avoid_negative_ts").arg("make_zero")
-        .arg("-vsync").arg("cfr")
-        .arg("-i").arg(src);
+const DEFAULT_WATCHDOG_TIMEOUT_S: u64 = 120;
 
-    // Si une durée de découpe est fournie, la limiter
-    if let Some(dms) = duration_ms {
-        let d = format!("{:.3}", (dms as f64) / 1000.0);
-        cmd.arg("-t").arg(d);
-    }
+const DEFAULT_PROGRESS_INTERVAL_MS: u64 = 250;
 
-    let gop = fps * 2;
-    cmd.arg("-an")
-        .arg("-vf").arg(&vf)
-        .arg("-pix_fmt").arg("yuv420p")
-        .arg("-c:v").arg(&codec)
-        .arg("-g").arg(gop.to_string());
+// Limite le débit des événements `export-progress` : au plus un par intervalle, sauf le dernier
+struct ProgressThrottle {
+    interval: Duration,
+    last: Option<Instant>,
+}
 
-    if let Some(Some(preset)) = extra.get("preset") {
-        cmd.arg("-preset").arg(preset);
+impl ProgressThrottle {
+    fn new(interval: Duration) -> Self {
+        Self { interval, last: None }
     }
 
-    for param in params {
-        cmd.arg(param);
+    // `force` : fin d'encodage, toujours émise pour que l'interface atteigne 100 %
+    fn ready(&mut self, force: bool) -> bool {
+        let due = force || !matches!(self.last, Some(t) if t.elapsed() < self.interval);
+        if due {
+            self.last = Some(Instant::now());
+        }
+        due
     }
+}
+const TIMEOUT_ERROR: &str = "TIMEOUT";
 
-    cmd.arg(dst);
+// Chien de garde : si aucun battement (`beat`) n'arrive pendant `timeout` alors qu'il est armé,
+// les processus surveillés sont tués et `fired()` passe à vrai. Le thread s'arrête au drop.
+struct Watchdog {
+    last_beat: Arc<Mutex<Instant>>,
+    armed: Arc<AtomicBool>,
+    fired: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
 
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut cmd);
+impl Watchdog {
+    fn spawn(timeout: Option<Duration>, children: Vec<crate::renderer::SharedChild>) -> Self {
+        let watchdog = Watchdog {
+            last_beat: Arc::new(Mutex::new(Instant::now())),
+            armed: Arc::new(AtomicBool::new(false)),
+            fired: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        let Some(timeout) = timeout else {
+            return watchdog;
+        };
 
-    println!("[preproc] ffmpeg scale+pad -> {}", Path::new(dst).file_name().unwrap_or_default().to_string_lossy());
+        let (last_beat, armed, fired, stop) = (
+            watchdog.last_beat.clone(),
+            watchdog.armed.clone(),
+            watchdog.fired.clone(),
+            watchdog.stop.clone(),
+        );
+        std::thread::spawn(move || {
+            let tick = (timeout / 4).min(Duration::from_secs(1));
+            while !stop.load(Ordering::SeqCst) {
+                std::thread::sleep(tick);
+                let idle = last_beat.lock().map(|t| t.elapsed()).unwrap_or_default();
+                if armed.load(Ordering::SeqCst) && idle >= timeout {
+                    log_out!("[watchdog] Aucune progression depuis {:.0}s, arrêt de FFmpeg", idle.as_secs_f64());
+                    fired.store(true, Ordering::SeqCst);
+                    for child in &children {
+                        kill_child(child);
+                    }
+                    break;
+                }
+            }
+        });
+        watchdog
+    }
 
-    let status = cmd.status()?;
-    if !status.success() {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "FFmpeg preprocessing failed")));
+    fn beat(&self) {
+        if let Ok(mut t) = self.last_beat.lock() {
+            *t = Instant::now();
+        }
     }
 
-    Ok(())
+    // Arme le chien de garde jusqu'au drop du garde retourné
+    fn arm(&self) -> WatchdogArm<'_> {
+        self.beat();
+        self.armed.store(true, Ordering::SeqCst);
+        WatchdogArm(self)
+    }
+
+    fn fired(&self) -> bool {
+        self.fired.load(Ordering::SeqCst)
+    }
 }
 
-fn create_video_from_image(image_path: &str, output_path: &str, w: i32, h: i32, fps: i32, duration_s: f64, prefer_hw: bool, blur: Option<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
-    
-    // Construire le filtre vidéo avec blur optionnel
-    let mut vf_parts = vec![
-        format!("scale={}:{}:force_original_aspect_ratio=increase", w, h),
-        format!("crop={}:{}:(in_w-{})/2:(in_h-{})/2", w, h, w, h),
-    ];
-    
-    // Ajouter le flou si spécifié et > 0
-    if let Some(blur_value) = blur {
-        if blur_value > 0.0 {
-            vf_parts.push(format!("gblur=sigma={}", blur_value));
-        }
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
     }
-    
-    let video_filter = vf_parts.join(",");
-    
-    // Choisir le meilleur codec avec détection automatique
-    let (codec, codec_params, codec_extra) = choose_best_codec(prefer_hw);
-    
-    let mut cmd = Command::new(&ffmpeg_exe);
-    cmd.args(&[
-        "-y",
-        "-hide_banner", 
-        "-loglevel", "info",
-        "-fflags", "+genpts",
-        "-avoid_negative_ts", "make_zero",
-        "-vsync", "cfr",
-        "-loop", "1",
-        "-i", image_path,
-        "-vf", &video_filter,
-        "-c:v", &codec,
-        "-r", &fps.to_string(),
-        "-g", &(fps * 2).to_string(),
-        "-t", &format!("{:.6}", duration_s),
-    ]);
-    
-    // Ajouter le preset si disponible
-    if let Some(Some(preset)) = codec_extra.get("preset") {
-        cmd.arg("-preset").arg(preset);
-    }
-    
-    // Ajouter les paramètres du codec
-    for param in codec_params {
-        cmd.arg(param);
-    }
-    
-    // Ajouter des paramètres de qualité selon le codec
-    if codec == "libx264" {
-        cmd.args(&["-crf", "23"]);
-    } else if codec.contains("nvenc") {
-        cmd.args(&["-cq", "23"]);
-    }
-    
-    cmd.arg(output_path);
-
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut cmd);
+}
 
-    println!("[preproc][IMG] Création vidéo depuis image: {} -> {}", image_path, output_path);
-    println!("[preproc][IMG] Commande: {:?}", cmd);
+struct WatchdogArm<'a>(&'a Watchdog);
 
-    let status = cmd.status()?;
-    if !status.success() {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "FFmpeg image-to-video failed")));
+impl Drop for WatchdogArm<'_> {
+    fn drop(&mut self) {
+        self.0.armed.store(false, Ordering::SeqCst);
     }
-
-    Ok(())
 }
 
-fn is_image_file(path: &str) -> bool {
-    let path_lower = path.to_lowercase();
-    path_lower.ends_with(".jpg") || path_lower.ends_with(".jpeg") || 
-    path_lower.ends_with(".png") || path_lower.ends_with(".bmp") || 
-    path_lower.ends_with(".gif") || path_lower.ends_with(".webp") ||
-    path_lower.ends_with(".tiff") || path_lower.ends_with(".tif")
-}
+impl ExportOptions {
+    fn video_codec(&self, out_path: &str) -> VideoCodec {
+        self.video_codec.unwrap_or(if output_extension(out_path) == "webm" { VideoCodec::Vp9 } else { VideoCodec::H264 })
+    }
 
-fn preprocess_background_videos(video_paths: &[String], w: i32, h: i32, fps: i32, prefer_hw: bool, start_time_ms: i32, duration_ms: Option<i32>, blur: Option<f64>) -> Vec<String> {
-    println!("[preproc] Début du prétraitement pour {} vidéos/images...", video_paths.len());
-    let mut out_paths = Vec::new();
-    let cache_dir = std::env::temp_dir().join("qurancaption-preproc");
-    fs::create_dir_all(&cache_dir).ok();
+    // 1 ou 2 ; toute autre valeur retombe sur la stéréo
+    fn audio_channels(&self) -> u32 {
+        match self.audio_channels {
+            Some(1) => 1,
+            _ => 2,
+        }
+    }
 
-    // Cas spécial : une seule image
-    if video_paths.len() == 1 && is_image_file(&video_paths[0]) {
-        let image_path = &video_paths[0];
-        let duration_s = if let Some(dur_ms) = duration_ms { 
-            dur_ms as f64 / 1000.0 
-        } else { 
-            30.0 // Durée par défaut si non spécifiée
-        };
+    fn audio_sample_rate(&self) -> Result<u32, String> {
+        match self.audio_sample_rate.unwrap_or(48000) {
+            rate @ (44100 | 48000) => Ok(rate),
+            other => Err(format!("{}: {} Hz (valeurs acceptées : 44100, 48000)", UNSUPPORTED_SAMPLE_RATE_ERROR, other)),
+        }
+    }
 
-        // Construire un nom de cache unique pour l'image
-        let blur_suffix = if let Some(b) = blur {
-            if b > 0.0 { format!("-blur{}", b) } else { String::new() }
-        } else { String::new() };
-        let hash_input = format!("{}-{}x{}-{}-dur{}{}-hw{}", image_path, w, h, fps, duration_s, blur_suffix, prefer_hw);
-        let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
-        let stem_hash = &stem_hash[..10.min(stem_hash.len())];
-        let dst = cache_dir.join(format!("img-bg-{}-{}x{}-{}.mp4", stem_hash, w, h, fps));
-
-        if !dst.exists() {
-            match create_video_from_image(image_path, &dst.to_string_lossy(), w, h, fps, duration_s, prefer_hw, blur) {
-                Ok(_) => {},
-                Err(e) => {
-                    println!("[preproc][ERREUR] Impossible de créer la vidéo à partir de l'image: {:?}", e);
-                    return vec![];
-                }
-            }
+    fn log_level(&self) -> Result<String, String> {
+        let level = self.log_level.as_deref().unwrap_or("info").to_lowercase();
+        if FFMPEG_LOG_LEVELS.contains(&level.as_str()) {
+            Ok(level)
+        } else {
+            Err(format!("{}: '{}' (valeurs : {})", INVALID_LOG_LEVEL_ERROR, level, FFMPEG_LOG_LEVELS.join(", ")))
         }
+    }
 
-        out_paths.push(dst.to_string_lossy().to_string());
-        return out_paths;
+    // `None` pour « pas de -tune » (absent ou `none`)
+    fn tune(&self) -> Result<Option<String>, String> {
+        let tune = match self.tune.as_deref().map(str::to_lowercase) {
+            None => return Ok(None),
+            Some(t) if t == "none" => return Ok(None),
+            Some(t) => t,
+        };
+        if X264_TUNES.contains(&tune.as_str()) {
+            Ok(Some(tune))
+        } else {
+            Err(format!("{}: '{}' (valeurs : none, {})", INVALID_TUNE_ERROR, tune, X264_TUNES.join(", ")))
+        }
     }
 
-    // Calculer les durées (ms) de chaque vidéo
-    let mut video_durations_ms: Vec<i64> = Vec::new();
-    for p in video_paths {
-        let d = (ffprobe_duration_sec(p) * 1000.0).round() as i64;
-        video_durations_ms.push(d);
+    fn prefetch_depth(&self) -> usize {
+        self.prefetch_captions.unwrap_or(DEFAULT_PREFETCH_CAPTIONS).min(MAX_PREFETCH_CAPTIONS)
     }
 
-    // Limite de la plage demandée
-    let limit_ms: i64 = if let Some(dur) = duration_ms { dur as i64 } else { i64::MAX };
+    fn gop_size(&self, fps: FrameRate) -> Result<i32, String> {
+        let Some(interval) = self.keyframe_interval_s else {
+            return Ok(fps.rounded() as i32 * 2);
+        };
+        let gop = (fps.as_f64() * interval).round();
+        if !gop.is_finite() || gop < 1.0 {
+            return Err(format!(
+                "{}: {} s à {} fps donne moins d'une image entre images clés",
+                INVALID_KEYFRAME_INTERVAL_ERROR, interval, fps
+            ));
+        }
+        Ok(gop as i32)
+    }
 
-    // Parcourir les vidéos et extraire uniquement les segments pertinents
-    let mut cum_start: i64 = 0;
-    for (idx, p) in video_paths.iter().enumerate() {
-        let vid_len = video_durations_ms.get(idx).cloned().unwrap_or(0);
-        let cum_end = cum_start + vid_len;
+    // (entrée, sortie) du fondu du fond, en secondes
+    fn background_fade_s(&self) -> (f64, f64) {
+        let s = |ms: Option<u32>| ms.unwrap_or(0) as f64 / 1000.0;
+        (s(self.background_fade_in_ms), s(self.background_fade_out_ms))
+    }
 
-        // Si la vidéo se termine avant le début recherché, on l'ignore complètement
-        if cum_end <= start_time_ms as i64 {
-            cum_start = cum_end;
-            continue;
+    // Sigma du flou (gblur) pour un fond de `height` pixels de haut
+    fn blur_sigma(&self, blur: Option<f64>, height: i32) -> Option<f64> {
+        match self.blur_unit {
+            BlurUnit::Sigma => blur,
+            BlurUnit::Height => blur.map(|fraction| fraction * height as f64),
         }
+    }
 
-        // Si on a déjà dépassé la limite demandée, on arrête
-        let elapsed_so_far = cum_start - (start_time_ms as i64);
-        if elapsed_so_far >= limit_ms {
-            break;
+    // Valeur de flou du décodeur WGPU (boxblur de rayon `blur * 10`). En mode `height`, le rayon
+    // est choisi pour que l'écart-type du boxblur (r/√3) égale le sigma converti.
+    fn decoder_blur(&self, blur: Option<f64>, height: i32) -> f64 {
+        match self.blur_unit {
+            BlurUnit::Sigma => blur.unwrap_or(0.0),
+            BlurUnit::Height => self.blur_sigma(blur, height).map_or(0.0, |sigma| sigma * 3f64.sqrt() / 10.0),
         }
+    }
 
-        // Déterminer le début à l'intérieur de cette vidéo
-        let start_within = if start_time_ms as i64 > cum_start { start_time_ms as i64 - cum_start } else { 0 };
+    // (entrée, sortie) du fondu des sous-titres en ms, `fade_duration` servant aux deux par défaut
+    fn caption_fade_ms(&self, fade_duration_ms: i32) -> (i32, i32) {
+        let or_default = |ms: Option<u32>| ms.map_or(fade_duration_ms, |ms| ms as i32).max(0);
+        (or_default(self.fade_in_ms), or_default(self.fade_out_ms))
+    }
 
-        // Durée restante à prendre dans cette vidéo
-        let elapsed_from_start = (cum_start + start_within) - (start_time_ms as i64);
-        let remaining_needed = (limit_ms - elapsed_from_start).max(0);
-        let take_ms = remaining_needed.min(vid_len - start_within);
+    fn clip_fade(&self, fade_duration_ms: i32, fps: FrameRate) -> ClipFade {
+        let (fade_in_ms, fade_out_ms) = self.caption_fade_ms(fade_duration_ms);
+        ClipFade::new((fade_in_ms as f64 / 1000.0, fade_out_ms as f64 / 1000.0), fps, self.min_clip_ms)
+    }
 
-        if take_ms <= 0 {
-            cum_start = cum_end;
-            continue;
+    // Durées des fonds en secondes : celles fournies par l'appelant, sinon sondées
+    fn background_durations_s(&self, bg_videos: &[String]) -> Vec<f64> {
+        match &self.background_durations_ms {
+            Some(durations) => durations.iter().map(|ms| *ms as f64 / 1000.0).collect(),
+            None => ffprobe_durations_sec(bg_videos),
         }
+    }
 
-        // Construire un nom de cache unique qui inclut les offsets et le blur
-        let blur_suffix = if let Some(b) = blur {
-            if b > 0.0 { format!("-blur{}", b) } else { String::new() }
-        } else { String::new() };
-        let hash_input = format!("{}-{}x{}-{}-start{}-len{}{}-hw{}", p, w, h, fps, start_within, take_ms, blur_suffix, prefer_hw);
-        let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
-        let stem_hash = &stem_hash[..10.min(stem_hash.len())];
-        let dst = cache_dir.join(format!("bg-{}-{}x{}-{}.mp4", stem_hash, w, h, fps));
+    // Taille ramenée sous `max_dimension` en gardant les proportions (côtés pairs), ou erreur
+    fn capped_size(&self, (w, h): (i32, i32)) -> Result<(i32, i32), String> {
+        let Some(max) = self.max_dimension.filter(|m| *m > 0).map(|m| m as i32) else {
+            return Ok((w, h));
+        };
+        if w.max(h) <= max {
+            return Ok((w, h));
+        }
+        if !self.downscale_to_max_dimension {
+            return Err(format!(
+                "{}: {}x{} dépasse le plafond de {} px",
+                crate::renderer::RESOLUTION_TOO_LARGE_ERROR, w, h, max
+            ));
+        }
+        let scale = max as f64 / w.max(h) as f64;
+        let fit = |v: i32| ((v as f64 * scale).floor() as i32 / 2 * 2).max(2);
+        Ok((fit(w), fit(h)))
+    }
 
-        println!("[preproc] Traitement du segment {}/{} -> {:?}", idx + 1, video_paths.len(), dst.file_name());
+    fn subtitle_opacity(&self) -> f64 {
+        self.subtitle_opacity.unwrap_or(1.0).clamp(0.0, 1.0)
+    }
 
-        if !dst.exists() {
-            // Appeler ffmpeg_preprocess_video avec les offsets locaux
-            match ffmpeg_preprocess_video(p, &dst.to_string_lossy(), w, h, fps, prefer_hw, Some(start_within as i32), Some(take_ms as i32), blur) {
-                Ok(_) => {},
-                Err(e) => {
-                    println!("[preproc][ERREUR] {:?}", e);
-                    // En cas d'échec, utiliser la vidéo originale (et laisser ffmpeg final gérer le trim)
-                    out_paths.push(p.clone());
-                    cum_start = cum_end;
-                    continue;
-                }
-            }
+    // `None` si la couche de traduction est désactivée (suffixe vide)
+    fn translation_suffix(&self) -> Option<&str> {
+        Some(self.translation_suffix.as_deref().unwrap_or("_t")).filter(|s| !s.is_empty())
+    }
+
+    // Traitements audio par piste (décalages, boucle, canaux, débit)
+    fn audio_options(&self) -> crate::renderer::AudioOptions {
+        crate::renderer::AudioOptions {
+            loop_audio: self.audio_loop,
+            track_delay_ms: self.audio_delays_ms.clone().unwrap_or_default(),
+            codec_args: Vec::new(),
+            channels: self.audio_channels(),
+            sample_rate: self.audio_sample_rate().unwrap_or(48000),
+            output_tracks: self.audio_tracks.clone().unwrap_or_default(),
         }
+    }
+}
 
-        out_paths.push(dst.to_string_lossy().to_string());
+// Fenêtre analysée pour détecter le silence de début de piste
+const SILENCE_SCAN_S: f64 = 30.0;
 
-        // Si on a atteint la limite, on arrête
-        let elapsed_total = (cum_start + start_within + take_ms) - (start_time_ms as i64);
-        if elapsed_total >= limit_ms {
-            break;
+// Durée du silence en début de piste (silencedetect sur les premières secondes).
+// Une piste silencieuse sur toute la fenêtre, ou jusqu'à sa fin, n'est pas sautée : on ne veut pas la vider.
+fn leading_silence_s(path: &str) -> f64 {
+    let exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    let mut cmd = Command::new(&exe);
+    cmd.args(&["-hide_banner", "-nostats", "-t", &SILENCE_SCAN_S.to_string(), "-i", path])
+        .args(&["-af", "silencedetect=noise=-50dB:d=0.1", "-vn", "-f", "null", "-"]);
+    configure_command_no_window(&mut cmd);
+
+    let Ok(output) = cmd.output() else { return 0.0 };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let value_after = |line: &str, key: &str| -> Option<f64> {
+        let rest = &line[line.find(key)? + key.len()..];
+        rest.split_whitespace().next()?.parse::<f64>().ok()
+    };
+
+    let mut lines = stderr.lines();
+    let starts_silent = lines
+        .by_ref()
+        .find_map(|l| value_after(l, "silence_start:"))
+        .is_some_and(|start| start <= 0.01);
+    if !starts_silent {
+        return 0.0;
+    }
+    match lines.find_map(|l| value_after(l, "silence_end:")) {
+        Some(end) if end < SILENCE_SCAN_S - 0.1 && end < ffprobe_duration_sec(path) - 0.1 => {
+            log_out!("[audio] Silence de début détecté: {:.3}s ({})", end, path);
+            end
         }
+        _ => 0.0,
+    }
+}
 
-        cum_start = cum_end;
+// Début d'export repoussé après le silence de début de la première piste (décalage compris).
+// On décale toute la timeline plutôt que de rogner l'audio : sous-titres et audio restent alignés,
+// et la fin de l'export ne bouge pas. Sans effet si l'export commence déjà après le silence.
+fn skip_leading_silence(start_time_ms: i32, duration_ms: Option<i32>, silence_s: f64, delay_ms: u32) -> (i32, Option<i32>) {
+    let speech_ms = delay_ms as i32 + (silence_s * 1000.0).round() as i32;
+    if silence_s <= 0.0 || speech_ms <= start_time_ms {
+        return (start_time_ms, duration_ms);
     }
+    let shift = speech_ms - start_time_ms;
+    (speech_ms, duration_ms.map(|d| (d - shift).max(1)))
+}
 
-    out_paths
+// Motif de sortie pour l'export en séquence d'images, s'il y a lieu.
+// Avec le drapeau seul, `out/video.mp4` devient `out/video/%06d.png`.
+fn image_sequence_pattern(out_path: &str, options: &ExportOptions) -> Option<String> {
+    if out_path.contains('%') && out_path.to_lowercase().ends_with(".png") {
+        Some(out_path.to_string())
+    } else if options.image_sequence {
+        Some(Path::new(out_path).with_extension("").join("%06d.png").to_string_lossy().to_string())
+    } else {
+        None
+    }
 }
 
-fn ffprobe_duration_sec(path: &str) -> f64 {
-    let exe = resolve_ffprobe_binary();
-    
-    let mut cmd = Command::new(&exe);
-    cmd.args(&[
-        "-v", "error",
-        "-show_entries", "format=duration",
-        "-of", "default=nokey=1:noprint_wrappers=1",
-        path,
-    ]);
-    
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut cmd);
-    
-    let output = match cmd.output() {
-        Ok(output) => output,
-        Err(_) => return 0.0,
-    };
-    
-    let txt = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    txt.parse::<f64>().unwrap_or(0.0)
+// L'audio d'une séquence d'images est écrit à côté des frames
+fn image_sequence_audio_path(pattern: &str) -> PathBuf {
+    Path::new(pattern).parent().unwrap_or_else(|| Path::new(".")).join("audio.m4a")
 }
 
-fn video_has_audio(path: &str) -> bool {
-    let exe = resolve_ffprobe_binary();
+// Chemin de la traduction associée à `{idx}.png` (`{idx}{suffixe}.png`), si elle existe
+fn translation_path_for(image_path: &str, suffix: &str) -> Option<String> {
+    let p = Path::new(image_path);
+    let stem = p.file_stem()?.to_str()?;
+    let candidate = p.with_file_name(format!("{}{}.png", stem, suffix));
+    candidate.exists().then(|| candidate.to_string_lossy().to_string())
+}
 
-    let output = Command::new(&exe)
-        .args(&[
-            "-v", "error",
-            "-select_streams", "a",
-            "-show_entries", "stream=index",
-            "-of", "csv=p=0",
-            path,
-        ])
-        .output();
+// Poignée d'annulation d'un export actif : soit un processus FFmpeg unique,
+// soit une session de streaming WGPU (décodeur + encodeur + drapeau d'annulation)
+enum ExportHandle {
+    Process(Arc<Mutex<Option<std::process::Child>>>),
+    Streaming {
+        children: Vec<crate::renderer::SharedChild>,
+        cancelled: Arc<AtomicBool>,
+    },
+}
 
-    match output {
-        Ok(out) => !out.stdout.is_empty(),
-        Err(_) => false,
+impl ExportHandle {
+    /// Tue le(s) processus associé(s). Retourne `true` si au moins un processus était encore actif.
+    fn cancel(&self) -> bool {
+        match self {
+            ExportHandle::Process(process_ref) => kill_child(process_ref),
+            ExportHandle::Streaming { children, cancelled } => {
+                cancelled.store(true, Ordering::SeqCst);
+                let mut killed = false;
+                for child in children {
+                    killed |= kill_child(child);
+                }
+                killed
+            }
+        }
     }
-}
 
-struct ExportTimings {
-    durations_s: Vec<f64>,
-    start_s: f64,
-    duration_s: f64,
+    // PID des processus encore actifs (verrous libres : appelé juste après l'enregistrement)
+    fn pids(&self) -> Vec<u32> {
+        let children: Vec<&crate::renderer::SharedChild> = match self {
+            ExportHandle::Process(process_ref) => vec![process_ref],
+            ExportHandle::Streaming { children, .. } => children.iter().collect(),
+        };
+        children
+            .into_iter()
+            .filter_map(|c| c.try_lock().ok().and_then(|guard| guard.as_ref().map(|child| child.id())))
+            .collect()
+    }
 }
 
-fn calculate_export_timings(
-    timestamps_ms: &[i32],
-    fps: i32,
-    fade_duration_ms: i32,
-    start_time_ms: i32,
-    duration_ms: Option<i32>,
-    is_high_fidelity: bool,
-) -> ExportTimings {
-    let n = timestamps_ms.len();
-    let tail_ms = fade_duration_ms.max(1000);
-    let frame_duration = 1.0 / (fps as f64);
-    
-    let snap_time = |ms: i32| -> f64 {
-        let seconds = ms as f64 / 1000.0;
-        let frames = (seconds / frame_duration).round();
-        frames * frame_duration
+// Tue et nettoie un processus partagé. Retourne `true` s'il était encore actif.
+fn kill_child(process_ref: &crate::renderer::SharedChild) -> bool {
+    let mut child_guard = match process_ref.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
     };
-
-    let start_s = snap_time(start_time_ms);
-    let end_ms = if let Some(dur_ms) = duration_ms {
-        start_time_ms + dur_ms
+    if let Some(mut child) = child_guard.take() {
+        let _ = child.kill();
+        let _ = child.wait(); // Nettoyer
+        true
     } else {
-        timestamps_ms[n - 1] + tail_ms
+        false
+    }
+}
+
+// Gestionnaire des processus actifs pour pouvoir les annuler
+static ACTIVE_EXPORTS: LazyLock<Mutex<HashMap<String, ExportHandle>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// PID ffmpeg des exports en cours, recopiés dans un fichier du dossier de données de l'app :
+// un PID encore listé au lancement suivant vient d'une session qui a planté.
+const PID_FILE_NAME: &str = "ffmpeg-pids.txt";
+static PID_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+static TRACKED_PIDS: LazyLock<Mutex<HashMap<String, Vec<u32>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Met à jour les PID d'un export (`None` : export terminé) et réécrit le fichier
+fn track_export_pids(export_id: &str, pids: Option<Vec<u32>>) {
+    let Ok(mut tracked) = TRACKED_PIDS.lock() else { return };
+    match pids {
+        Some(pids) => tracked.insert(export_id.to_string(), pids),
+        None => tracked.remove(export_id),
     };
-    let end_s = snap_time(end_ms);
-    let duration_s_total = (end_s - start_s).max(frame_duration);
-    
-    let mut raw_durations = Vec::new();
-    for i in 0..n {
-        let t_curr = timestamps_ms[i];
-        let t_next = if i < n - 1 { timestamps_ms[i + 1] } else { timestamps_ms[i] + tail_ms };
-        let dur = (snap_time(t_next) - snap_time(t_curr)).max(0.001);
-        raw_durations.push(dur);
+    let Some(path) = PID_FILE.lock().ok().and_then(|p| p.clone()) else { return };
+    let content: String = tracked.values().flatten().map(|pid| format!("{}\n", pid)).collect();
+    if let Err(e) = fs::write(&path, content) {
+        eprintln!("[pids] ⚠️ Écriture impossible de {}: {}", path.display(), e);
     }
+}
 
-    let mut durations_s = Vec::new();
-    if is_high_fidelity {
-        durations_s = raw_durations;
-    } else {
-        // En mode Fast, on ne regroupe pas ici car build_filter_complex a besoin des labels individuels
-        durations_s = raw_durations;
+/// À appeler au démarrage : tue les ffmpeg encore actifs d'une session précédente (plantage
+/// pendant un export) puis active le suivi des PID dans `data_dir`. Renvoie le nombre de processus tués.
+pub fn reap_orphaned_ffmpeg(data_dir: &Path) -> usize {
+    let path = data_dir.join(PID_FILE_NAME);
+    let mut reaped = 0;
+    if let Ok(content) = fs::read_to_string(&path) {
+        for pid in content.lines().filter_map(|l| l.trim().parse::<u32>().ok()) {
+            // Le PID a pu être réattribué : on ne tue que des ffmpeg
+            if is_ffmpeg_process(pid) && kill_pid(pid) {
+                log_out!("[pids] Processus ffmpeg orphelin {} arrêté", pid);
+                reaped += 1;
+            }
+        }
     }
-    
-    ExportTimings {
-        durations_s,
-        start_s,
-        duration_s: duration_s_total,
+    let _ = fs::create_dir_all(data_dir);
+    let _ = fs::write(&path, "");
+    if let Ok(mut pid_file) = PID_FILE.lock() {
+        *pid_file = Some(path);
     }
+    reaped
 }
 
-struct FilterContext {
-    filter_complex: String,
-    have_audio: bool,
-    current_idx: i32,
-    bg_start_idx: i32,
-    audio_start_idx: i32,
-    total_bg_s: f64,
+fn is_ffmpeg_process(pid: u32) -> bool {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("tasklist");
+        cmd.args(["/FI", &format!("PID eq {}", pid), "/NH"]);
+        cmd
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = Command::new("ps");
+        cmd.args(["-p", &pid.to_string(), "-o", "comm="]);
+        cmd
+    };
+    configure_command_no_window(&mut cmd);
+    cmd.output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_lowercase().contains("ffmpeg"))
+        .unwrap_or(false)
 }
-fn build_filter_complex_content(
-    w: i32,
-    h: i32,
-    fps: i32,
-    fade_s: f64,
-    n: usize,
-    durations_s: &[f64],
-    start_s: f64,
-    duration_s: f64,
-    pre_videos: &[String],
-    audio_paths: &[String],
-    audio_start_idx: i32,
-    bg_start_idx: i32,
-    current_idx: i32,
-    is_streaming: bool,
-    is_high_fidelity: bool,
-) -> FilterContext {
-    let mut filter_lines = Vec::new();
-    let mut cur_idx = current_idx;
 
-    let overlay_label = if is_streaming && is_high_fidelity {
-        // Mode Linéaire (Fidélité Totale) : Le flux pipe contient déjà la séquence complète capturée à 30fps
-        filter_lines.push(format!(
-            "[0:v]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva420p[lin_overlay]",
-            w, h, w, h, fps
-        ));
-        "lin_overlay".to_string()
-    } else {
-        // Mode Rapide (Fade Linéaire) : Découpage intelligent par CLIPS logiques
-        let mut split_outputs = String::new();
-        for i in 0..n {
-            split_outputs.push_str(&format!("[b{}]", i));
-        }
-        
-        filter_lines.push(format!(
-            "[0:v]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva420p,split={}{}",
-            w, h, w, h, fps, n, split_outputs
-        ));
-        
-        // --- LOGIQUE DE GROUPEMENT PAR CLIPS ---
-        // On regroupe les segments qui pointent sur les mêmes timings (identiques)
-        struct GroupedClip {
-            input_indices: Vec<usize>,
-            pure_duration: f64,
-            pipe_start: f64,
-        }
-        let mut groups: Vec<GroupedClip> = Vec::new();
-        let mut current_pipe_pos = 0.0;
-        
-        for i in 0..n {
-            let dur = durations_s[i];
-            // Pour l'instant on garde simple: on ne regroupe que si c'est vraiment collé
-            // Mais en streaming Fast, chaque index i est une image unique
-            // Cependant, le frontend peut envoyer plusieurs fois vers des timings proches.
-            // On va traiter chaque segment comme un clip pour FFmpeg MAIS on s'assure
-            // que si le segment est la suite d'un silence ou d'un changement, FFmpeg gère.
-            
-            groups.push(GroupedClip {
-                input_indices: vec![i],
-                pure_duration: dur,
-                pipe_start: current_pipe_pos,
-            });
-            current_pipe_pos += dur;
+fn kill_pid(pid: u32) -> bool {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("taskkill");
+        cmd.args(["/F", "/PID", &pid.to_string()]);
+        cmd
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = Command::new("kill");
+        cmd.args(["-9", &pid.to_string()]);
+        cmd
+    };
+    configure_command_no_window(&mut cmd);
+    cmd.status().map(|s| s.success()).unwrap_or(false)
+}
+
+// Découpage annoncé par le frontend pour les exports en chunks (progression globale)
+struct ChunkPlan {
+    weights: Vec<f64>,
+}
+
+impl ChunkPlan {
+    /// Progression globale (0–100) : chunks précédents terminés + fraction du chunk courant
+    fn overall_progress(&self, chunk_index: usize, chunk_progress: f64) -> Option<f64> {
+        let total: f64 = self.weights.iter().sum();
+        let current = *self.weights.get(chunk_index)?;
+        if total <= 0.0 {
+            return None;
         }
+        let done: f64 = self.weights[..chunk_index].iter().sum();
+        Some(((done + current * chunk_progress.clamp(0.0, 100.0) / 100.0) / total * 100.0).min(100.0))
+    }
+}
 
-        let mut concat_inputs = String::new();
-        for (idx, group) in groups.iter().enumerate() {
-            let s = group.pipe_start;
-            let e = s + group.pure_duration;
-            let d = group.pure_duration;
-            
-            // Sécurité fondu
-            let safe_fade = fade_s.min(d / 2.0);
-            let fade_out_start = (d - safe_fade).max(0.0);
+static CHUNK_PLANS: LazyLock<Mutex<HashMap<String, ChunkPlan>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+// Rapports des chunks terminés, cumulés jusqu'à `concat_videos` qui ajoute l'étape `concat`
+static CHUNK_REPORTS: LazyLock<Mutex<HashMap<String, ExportReport>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
 
-            // On ne peut trimmer qu'un seul index b{} à la fois
-            // Note: on utilise le premier index du groupe pour l'image source
-            let src_idx = group.input_indices[0];
+// Gestionnaire des sessions de streaming actives
 
-            filter_lines.push(format!(
-                "[b{}]trim=start={:.6}:end={:.6},setpts=PTS-STARTPTS,fade=t=in:st=0:d={:.6}:alpha=1,fade=t=out:st={:.6}:d={:.6}:alpha=1[s{}]",
-                src_idx, s, e, safe_fade, fade_out_start, safe_fade, idx
-            ));
-            
-            concat_inputs.push_str(&format!("[s{}]", idx));
+pub struct WgpuStreamingSession {
+    pub renderer: Arc<TokioMutex<crate::renderer::Renderer>>,
+    pub decoder: Arc<TokioMutex<crate::renderer::VideoDecoder>>,
+    pub encoder: Arc<TokioMutex<crate::renderer::VideoEncoder>>,
+    // Habillage animé (`overlay_video`) : une frame par frame d'export à partir de `motion_start_s`
+    motion_decoder: Option<TokioMutex<crate::renderer::VideoDecoder>>,
+    motion_start_s: f64,
+    // Repères karaoké par sous-titre (`ExportOptions::karaoke`)
+    karaoke: Vec<crate::renderer::KaraokeCue>,
+    clip_fade: ClipFade,
+    pub fps: FrameRate,
+    pub is_high_fidelity: bool,
+    pub overlay_enable: bool,
+    pub overlay_color: String,
+    pub overlay_opacity: f32,
+    pub cancelled: Arc<AtomicBool>,
+    // Armé pendant chaque `send_frame` : un décodeur/encodeur bloqué est tué au bout du délai
+    watchdog: Watchdog,
+    // Rapport complété au fil des frames et finalisé par `finish_streaming_export`
+    report: Mutex<ExportReport>,
+    started: Instant,
+    // Fin de la mise en place : début de l'étape `encode` (frames reçues par `send_frame`)
+    encode_started: Instant,
+    // Sous-titres suivants décodés en tâche de fond (chemins connus via `export_video`)
+    prefetch: Option<CaptionPrefetcher>,
+    // Séquence PNG : pas de fichier vidéo à vérifier en fin d'export
+    is_image_sequence: bool,
+    // Frames de chaque sous-titre, calculées comme le graphe FFmpeg (`streaming_segment_frames`)
+    segment_frames: Vec<u32>,
+    // Fondu du fond (entrée, sortie en secondes) sur la durée totale de l'export
+    background_fade_s: (f64, f64),
+    duration_s: f64,
+    // Progression émise à la fin de chaque `send_frame`
+    app: tauri::AppHandle,
+    chunk_index: Option<i32>,
+    // Commande de l'encodeur, gardée si `write_success_log` est demandé
+    success_log_command: Option<Vec<String>>,
+    measure_loudness: bool,
+    progress_throttle: Mutex<ProgressThrottle>,
+}
+
+impl WgpuStreamingSession {
+    // Progression en frames (`max_frames` : frames prévues), limitée par `progress_interval_ms`
+    fn emit_progress(&self, export_id: &str, max_frames: Option<u64>) {
+        let frame = self.report.lock().unwrap().frames;
+        let total_frames = max_frames.unwrap_or(0);
+        let last_frame = total_frames > 0 && frame >= total_frames;
+        if !self.progress_throttle.lock().unwrap().ready(last_frame) {
+            return;
         }
-        
-        filter_lines.push(format!("{}concat=n={}:v=1:a=0[comp_overlay]", concat_inputs, groups.len()));
-        "comp_overlay".to_string()
-    };
-    
-    let mut total_bg_s = 0.0;
-    for p in pre_videos {
-        total_bg_s += ffprobe_duration_sec(p);
-    }
-    
-    let bg_label = if pre_videos.is_empty() || total_bg_s <= 1e-6 {
-        let color_full_idx = cur_idx;
-        cur_idx += 1;
-        // On ne peut pas mettre le -f lavfi ici, il sera ajouté dans le cmd builder
-        format!("{}:v", color_full_idx)
-    } else {
-        let prev = if pre_videos.len() > 1 {
-            let mut ins = String::new();
-            for (i, _) in pre_videos.iter().enumerate() {
-                ins.push_str(&format!("[{}:v]", bg_start_idx + i as i32));
-            }
-            filter_lines.push(format!("{}concat=n={}:v=1:a=0[bgcat]", ins, pre_videos.len()));
-            "bgcat".to_string()
-        } else {
-            format!("{}:v", bg_start_idx)
+        let progress = match max_frames {
+            Some(max) if max > 0 => (frame as f64 / max as f64 * 100.0).min(100.0),
+            _ => 0.0,
         };
-        
-        filter_lines.push(format!("[{}]setpts=PTS-STARTPTS,setsar=1[bgtrim]", prev));
-        let mut bl = "bgtrim".to_string();
-        
-        if total_bg_s + 1e-6 < duration_s {
-            let remain = duration_s - total_bg_s;
-            let color_pad_idx = cur_idx;
-            cur_idx += 1;
-            filter_lines.push(format!("[{}:v]setsar=1[colorpad]", color_pad_idx));
-            filter_lines.push(format!("[bgtrim][colorpad]concat=n=2:v=1:a=0[bg]"));
-            bl = "bg".to_string();
+        let current_time_s = frame as f64 / self.fps.as_f64();
+        let _ = self.app.emit(
+            "export-progress",
+            progress_event(export_id, self.chunk_index, progress, current_time_s, self.duration_s, Some(frame), total_frames),
+        );
+    }
+
+    // Échec GPU : même `export-error` que le chemin FFmpeg, l'erreur est renvoyée pour `?`
+    fn emit_error(&self, export_id: &str, error: String) -> String {
+        eprintln!("[send_frame] ❌ {}", error);
+        let mut error_data = serde_json::json!({
+            "export_id": export_id,
+            "error": error
+        });
+        if let Some(chunk_idx) = self.chunk_index {
+            error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
         }
-        bl
-    };
-    
-    filter_lines.push(format!("[{}][{}]overlay=shortest=1:x=0:y=0,format=yuv420p[vout]", bg_label, overlay_label));
-    
-    let mut total_audio_s = 0.0;
-    for p in audio_paths {
-        total_audio_s += ffprobe_duration_sec(p);
+        let _ = self.app.emit("export-error", error_data);
+        error
     }
-    let have_audio = !audio_paths.is_empty() && start_s < total_audio_s - 1e-6;
+}
 
-    if have_audio {
-        let a = audio_paths.len();
-        if a == 1 {
-            let a_idx = format!("{}:a", audio_start_idx);
-            filter_lines.push(format!("[{}]aresample=48000[aa0]", a_idx));
-            filter_lines.push(format!("[aa0]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[aout]", start_s, duration_s));
-        } else {
-            for j in 0..a {
-                let idx = audio_start_idx + j as i32;
-                filter_lines.push(format!("[{}:a]aresample=48000[aa{}]", idx, j));
-            }
-            let mut ins = String::new();
-            for j in 0..a {
-                ins.push_str(&format!("[aa{}]", j));
+/// Rapport de fin d'export, émis avec `export-complete` et renvoyé par la commande.
+#[derive(serde::Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub output_path: String,
+    pub codec: String,
+    pub hardware_accelerated: bool,
+    /// L'encodeur matériel était demandé mais indisponible : repli sur libx264.
+    pub cpu_fallback: bool,
+    /// Backend wgpu effectivement utilisé (`vulkan`, `dx12`, `metal`, `gl`...).
+    pub gpu_backend: String,
+    pub file_size_bytes: Option<u64>,
+    pub duration_s: f64,
+    pub frames: u64,
+    /// Cadence effective (images/s), éventuellement fractionnaire avec `match_source_fps` (29.97) :
+    /// c'est elle, et non la cadence demandée, qui doit servir à compter les frames envoyées.
+    pub fps: f64,
+    pub wall_clock_s: f64,
+    /// Durée de chaque étape en secondes : `scan`, `preprocess`, `setup`, `encode`, `finalize`,
+    /// `loudness`, et `concat` pour un export en chunks (étapes des chunks cumulées).
+    pub stage_timings: BTreeMap<String, f64>,
+    /// Sonie intégrée (EBU R128) de l'audio final, si `measure_loudness`.
+    pub integrated_lufs: Option<f64>,
+    /// True peak de l'audio final en dBTP, si `measure_loudness`.
+    pub true_peak_dbtp: Option<f64>,
+    /// Sonie demandée pour un export en chunks : mesurée après la concaténation.
+    #[serde(skip)]
+    loudness_after_concat: bool,
+}
+
+impl ExportReport {
+    fn record_stage(&mut self, stage: &str, since: Instant) {
+        self.record_duration(stage, since.elapsed().as_secs_f64());
+    }
+
+    fn record_duration(&mut self, stage: &str, elapsed_s: f64) {
+        log_out!("[perf] Étape {} : {:.3}s", stage, elapsed_s);
+        self.stage_timings.insert(stage.to_string(), elapsed_s);
+    }
+
+    // Ajoute le rapport d'un chunk au cumul : la sonie d'un chunk ne vaut pas pour la vidéo finale
+    fn accumulate(&mut self, chunk: &ExportReport) {
+        self.duration_s += chunk.duration_s;
+        self.frames += chunk.frames;
+        self.wall_clock_s += chunk.wall_clock_s;
+        self.cpu_fallback |= chunk.cpu_fallback;
+        self.loudness_after_concat |= chunk.loudness_after_concat;
+        for (stage, elapsed_s) in &chunk.stage_timings {
+            *self.stage_timings.entry(stage.clone()).or_insert(0.0) += elapsed_s;
+        }
+    }
+
+    fn record_loudness(&mut self) {
+        let started = Instant::now();
+        match measure_loudness(&self.output_path) {
+            Some((lufs, peak)) => {
+                log_out!("[loudness] {:.1} LUFS, true peak {:.1} dBTP", lufs, peak);
+                self.integrated_lufs = Some(lufs);
+                self.true_peak_dbtp = Some(peak);
             }
-            filter_lines.push(format!("{}concat=n={}:v=0:a=1[aacat]", ins, a));
-            filter_lines.push(format!("[aacat]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[aout]", start_s, duration_s));
+            None => log_out!("[loudness] Aucune mesure (pas d'audio ou analyse impossible)"),
         }
+        self.record_stage("loudness", started);
     }
-    
-    FilterContext {
-        filter_complex: filter_lines.join(";"),
-        have_audio,
-        current_idx: cur_idx,
-        bg_start_idx,
-        audio_start_idx,
-        total_bg_s,
+}
+
+// Mesure EBU R128 de la première piste audio : (sonie intégrée en LUFS, true peak en dBTP)
+fn measure_loudness(path: &str) -> Option<(f64, f64)> {
+    if !has_stream(path, "a") {
+        return None;
     }
+    let exe = resolve_ffmpeg_binary()?;
+    let mut cmd = Command::new(&exe);
+    cmd.args(["-hide_banner", "-nostats", "-i", path, "-map", "0:a:0", "-af", "ebur128=peak=true", "-f", "null", "-"]);
+    configure_command_no_window(&mut cmd);
+    let output = cmd.output().ok()?;
+    parse_ebur128_summary(&String::from_utf8_lossy(&output.stderr))
 }
 
-#[allow(clippy::too_many_arguments)]
-fn build_and_run_ffmpeg_filter_complex(
-    export_id: &str,
-    out_path: &str,
-    image_paths: &[String],
-    timestamps_ms: &[i32],
-    target_size: (i32, i32),
-    fps: i32,
-    fade_duration_ms: i32,
-    start_time_ms: i32,
-    audio_paths: &[String],
-    bg_videos: &[String],
-    prefer_hw: bool,
-    imgs_cwd: Option<&str>,
-    duration_ms: Option<i32>,
-    chunk_index: Option<i32>,
-    blur: Option<f64>,
-    app_handle: tauri::AppHandle,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let (w, h) = target_size;
-    let fade_s = (fade_duration_ms as f64 / 1000.0).max(0.0);
-    
-    let n = image_paths.len();
-    if n == 0 {
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Aucune image fournie")));
+// Valeurs `I:` et `Peak:` du résumé final d'`ebur128` (le silence donne -inf)
+fn parse_ebur128_summary(stderr: &str) -> Option<(f64, f64)> {
+    let summary = &stderr[stderr.rfind("Summary:")?..];
+    let value = |section: &str, key: &str| -> Option<f64> {
+        summary[summary.find(section)?..]
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(key))
+            .and_then(|v| v.split_whitespace().next())
+            .and_then(|v| v.parse().ok())
+    };
+    Some((value("Integrated loudness:", "I:")?, value("True peak:", "Peak:")?))
+}
+
+static WGPU_STREAMS: LazyLock<Mutex<HashMap<String, Arc<WgpuStreamingSession>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+// Fonction utilitaire pour configurer les commandes et cacher les fenêtres CMD sur Windows
+fn configure_command_no_window(cmd: &mut Command) {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x00004000;
+        cmd.creation_flags(CREATE_NO_WINDOW | BELOW_NORMAL_PRIORITY_CLASS);
     }
-    
-    let timings = calculate_export_timings(timestamps_ms, fps, fade_duration_ms, start_time_ms, duration_ms, false);
-    let durations_s = timings.durations_s;
-    let start_s = timings.start_s;
-    let duration_s = timings.duration_s;
+}
 
-    let (vcodec, vparams, vextra) = choose_best_codec(prefer_hw);
-    
-    let mut pre_videos = Vec::new();
-    if !bg_videos.is_empty() {
-        pre_videos = preprocess_background_videos(bg_videos, w, h, fps, prefer_hw, start_time_ms, duration_ms, blur);
+fn resolve_ffmpeg_binary() -> Option<String> {
+    if let Some(path) = binaries::resolve_binary("ffmpeg") {
+        return Some(path);
     }
-    
-    // Préparer le fichier concat
-    let base_dir = if let Some(cwd) = imgs_cwd {
-        PathBuf::from(cwd)
-    } else {
-        std::env::temp_dir()
-    };
-    fs::create_dir_all(&base_dir).ok();
-    
-    let concat_content = image_paths.join("|");
-    let concat_hash = format!("{:x}", md5::compute(concat_content.as_bytes()));
-    let concat_path = base_dir.join(format!("images-{}.ffconcat", &concat_hash[..8]));
-    
-    let mut concat_file = fs::File::create(&concat_path)?;
-    writeln!(concat_file, "ffconcat version 1.0")?;
-    for (i, p) in image_paths.iter().enumerate() {
-        let escaped = path_utils::escape_ffconcat_path(p);
-        writeln!(concat_file, "file '{}'", escaped)?;
-        let duration_with_padding = durations_s[i] + fade_s;
-        writeln!(concat_file, "duration {:.6}", duration_with_padding)?;
+
+    // En dernier recours, utiliser ffmpeg du PATH système
+    log_out!("[ffmpeg] Tentative d'utilisation de ffmpeg du système (PATH)");
+    if let Ok(_) = std::process::Command::new("ffmpeg").arg("-version").output() {
+        log_out!("[ffmpeg] ✓ FFmpeg trouvé dans le PATH système");
+        return Some("ffmpeg".to_string());
     }
-    let escaped_last = path_utils::escape_ffconcat_path(&image_paths[n - 1]);
-    writeln!(concat_file, "file '{}'", escaped_last)?;
-    
-    let mut cmd = Vec::new();
-    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
-    cmd.extend_from_slice(&[
-        ffmpeg_exe.clone(),
-        "-y".to_string(),
-        "-hide_banner".to_string(),
-        "-loglevel".to_string(), "info".to_string(),
-        "-fflags".to_string(), "+genpts".to_string(),
-        "-avoid_negative_ts".to_string(), "make_zero".to_string(),
-        "-vsync".to_string(), "cfr".to_string(),
-        "-stats".to_string(),
-        "-progress".to_string(), "pipe:2".to_string(),
-    ]);
-    
-    let concat_name = concat_path.to_string_lossy().to_string();
-    cmd.extend_from_slice(&[
-        "-safe".to_string(), "0".to_string(),
-        "-f".to_string(), "concat".to_string(),
-        "-i".to_string(), concat_name,
-    ]);
-    
-    let mut current_idx = 1;
-    let bg_start_idx = current_idx;
-    for p in &pre_videos {
-        cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
-        current_idx += 1;
+
+    // Aucun binaire FFmpeg trouvé
+    None
+}
+
+const FFMPEG_MISSING_ERROR: &str = "FFMPEG_MISSING";
+
+// Échoue tôt et clairement sans FFmpeg, plutôt que sur une erreur de spawn enfouie dans les logs
+fn require_ffmpeg_binary() -> Result<String, String> {
+    resolve_ffmpeg_binary().ok_or_else(|| {
+        format!(
+            "{}: FFmpeg introuvable. Emplacements recherchés : {}",
+            FFMPEG_MISSING_ERROR,
+            binaries::searched_locations("ffmpeg").join(", ")
+        )
+    })
+}
+
+fn resolve_ffprobe_binary() -> String {
+    if let Some(path) = binaries::resolve_binary("ffprobe") {
+        return path;
     }
-    
-    let audio_start_idx = current_idx;
-    // On ne sait pas encore si on a de l'audio avant build_filter_complex_content
-    // mais on ajoute les entrées quand même si audio_paths n'est pas vide
-    if !audio_paths.is_empty() {
-        for p in audio_paths {
-            cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
-            current_idx += 1;
-        }
+
+    // En dernier recours, utiliser ffprobe du PATH système
+    log_out!("[ffprobe] Tentative d'utilisation de ffprobe du système (PATH)");
+    if let Ok(_) = std::process::Command::new("ffprobe").arg("-version").output() {
+        log_out!("[ffprobe] ✓ FFprobe trouvé dans le PATH système");
+        return "ffprobe".to_string();
     }
 
-    let filter_ctx = build_filter_complex_content(
-        w, h, fps, fade_s, n, &durations_s, start_s, duration_s, 
-        &pre_videos, audio_paths, audio_start_idx, bg_start_idx, current_idx, false, false
-    );
-    
-    let filter_complex = filter_ctx.filter_complex;
-    let have_audio = filter_ctx.have_audio;
-    let _final_idx = filter_ctx.current_idx;
+    // Fallback vers le binaire système
+    "ffprobe".to_string()
+}
 
-    if pre_videos.is_empty() || filter_ctx.total_bg_s <= 1e-6 {
-        cmd.extend_from_slice(&[
-            "-f".to_string(), "lavfi".to_string(),
-            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, duration_s),
-        ]);
-    } else if filter_ctx.total_bg_s + 1e-6 < duration_s {
-        let remain = duration_s - filter_ctx.total_bg_s;
-        cmd.extend_from_slice(&[
-            "-f".to_string(), "lavfi".to_string(),
-            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, remain),
-        ]);
+// Résultat du test NVENC, mémorisé pour la durée du processus (voir `reset_nvenc_cache`)
+static NVENC_AVAILABLE: Mutex<Option<bool>> = Mutex::new(None);
+// Résolutions (côté carré) essayées tour à tour tant que NVENC refuse la taille de frame
+static NVENC_TEST_RESOLUTIONS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+const DEFAULT_NVENC_TEST_RESOLUTIONS: [u32; 2] = [128, 256];
+
+#[tauri::command]
+pub fn set_nvenc_test_resolutions(sizes: Vec<u32>) {
+    *NVENC_TEST_RESOLUTIONS.lock().unwrap() = sizes;
+}
+
+/// Oublie le résultat mémorisé : le prochain export relance le test d'encodage.
+#[tauri::command]
+pub fn reset_nvenc_cache() {
+    *NVENC_AVAILABLE.lock().unwrap() = None;
+}
+
+/// Teste si NVENC est réellement disponible en essayant un encodage rapide (une seule fois par processus)
+fn test_nvenc_availability(ffmpeg_path: Option<&str>) -> bool {
+    let mut cached = NVENC_AVAILABLE.lock().unwrap();
+    if let Some(available) = *cached {
+        return available;
     }
+    let available = probe_nvenc(ffmpeg_path);
+    *cached = Some(available);
+    available
+}
+
+fn probe_nvenc(ffmpeg_path: Option<&str>) -> bool {
+    let exe = ffmpeg_path.unwrap_or("ffmpeg");
+    let sizes = {
+        let sizes = NVENC_TEST_RESOLUTIONS.lock().unwrap();
+        if sizes.is_empty() { DEFAULT_NVENC_TEST_RESOLUTIONS.to_vec() } else { sizes.clone() }
+    };
     
-    let tmp_dir = std::env::temp_dir();
-    let fg_path = tmp_dir.join(format!("filter-{}.ffgraph", &format!("{:x}", md5::compute(filter_complex.as_bytes()))[..8]));
+    log_out!("[nvenc_test] Test de disponibilité NVENC...");
+    
+    for size in sizes {
+        // Entrée de test très courte (1 frame noir) ; NVENC impose une résolution minimale
+        let mut cmd = Command::new(exe);
+        cmd.args(&[
+            "-y",
+            "-hide_banner",
+            "-loglevel", "error",
+            "-f", "lavfi",
+            "-i", &format!("color=c=black:s={}x{}:r=1:d=0.04", size, size),
+            "-c:v", "h264_nvenc",
+            "-preset", "fast",
+            "-pix_fmt", "yuv420p",
+            "-frames:v", "1",
+            "-f", "null", // Sortie nulle pour éviter d'écrire un fichier
+            "-"
+        ]);
+        
+        configure_command_no_window(&mut cmd);
+        
+        match cmd.output() {
+            Ok(output) => {
+                if output.status.success() {
+                    log_out!("[nvenc_test] ✓ NVENC disponible et fonctionnel ({}x{})", size, size);
+                    return true;
+                }
+                // Analyser les erreurs pour distinguer "pas disponible" vs "erreur de config"
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let stderr_lower = stderr.to_lowercase();
+                
+                if stderr_lower.contains("cannot load nvcuda.dll") || 
+                   stderr_lower.contains("no nvidia devices") ||
+                   stderr_lower.contains("cuda") ||
+                   stderr_lower.contains("driver") {
+                    log_out!("[nvenc_test] ✗ NVENC non disponible (pas de GPU NVIDIA ou drivers manquants)");
+                    return false;
+                } else if stderr_lower.contains("frame dimension") {
+                    // Problème de dimensions uniquement : essayer la résolution suivante
+                    log_out!("[nvenc_test] {}x{} refusée, retry avec la résolution suivante...", size, size);
+                } else {
+                    log_out!("[nvenc_test] ✗ NVENC erreur: {}", stderr.trim());
+                    return false;
+                }
+            }
+            Err(e) => {
+                log_out!("[nvenc_test] ✗ Erreur lors du test NVENC: {}", e);
+                return false;
+            }
+        }
+    }
+    log_out!("[nvenc_test] ✗ NVENC toujours non disponible aux résolutions testées");
+    false
+}
+
+// Remplace la valeur de `-bf` choisie par `choose_best_codec` (ou l'ajoute si l'encodeur n'en fixe pas)
+fn apply_bframes(params: &mut Vec<String>, bframes: Option<u8>) {
+    let Some(bf) = bframes else { return };
+    match params.iter().position(|p| p == "-bf") {
+        Some(i) if i + 1 < params.len() => params[i + 1] = bf.to_string(),
+        _ => params.extend(["-bf".to_string(), bf.to_string()]),
+    }
+}
+
+// Ajoute `-tune` si l'encodeur connaît ce réglage : tous ceux de x264 pour libx264, une partie
+// pour libx265. Ailleurs (encodeurs matériels, VP9, AV1) il est ignoré avec un avertissement.
+fn apply_tune(codec: &str, params: &mut Vec<String>, tune: Option<String>) {
+    let Some(tune) = tune else { return };
+    let supported = match codec {
+        "libx264" => X264_TUNES.contains(&tune.as_str()),
+        "libx265" => X265_TUNES.contains(&tune.as_str()),
+        _ => false,
+    };
+    if !supported {
+        eprintln!("[codec] ⚠️ -tune {} ignoré : non supporté par {}", tune, codec);
+        return;
+    }
+    match params.iter().position(|p| p == "-tune") {
+        Some(i) if i + 1 < params.len() => params[i + 1] = tune,
+        _ => params.extend(["-tune".to_string(), tune]),
+    }
+}
+
+// Encodeurs H.264 matériels listés par `ffmpeg -encoders`, par ordre de préférence
+fn detect_hw_encoders(ffmpeg_exe: Option<&str>) -> Vec<String> {
+    let mut found_hw_encoders = Vec::new();
+    let exe = ffmpeg_exe.unwrap_or("ffmpeg");
+    if let Ok(output) = Command::new(exe)
+        .args(&["-hide_banner", "-encoders"])
+        .output()
+    {
+        let txt = String::from_utf8_lossy(&output.stdout).to_lowercase();
+        for enc in ["h264_nvenc", "h264_qsv", "h264_amf"] {
+            if txt.contains(enc) {
+                found_hw_encoders.push(enc.to_string());
+            }
+        }
+    }
+    found_hw_encoders
+}
+
+// Encodeur retenu pour l'export (`codec-selected`) ; `fallback` : matériel demandé mais indisponible.
+// Le frontend affiche ainsi pourquoi l'export est lent.
+fn emit_codec_selected(app_handle: &tauri::AppHandle, export_id: &str, codec: &str, fallback: bool) {
+    let _ = app_handle.emit("codec-selected", serde_json::json!({
+        "exportId": export_id,
+        "codec": codec,
+        "hardwareAccelerated": !codec.starts_with("lib"),
+        "fallback": fallback,
+    }));
+}
+
+fn choose_best_codec(prefer_hw: bool) -> (String, Vec<String>, HashMap<String, Option<String>>) {
+    let ffmpeg_exe = resolve_ffmpeg_binary();
+    let found_hw_encoders = if prefer_hw { detect_hw_encoders(ffmpeg_exe.as_deref()) } else { Vec::new() };
+    
+    if !found_hw_encoders.is_empty() {
+        // Tester spécifiquement NVENC s'il est détecté
+        if found_hw_encoders[0] == "h264_nvenc" {
+            if test_nvenc_availability(ffmpeg_exe.as_deref()) {
+                log_out!("[codec] Utilisation de NVENC (accélération GPU NVIDIA)");
+                let codec = found_hw_encoders[0].clone();
+                let params = vec![
+                    "-pix_fmt".to_string(), "yuv420p".to_string(),
+                    "-bf".to_string(), "0".to_string(),
+                ];
+                let mut extra = HashMap::new();
+                extra.insert("preset".to_string(), Some("p4".to_string()));
+                return (codec, params, extra);
+            } else {
+                log_out!("[codec] NVENC détecté mais non fonctionnel, fallback vers libx264");
+            }
+        } else {
+            // Pour les autres encodeurs hardware (QSV, AMF), utiliser directement
+            log_out!("[codec] Utilisation de l'encodeur hardware: {}", found_hw_encoders[0]);
+            let codec = found_hw_encoders[0].clone();
+            let params = vec!["-pix_fmt".to_string(), "yuv420p".to_string()];
+            let mut extra = HashMap::new();
+            extra.insert("preset".to_string(), None);
+            return (codec, params, extra);
+        }
+    }
+    
+    // Fallback libx264
+    log_out!("[codec] Utilisation de libx264 (encodage logiciel)");
+    let codec = "libx264".to_string();
+    let params = vec![
+        "-pix_fmt".to_string(), "yuv420p".to_string(),
+        "-crf".to_string(), "22".to_string(),
+    ];
+    let mut extra = HashMap::new();
+    extra.insert("preset".to_string(), Some("ultrafast".to_string()));
+    
+    (codec, params, extra)
+}
+
+/// Encodeur logiciel d'un codec autre que H.264, au format de `choose_best_codec`
+fn software_codec(codec: VideoCodec) -> (String, Vec<String>, HashMap<String, Option<String>>) {
+    let (name, params, preset) = codec.software_encoder();
+    log_out!("[codec] Utilisation de {} (encodage logiciel, pas d'encodeur matériel pour ce codec)", name);
+    let mut extra = HashMap::new();
+    extra.insert("preset".to_string(), preset);
+    (name.to_string(), params, extra)
+}
+
+/// Codec sans perte pour les chunks intermédiaires : évite de cumuler deux passes
+/// avec perte (chunk puis concat) aux jonctions
+fn lossless_chunk_codec() -> (String, Vec<String>, HashMap<String, Option<String>>) {
+    log_out!("[codec] Chunk intermédiaire sans perte (libx264 -qp 0)");
+    let params = vec![
+        "-pix_fmt".to_string(), "yuv420p".to_string(),
+        "-qp".to_string(), "0".to_string(),
+    ];
+    let mut extra = HashMap::new();
+    extra.insert("preset".to_string(), Some("ultrafast".to_string()));
+    ("libx264".to_string(), params, extra)
+}
+
+// Filtres d'habillage du fond (flou, vignette), ajoutés après le scale/pad
+fn background_effect_filters(blur: Option<f64>, options: &ExportOptions) -> Vec<String> {
+    let mut parts = Vec::new();
+
+    // Ajouter le flou si spécifié et > 0
+    if let Some(blur_value) = blur {
+        if blur_value > 0.0 {
+            parts.push(format!("gblur=sigma={}", blur_value));
+        }
+    }
+
+    // Vignette : 0.0 → aucun effet, 1.0 → angle maximal (PI/2)
+    if let Some(v) = options.vignette {
+        if v > 0.0 {
+            parts.push(format!("vignette=angle={:.4}", v.min(1.0) * std::f64::consts::FRAC_PI_2));
+        }
+    }
+
+    // Assombrissement uniforme des trois canaux
+    if let Some(d) = options.dim {
+        if d > 0.0 {
+            let k = 1.0 - d.min(1.0);
+            parts.push(format!("colorchannelmixer=rr={k:.4}:gg={k:.4}:bb={k:.4}"));
+        }
+    }
+
+    parts
+}
+
+// Filtres d'orientation (rotation/miroir), placés avant le scale
+fn background_orientation_filters(options: &ExportOptions) -> Vec<String> {
+    let mut parts = Vec::new();
+    match options.rotate.map(|r| r.rem_euclid(360)) {
+        Some(90) => parts.push("transpose=clock".to_string()),
+        Some(180) => parts.push("hflip,vflip".to_string()),
+        Some(270) => parts.push("transpose=cclock".to_string()),
+        Some(0) | None => {}
+        Some(other) => log_out!("[preproc] Rotation {}° ignorée (0/90/180/270 uniquement)", other),
+    }
+    match options.flip {
+        Some(Flip::H) => parts.push("hflip".to_string()),
+        Some(Flip::V) => parts.push("vflip".to_string()),
+        None => {}
+    }
+    parts
+}
+
+// Suffixe de clé de cache correspondant à `background_effect_filters`
+fn background_effects_cache_suffix(blur: Option<f64>, options: &ExportOptions) -> String {
+    let mut suffix = String::new();
+    if let Some(b) = blur {
+        if b > 0.0 { suffix.push_str(&format!("-blur{}", b)); }
+    }
+    if let Some(v) = options.vignette {
+        if v > 0.0 { suffix.push_str(&format!("-vig{}", v)); }
+    }
+    if let Some(d) = options.dim {
+        if d > 0.0 { suffix.push_str(&format!("-dim{}", d)); }
+    }
+    if let Some(r) = options.rotate.map(|r| r.rem_euclid(360)).filter(|r| *r != 0) {
+        suffix.push_str(&format!("-rot{}", r));
+    }
+    if let Some(f) = options.flip {
+        suffix.push_str(&format!("-flip{:?}", f));
+    }
+    // En mode auto, le résultat ne dépend que de la source : rien à ajouter
+    match options.deinterlace {
+        Some(true) => suffix.push_str("-deint"),
+        Some(false) => suffix.push_str("-nodeint"),
+        None => {}
+    }
+    if options.smooth_motion {
+        suffix.push_str("-mi");
+    }
+    if let Some(d) = options.denoise {
+        if d > 0.0 { suffix.push_str(&format!("-dn{}", d)); }
+    }
+    suffix
+}
+
+// Filtre de débruitage pour une force 0–10 ; hqdn3d déduit lui-même chroma et temporel de `luma_spatial`
+fn denoise_filter(strength: Option<f64>) -> Option<String> {
+    let s = strength.filter(|s| *s > 0.0)?.min(10.0);
+    if s <= 6.0 {
+        Some(format!("hqdn3d=luma_spatial={:.2}", s))
+    } else {
+        Some(format!("nlmeans=s={:.2}", s / 2.0))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ffmpeg_preprocess_video(src: &str, dst: &str, w: i32, h: i32, fps: FrameRate, prefer_hw: bool, start_ms: Option<i32>, duration_ms: Option<i32>, blur: Option<f64>, options: &ExportOptions) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+
+    // Chemin rapide : source déjà au format cible, sans effet ni découpe → simple copie du flux
+    if blur.unwrap_or(0.0) <= 0.0 && background_effects_cache_suffix(None, options).is_empty() {
+        if let Some(info) = probe_media(src) {
+            if source_matches_target(&info, w, h, fps, start_ms, duration_ms) {
+                log_out!("[preproc] Source déjà au format {}x{}@{}, copie du flux sans ré-encodage: {}", w, h, fps, src);
+                let mut cmd = Command::new(&exe);
+                cmd.args(["-y", "-hide_banner", "-loglevel", "error", "-i", src, "-map", "0:v:0", "-an", "-c", "copy", dst]);
+                configure_command_no_window(&mut cmd);
+                if cmd.status().map(|s| s.success()).unwrap_or(false) {
+                    return Ok(());
+                }
+                log_out!("[preproc] Copie du flux impossible, ré-encodage: {}", src);
+            }
+        }
+    }
+
+    let (codec, params, extra) = choose_best_codec(prefer_hw);
+
+    // Construire le filtre vidéo avec blur optionnel
+    let mut vf_parts = vec![
+        format!("scale=w={}:h={}:force_original_aspect_ratio=decrease", w, h),
+        format!("pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black", w, h),
+    ];
+    vf_parts.extend(background_effect_filters(blur, options));
+    vf_parts.splice(0..0, background_orientation_filters(options));
+    // Pixels non carrés (DVD, certains téléphones) : on étire d'abord à l'aspect d'affichage,
+    // sinon le fit/pad garde les dimensions stockées et l'image sort déformée
+    vf_parts.splice(0..0, square_pixel_filters());
+    // Débruiter à la résolution source, avant le scale
+    if let Some(dn) = denoise_filter(options.denoise) {
+        vf_parts.insert(0, dn);
+    }
+
+    // Désentrelacer avant le scale (sinon le peigne est étiré). Le décodage est logiciel
+    // ici, donc bwdif plutôt qu'un désentrelaceur matériel.
+    let deinterlace = options.deinterlace.unwrap_or_else(|| {
+        probe_media(src)
+            .and_then(|m| m.video)
+            .map(|v| is_interlaced(&v.field_order))
+            .unwrap_or(false)
+    });
+    if deinterlace {
+        log_out!("[preproc] Source entrelacée, ajout de bwdif: {}", src);
+        vf_parts.insert(0, "bwdif=mode=send_frame:deint=interlaced".to_string());
+    }
+    
+    // minterpolate synthétise les images intermédiaires au lieu de dupliquer (saccades 24/25 → 60 fps)
+    if options.smooth_motion {
+        vf_parts.push(format!("minterpolate=fps={}:mi_mode=mci", fps));
+    } else {
+        vf_parts.push(format!("fps={}", fps));
+    }
+    vf_parts.push("setsar=1".to_string());
+    
+    let vf = vf_parts.join(",");
+
+    let mut cmd = Command::new(&exe);
+
+    // Si un offset de début est fourni, l'ajouter avant -i pour seek rapide (et après -i en mode précis)
+    let (seek_before, seek_after) = start_ms
+        .map(|sms| seek_args(sms, options.accurate_seek))
+        .unwrap_or_default();
+    cmd.args(&seek_before);
+
+    cmd.arg("-y")
+        .arg("-hide_banner")
+        .arg("-loglevel").arg("error")
+        .arg("-fflags").arg("+genpts")
+        .arg("-avoid_negative_ts").arg("make_zero")
+        .arg("-vsync").arg("cfr")
+        .arg("-i").arg(src)
+        .args(&seek_after);
+
+    // Si une durée de découpe est fournie, la limiter
+    if let Some(dms) = duration_ms {
+        let d = format!("{:.3}", (dms as f64) / 1000.0);
+        cmd.arg("-t").arg(d);
+    }
+
+    let gop = options.gop_size(fps)?;
+    cmd.arg("-an")
+        .arg("-vf").arg(&vf)
+        .arg("-pix_fmt").arg("yuv420p")
+        .arg("-c:v").arg(&codec)
+        .arg("-g").arg(gop.to_string());
+
+    if let Some(Some(preset)) = extra.get("preset") {
+        cmd.arg("-preset").arg(preset);
+    }
+
+    for param in params {
+        cmd.arg(param);
+    }
+
+    cmd.arg(dst);
+
+    // Configurer la commande pour cacher les fenêtres CMD sur Windows
+    configure_command_no_window(&mut cmd);
+
+    log_out!("[preproc] ffmpeg scale+pad -> {}", Path::new(dst).file_name().unwrap_or_default().to_string_lossy());
+
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "FFmpeg preprocessing failed")));
+    }
+
+    Ok(())
+}
+
+// Ramène une source anamorphique à des pixels carrés (sans effet si le SAR vaut déjà 1:1)
+fn square_pixel_filters() -> Vec<String> {
+    vec!["scale=w=trunc(iw*sar/2)*2:h=ih".to_string(), "setsar=1".to_string()]
+}
+
+// Pré-roll du seek précis : saut rapide jusqu'à `start - pré-roll`, puis décodage jusqu'à l'instant exact
+const ACCURATE_SEEK_PREROLL_MS: i32 = 5000;
+
+// Arguments `-ss` à placer (avant `-i`, après `-i`). Le seek rapide seul s'aligne sur un keyframe
+// et peut décaler le fond de l'audio jusqu'à un GOP ; le mode précis découpe le seek en deux.
+fn seek_args(start_ms: i32, accurate: bool) -> (Vec<String>, Vec<String>) {
+    let secs = |ms: i32| format!("{:.3}", (ms as f64) / 1000.0);
+    if !accurate {
+        return (vec!["-ss".to_string(), secs(start_ms)], Vec::new());
+    }
+    let coarse = (start_ms - ACCURATE_SEEK_PREROLL_MS).max(0);
+    let mut before = Vec::new();
+    if coarse > 0 {
+        before = vec!["-ss".to_string(), secs(coarse)];
+    }
+    (before, vec!["-ss".to_string(), secs(start_ms - coarse)])
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_video_from_image(image_path: &str, output_path: &str, w: i32, h: i32, fps: FrameRate, duration_s: f64, prefer_hw: bool, blur: Option<f64>, options: &ExportOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    
+    // Construire le filtre vidéo avec blur optionnel
+    let mut vf_parts = vec![
+        format!("scale={}:{}:force_original_aspect_ratio=increase", w, h),
+        format!("crop={}:{}:(in_w-{})/2:(in_h-{})/2", w, h, w, h),
+    ];
+    vf_parts.extend(background_effect_filters(blur, options));
+    vf_parts.splice(0..0, background_orientation_filters(options));
+    
+    let video_filter = vf_parts.join(",");
+    
+    // Choisir le meilleur codec avec détection automatique
+    let (codec, codec_params, codec_extra) = choose_best_codec(prefer_hw);
+    
+    let mut cmd = Command::new(&ffmpeg_exe);
+    cmd.args(&[
+        "-y",
+        "-hide_banner", 
+        "-loglevel", "info",
+        "-fflags", "+genpts",
+        "-avoid_negative_ts", "make_zero",
+        "-vsync", "cfr",
+        "-loop", "1",
+        "-i", image_path,
+        "-vf", &video_filter,
+        "-c:v", &codec,
+        "-r", &fps.to_string(),
+        "-g", &options.gop_size(fps)?.to_string(),
+        "-t", &format!("{:.6}", duration_s),
+    ]);
+    
+    // Ajouter le preset si disponible
+    if let Some(Some(preset)) = codec_extra.get("preset") {
+        cmd.arg("-preset").arg(preset);
+    }
+    
+    // Ajouter les paramètres du codec
+    for param in codec_params {
+        cmd.arg(param);
+    }
+    
+    // Ajouter des paramètres de qualité selon le codec
+    if codec == "libx264" {
+        cmd.args(&["-crf", "23"]);
+    } else if codec.contains("nvenc") {
+        cmd.args(&["-cq", "23"]);
+    }
+    
+    cmd.arg(output_path);
+
+    // Configurer la commande pour cacher les fenêtres CMD sur Windows
+    configure_command_no_window(&mut cmd);
+
+    log_out!("[preproc][IMG] Création vidéo depuis image: {} -> {}", image_path, output_path);
+    log_out!("[preproc][IMG] Commande: {:?}", cmd);
+
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "FFmpeg image-to-video failed")));
+    }
+
+    Ok(())
+}
+
+fn is_image_file(path: &str) -> bool {
+    let path_lower = path.to_lowercase();
+    path_lower.ends_with(".jpg") || path_lower.ends_with(".jpeg") || 
+    path_lower.ends_with(".png") || path_lower.ends_with(".bmp") || 
+    path_lower.ends_with(".gif") || path_lower.ends_with(".webp") ||
+    path_lower.ends_with(".tiff") || path_lower.ends_with(".tif")
+}
+
+// Construit un clip "bouclable" de durée L - c : la fin du fond (c secondes) est fondue
+// dans son début, de sorte que la fin du clip s'enchaîne sans coupure sur son propre début.
+fn make_seamless_loop(pre_videos: &[String], total_s: f64, crossfade_s: f64, prefer_hw: bool) -> Result<String, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    if crossfade_s <= 0.0 || total_s <= crossfade_s * 2.0 {
+        return Err(format!("Fond trop court ({:.2}s) pour un fondu de boucle de {:.2}s", total_s, crossfade_s).into());
+    }
+
+    let cache_dir = preproc_cache_dir();
+    fs::create_dir_all(&cache_dir)?;
+    let hash_input = format!("{}-xfade{}-hw{}", pre_videos.join("|"), crossfade_s, prefer_hw);
+    let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
+    let dst = cache_dir.join(format!("loop-{}.mp4", &stem_hash[..10]));
+    if dst.exists() {
+        return Ok(dst.to_string_lossy().to_string());
+    }
+
+    let exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    let mut cmd = Command::new(&exe);
+    cmd.arg("-y").arg("-hide_banner").arg("-loglevel").arg("error");
+    if pre_videos.len() > 1 {
+        let list_path = cache_dir.join(format!("loop-{}.ffconcat", &stem_hash[..10]));
+        let mut list = fs::File::create(&list_path)?;
+        writeln!(list, "ffconcat version 1.0")?;
+        for p in pre_videos {
+            writeln!(list, "file '{}'", path_utils::escape_ffconcat_path(p))?;
+        }
+        cmd.arg("-safe").arg("0").arg("-f").arg("concat").arg("-i").arg(&list_path);
+    } else {
+        cmd.arg("-i").arg(&pre_videos[0]);
+    }
+
+    let c = crossfade_s;
+    let filter = format!(
+        "[0:v]setpts=PTS-STARTPTS,split=3[a][b][m];\
+         [a]trim=start={tail:.6},setpts=PTS-STARTPTS[tail];\
+         [b]trim=end={c:.6},setpts=PTS-STARTPTS[head];\
+         [m]trim=start={c:.6}:end={tail:.6},setpts=PTS-STARTPTS[mid];\
+         [tail][head]xfade=transition=fade:duration={c:.6}:offset=0[blend];\
+         [blend][mid]concat=n=2:v=1:a=0[out]",
+        tail = total_s - c,
+        c = c,
+    );
+
+    let (codec, params, extra) = choose_best_codec(prefer_hw);
+    cmd.arg("-filter_complex").arg(&filter)
+        .arg("-map").arg("[out]")
+        .arg("-an")
+        .arg("-pix_fmt").arg("yuv420p")
+        .arg("-c:v").arg(&codec);
+    if let Some(Some(preset)) = extra.get("preset") {
+        cmd.arg("-preset").arg(preset);
+    }
+    cmd.args(&params);
+    cmd.arg(&dst);
+
+    configure_command_no_window(&mut cmd);
+    log_out!("[preproc] Boucle avec fondu de {:.2}s -> {:?}", c, dst.file_name());
+
+    let status = cmd.status()?;
+    if !status.success() {
+        let _ = fs::remove_file(&dst);
+        return Err("FFmpeg loop crossfade failed".into());
+    }
+    Ok(dst.to_string_lossy().to_string())
+}
+
+// "30000/1001" → 29.97
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    match rate.split_once('/') {
+        Some((n, d)) => {
+            let (n, d) = (n.parse::<f64>().ok()?, d.parse::<f64>().ok()?);
+            (d > 0.0).then(|| n / d)
+        }
+        None => rate.parse().ok(),
+    }
+}
+
+const SOURCE_FPS_RANGE: std::ops::RangeInclusive<i32> = 24..=60;
+const SOURCE_FPS_WARNING: &str = "SOURCE_FPS_UNUSABLE";
+
+// Cadence d'export tirée du `avg_frame_rate` d'un fond, gardée rationnelle (30000/1001 reste 29.97)
+fn source_export_fps(avg_frame_rate: &str) -> Result<FrameRate, String> {
+    let rate = FrameRate::parse(avg_frame_rate)
+        .ok_or_else(|| format!("cadence illisible ({})", avg_frame_rate))?;
+    if !SOURCE_FPS_RANGE.contains(&(rate.rounded() as i32)) {
+        return Err(format!(
+            "{:.3} fps hors de la plage {}–{}",
+            rate.as_f64(), SOURCE_FPS_RANGE.start(), SOURCE_FPS_RANGE.end()
+        ));
+    }
+    Ok(rate)
+}
+
+// Avec `match_source_fps`, la cadence du premier fond vidéo remplace `fps` ; si elle est
+// illisible ou hors plage, `fps` est gardé avec un avertissement (log + `export-warning`)
+fn resolve_export_fps(
+    app_handle: &tauri::AppHandle,
+    export_id: &str,
+    options: &ExportOptions,
+    fps: FrameRate,
+    bg_videos: &[String],
+) -> FrameRate {
+    if !options.match_source_fps {
+        return fps;
+    }
+    let Some(source) = bg_videos.iter().find(|p| !p.starts_with("synthetic:") && !is_image_file(p)) else {
+        log_out!("[fps] Aucun fond vidéo, cadence demandée conservée : {} fps", fps);
+        return fps;
+    };
+    let path = path_utils::normalize_existing_path(source).to_string_lossy().to_string();
+    let source_fps = probe_media(&path)
+        .and_then(|info| info.video)
+        .ok_or_else(|| "flux vidéo illisible".to_string())
+        .and_then(|v| source_export_fps(&v.avg_frame_rate));
+    match source_fps {
+        Ok(source_fps) => {
+            log_out!("[fps] Cadence du fond conservée : {} fps (demandé : {})", source_fps, fps);
+            source_fps
+        }
+        Err(why) => {
+            let message = format!("Cadence du fond inutilisable ({}) : export à {} fps", why, fps);
+            eprintln!("[fps] ⚠️ {}", message);
+            let _ = app_handle.emit("export-warning", serde_json::json!({
+                "exportId": export_id,
+                "code": SOURCE_FPS_WARNING,
+                "message": message,
+            }));
+            fps
+        }
+    }
+}
+
+// Le prétraitement ne changerait rien : H.264 aux mêmes dimensions et cadence, progressif, et le clip est pris en entier
+fn source_matches_target(info: &MediaInfo, w: i32, h: i32, fps: FrameRate, start_ms: Option<i32>, duration_ms: Option<i32>) -> bool {
+    let Some(v) = &info.video else { return false };
+    let same_size = v.width == w as i64 && v.height == h as i64;
+    let same_fps = parse_frame_rate(&v.frame_rate).is_some_and(|r| (r - fps.as_f64()).abs() < 0.01);
+    let whole_clip = start_ms.unwrap_or(0) == 0
+        && duration_ms.filter(|d| (*d as f64) / 1000.0 < info.duration_s - 1.0 / fps.as_f64()).is_none();
+    let square_pixels = matches!(v.sample_aspect_ratio.as_str(), "" | "1:1" | "0:1" | "N/A");
+    // Une copie garderait la rotation en métadonnée, avec des dimensions stockées non redressées
+    v.codec == "h264" && same_size && same_fps && whole_clip && square_pixels && v.rotation == 0
+        && !is_interlaced(&v.field_order) && v.pix_fmt == "yuv420p"
+}
+
+// Dossier temporaire de l'application : `<tmp>/qurancaption-<kind>`
+fn app_temp_dir(kind: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("qurancaption-{}", kind))
+}
+
+fn preproc_cache_dir() -> PathBuf {
+    app_temp_dir("preproc")
+}
+
+/// Segment de fond retenu par le prétraitement, émis avec l'événement `background-plan`.
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BackgroundSegment {
+    pub source: String,
+    /// Début (ms) du segment dans la source, et durée prise.
+    pub start_within_ms: i64,
+    pub take_ms: i64,
+    /// Clip prétraité dans le cache (un échec de prétraitement fait échouer l'export).
+    pub output: String,
+    /// Déjà présent dans le cache, sans ré-encodage.
+    pub cached: bool,
+}
+
+fn emit_background_plan(app: &tauri::AppHandle, export_id: &str, plan: &[BackgroundSegment]) {
+    for (i, s) in plan.iter().enumerate() {
+        log_out!(
+            "[preproc] Plan {}: {} [{} ms + {} ms] -> {}{}",
+            i, s.source, s.start_within_ms, s.take_ms, s.output, if s.cached { " (cache)" } else { "" }
+        );
+    }
+    let _ = app.emit("background-plan", serde_json::json!({
+        "exportId": export_id,
+        "segments": plan,
+    }));
+}
+
+const BACKGROUND_PREPROCESS_ERROR: &str = "BACKGROUND_PREPROCESS_FAILED";
+
+#[allow(clippy::too_many_arguments)]
+fn preprocess_background_videos(video_paths: &[String], w: i32, h: i32, fps: FrameRate, prefer_hw: bool, start_time_ms: i32, duration_ms: Option<i32>, blur: Option<f64>, options: &ExportOptions) -> Result<Vec<BackgroundSegment>, String> {
+    log_out!("[preproc] Début du prétraitement pour {} vidéos/images...", video_paths.len());
+    let mut plan = Vec::new();
+    let cache_dir = preproc_cache_dir();
+    fs::create_dir_all(&cache_dir).ok();
+
+    // Cas spécial : une seule image
+    if video_paths.len() == 1 && is_image_file(&video_paths[0]) {
+        let image_path = &video_paths[0];
+        let duration_s = if let Some(dur_ms) = duration_ms { 
+            dur_ms as f64 / 1000.0 
+        } else { 
+            30.0 // Durée par défaut si non spécifiée
+        };
+
+        // Construire un nom de cache unique pour l'image
+        let effects_suffix = background_effects_cache_suffix(blur, options);
+        let hash_input = format!("{}-{}x{}-{}-dur{}{}-hw{}", image_path, w, h, fps, duration_s, effects_suffix, prefer_hw);
+        let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
+        let stem_hash = &stem_hash[..10.min(stem_hash.len())];
+        let dst = cache_dir.join(format!("img-bg-{}-{}x{}-{}.mp4", stem_hash, w, h, fps.tag()));
+
+        let cached = dst.exists();
+        if !cached {
+            if let Err(e) = create_video_from_image(image_path, &dst.to_string_lossy(), w, h, fps, duration_s, prefer_hw, blur, options) {
+                log_out!("[preproc][ERREUR] Impossible de créer la vidéo à partir de l'image: {:?}", e);
+                let _ = fs::remove_file(&dst);
+                return Err(format!("{}: image {}: {}", BACKGROUND_PREPROCESS_ERROR, image_path, e));
+            }
+        }
+
+        plan.push(BackgroundSegment {
+            source: image_path.clone(),
+            start_within_ms: 0,
+            take_ms: (duration_s * 1000.0).round() as i64,
+            output: dst.to_string_lossy().to_string(),
+            cached,
+        });
+        return Ok(plan);
+    }
+
+    // Calculer les durées (ms) de chaque vidéo
+    let video_durations_ms: Vec<i64> = options
+        .background_durations_s(video_paths)
+        .into_iter()
+        .map(|d| (d * 1000.0).round() as i64)
+        .collect();
+
+    // Limite de la plage demandée
+    let limit_ms: i64 = if let Some(dur) = duration_ms { dur as i64 } else { i64::MAX };
+
+    // Parcourir les vidéos et extraire uniquement les segments pertinents
+    let mut cum_start: i64 = 0;
+    for (idx, p) in video_paths.iter().enumerate() {
+        let vid_len = video_durations_ms.get(idx).cloned().unwrap_or(0);
+        let cum_end = cum_start + vid_len;
+
+        // Si la vidéo se termine avant le début recherché, on l'ignore complètement
+        if cum_end <= start_time_ms as i64 {
+            cum_start = cum_end;
+            continue;
+        }
+
+        // Si on a déjà dépassé la limite demandée, on arrête
+        let elapsed_so_far = cum_start - (start_time_ms as i64);
+        if elapsed_so_far >= limit_ms {
+            break;
+        }
+
+        // Déterminer le début à l'intérieur de cette vidéo
+        let start_within = if start_time_ms as i64 > cum_start { start_time_ms as i64 - cum_start } else { 0 };
+
+        // Durée restante à prendre dans cette vidéo
+        let elapsed_from_start = (cum_start + start_within) - (start_time_ms as i64);
+        let remaining_needed = (limit_ms - elapsed_from_start).max(0);
+        let take_ms = remaining_needed.min(vid_len - start_within);
+
+        if take_ms <= 0 {
+            cum_start = cum_end;
+            continue;
+        }
+
+        // Construire un nom de cache unique qui inclut les offsets et les effets (flou, vignette)
+        let effects_suffix = background_effects_cache_suffix(blur, options);
+        let seek_suffix = if options.accurate_seek { "-as" } else { "" };
+        let hash_input = format!("{}-{}x{}-{}-start{}-len{}{}{}-hw{}", p, w, h, fps, start_within, take_ms, effects_suffix, seek_suffix, prefer_hw);
+        let stem_hash = format!("{:x}", md5::compute(hash_input.as_bytes()));
+        let stem_hash = &stem_hash[..10.min(stem_hash.len())];
+        let dst = cache_dir.join(format!("bg-{}-{}x{}-{}.mp4", stem_hash, w, h, fps.tag()));
+
+        log_out!("[preproc] Traitement du segment {}/{} -> {:?}", idx + 1, video_paths.len(), dst.file_name());
+
+        let segment = |output: String, cached: bool| BackgroundSegment {
+            source: p.clone(),
+            start_within_ms: start_within,
+            take_ms,
+            output,
+            cached,
+        };
+        let cached = dst.exists();
+        if !cached {
+            // Appeler ffmpeg_preprocess_video avec les offsets locaux
+            // Ne jamais substituer la source entière : sa durée décalerait tous les fonds suivants.
+            // On retente en encodage CPU (échec matériel fréquent), puis on abandonne clairement.
+            let dst_str = dst.to_string_lossy();
+            let mut result = ffmpeg_preprocess_video(p, &dst_str, w, h, fps, prefer_hw, Some(start_within as i32), Some(take_ms as i32), blur, options);
+            if let (true, Err(e)) = (prefer_hw, &result) {
+                log_out!("[preproc][ERREUR] {:?} — nouvelle tentative en encodage CPU", e);
+                let _ = fs::remove_file(&dst);
+                result = ffmpeg_preprocess_video(p, &dst_str, w, h, fps, false, Some(start_within as i32), Some(take_ms as i32), blur, options);
+            }
+            if let Err(e) = result {
+                log_out!("[preproc][ERREUR] {:?}", e);
+                let _ = fs::remove_file(&dst);
+                return Err(format!(
+                    "{}: segment {} ms + {} ms de {}: {}",
+                    BACKGROUND_PREPROCESS_ERROR, start_within, take_ms, p, e
+                ));
+            }
+        }
+
+        plan.push(segment(dst.to_string_lossy().to_string(), cached));
+
+        // Si on a atteint la limite, on arrête
+        let elapsed_total = (cum_start + start_within + take_ms) - (start_time_ms as i64);
+        if elapsed_total >= limit_ms {
+            break;
+        }
+
+        cum_start = cum_end;
+    }
+
+    Ok(plan)
+}
+
+/// Prétraite les fonds sans lancer d'export et renvoie le plan des clips mis en cache.
+/// Un export lancé ensuite avec la même taille, cadence, blur, début, durée et options
+/// retrouve ces clips dans le cache au lieu de les ré-encoder.
+#[tauri::command]
+pub async fn warm_background_cache(
+    videos: Vec<String>,
+    target_size: (i32, i32),
+    fps: FrameRate,
+    blur: Option<f64>,
+    start_time_ms: i32,
+    duration_ms: Option<i32>,
+    options: Option<ExportOptions>,
+) -> Result<Vec<BackgroundSegment>, String> {
+    require_ffmpeg_binary()?;
+    ensure_dir_writable(&preproc_cache_dir())?;
+    let options = options.unwrap_or_default();
+    let (w, h) = even_dimensions(target_size.0, target_size.1);
+    let blur = options.blur_sigma(blur, h);
+    let videos: Vec<String> = videos
+        .iter()
+        .map(|p| path_utils::normalize_existing_path(p).to_string_lossy().to_string())
+        .collect();
+    let videos = video_backgrounds(&videos, &options)?;
+    if videos.is_empty() {
+        return Ok(Vec::new());
+    }
+    log_out!("[preproc] Préchauffage du cache : {} fond(s) en {}x{}@{}", videos.len(), w, h, fps);
+
+    // Prétraitement bloquant (FFmpeg) : hors du runtime pour garder l'interface réactive
+    let prefer_hw = should_prefer_hw_encoding();
+    tokio::task::spawn_blocking(move || {
+        preprocess_background_videos(&videos, w, h, fps, prefer_hw, start_time_ms, duration_ms, blur, &options)
+    })
+    .await
+    .map_err(|e| format!("Préchauffage du cache interrompu: {}", e))?
+}
+
+fn ffprobe_duration_sec(path: &str) -> f64 {
+    let exe = resolve_ffprobe_binary();
+    
+    let mut cmd = Command::new(&exe);
+    cmd.args(&[
+        "-v", "error",
+        "-show_entries", "format=duration",
+        "-of", "default=nokey=1:noprint_wrappers=1",
+        path,
+    ]);
+    
+    // Configurer la commande pour cacher les fenêtres CMD sur Windows
+    configure_command_no_window(&mut cmd);
+    
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(_) => return 0.0,
+    };
+    
+    let txt = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    txt.parse::<f64>().unwrap_or(0.0)
+}
+
+// Nombre maximal de ffprobe lancés en parallèle
+const FFPROBE_MAX_PARALLEL: usize = 8;
+
+// Durées de plusieurs fichiers, sondées en parallèle (pool borné), dans l'ordre des chemins
+fn ffprobe_durations_sec(paths: &[String]) -> Vec<f64> {
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(FFPROBE_MAX_PARALLEL)
+        .min(paths.len());
+    if workers <= 1 {
+        return paths.iter().map(|p| ffprobe_duration_sec(p)).collect();
+    }
+
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let mut durations = vec![0.0; paths.len()];
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut probed = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let Some(p) = paths.get(i) else { break };
+                        probed.push((i, ffprobe_duration_sec(p)));
+                    }
+                    probed
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (i, d) in handle.join().unwrap_or_default() {
+                durations[i] = d;
+            }
+        }
+    });
+    durations
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct VideoStreamInfo {
+    codec: String,
+    width: i64,
+    height: i64,
+    pix_fmt: String,
+    time_base: String,
+    frame_rate: String,
+    // Cadence moyenne réelle (`r_frame_rate` est celle du conteneur, parfois très au-dessus)
+    avg_frame_rate: String,
+    field_order: String,
+    // `num:den`, vide ou `0:1` si non renseigné (pixels carrés)
+    sample_aspect_ratio: String,
+    // Rotation d'affichage en degrés, sens horaire (vidéos de téléphone : 90 ou 270)
+    rotation: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AudioStreamInfo {
+    codec: String,
+    sample_rate: i64,
+    channels: i64,
+    time_base: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MediaInfo {
+    duration_s: f64,
+    video: Option<VideoStreamInfo>,
+    audio: Option<AudioStreamInfo>,
+}
+
+impl MediaInfo {
+    /// Deux fichiers sont concaténables en `-c copy` si leurs flux ont les mêmes paramètres
+    fn same_codec_params(&self, other: &MediaInfo) -> bool {
+        self.video == other.video && self.audio == other.audio
+    }
+}
+
+// Rotation horaire d'un flux ffprobe : tag `rotate` (anciens MP4), sinon matrice d'affichage
+// des `side_data_list`, dont l'angle est compté dans le sens antihoraire
+fn stream_rotation_cw(stream: &serde_json::Value) -> i32 {
+    let tag = stream
+        .get("tags")
+        .and_then(|t| t.get("rotate"))
+        .and_then(|r| r.as_str())
+        .and_then(|r| r.trim().parse::<f64>().ok());
+    let matrix = || {
+        stream
+            .get("side_data_list")
+            .and_then(|l| l.as_array())
+            .into_iter()
+            .flatten()
+            .find_map(|d| d.get("rotation").and_then(|r| r.as_f64()))
+            .map(|r| -r)
+    };
+    let degrees = tag.or_else(matrix).unwrap_or(0.0);
+    ((degrees / 90.0).round() as i32 * 90).rem_euclid(360)
+}
+
+/// Sonde un fichier avec ffprobe (premier flux vidéo et premier flux audio)
+fn probe_media(path: &str) -> Option<MediaInfo> {
+    let exe = resolve_ffprobe_binary();
+
+    let mut cmd = Command::new(&exe);
+    cmd.args(&[
+        "-v", "error",
+        "-print_format", "json",
+        "-show_streams",
+        "-show_format",
+        path,
+    ]);
+
+    // Configurer la commande pour cacher les fenêtres CMD sur Windows
+    configure_command_no_window(&mut cmd);
+
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+
+    let str_field = |v: &serde_json::Value, key: &str| -> String {
+        v.get(key).and_then(|x| x.as_str()).unwrap_or("").to_string()
+    };
+    // ffprobe renvoie certains nombres sous forme de chaîne (sample_rate, duration)
+    let num_field = |v: &serde_json::Value, key: &str| -> Option<f64> {
+        let x = v.get(key)?;
+        x.as_f64().or_else(|| x.as_str().and_then(|s| s.parse::<f64>().ok()))
+    };
+
+    let mut info = MediaInfo::default();
+    info.duration_s = json.get("format").and_then(|f| num_field(f, "duration")).unwrap_or(0.0);
+
+    for stream in json.get("streams").and_then(|s| s.as_array()).into_iter().flatten() {
+        match stream.get("codec_type").and_then(|t| t.as_str()) {
+            Some("video") if info.video.is_none() => {
+                info.video = Some(VideoStreamInfo {
+                    codec: str_field(stream, "codec_name"),
+                    width: num_field(stream, "width").unwrap_or(0.0) as i64,
+                    height: num_field(stream, "height").unwrap_or(0.0) as i64,
+                    pix_fmt: str_field(stream, "pix_fmt"),
+                    time_base: str_field(stream, "time_base"),
+                    frame_rate: str_field(stream, "r_frame_rate"),
+                    avg_frame_rate: str_field(stream, "avg_frame_rate"),
+                    field_order: str_field(stream, "field_order"),
+                    sample_aspect_ratio: str_field(stream, "sample_aspect_ratio"),
+                    rotation: stream_rotation_cw(stream),
+                });
+            }
+            Some("audio") if info.audio.is_none() => {
+                info.audio = Some(AudioStreamInfo {
+                    codec: str_field(stream, "codec_name"),
+                    sample_rate: num_field(stream, "sample_rate").unwrap_or(0.0) as i64,
+                    channels: num_field(stream, "channels").unwrap_or(0.0) as i64,
+                    time_base: str_field(stream, "time_base"),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Some(info)
+}
+
+// field_order ffprobe : "progressive"/"unknown" ou un ordre de trames (tt, bb, tb, bt)
+fn is_interlaced(field_order: &str) -> bool {
+    matches!(field_order, "tt" | "bb" | "tb" | "bt")
+}
+
+/// Vrai si `path` contient au moins un flux du type `selector` (`a` audio, `V` vidéo hors pochettes).
+fn has_stream(path: &str, selector: &str) -> bool {
+    stream_count(path, selector) > 0
+}
+
+/// Nombre de flux du type `selector` dans `path` (0 si ffprobe échoue).
+fn stream_count(path: &str, selector: &str) -> usize {
+    let exe = resolve_ffprobe_binary();
+
+    let output = Command::new(&exe)
+        .args(&[
+            "-v", "error",
+            "-select_streams", selector,
+            "-show_entries", "stream=index",
+            "-of", "csv=p=0",
+            path,
+        ])
+        .output();
+
+    match output {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).lines().filter(|l| !l.trim().is_empty()).count(),
+        Err(_) => 0,
+    }
+}
+
+/// Durée de maintien du dernier sous-titre : valeur explicite, sinon `max(fondu de sortie, 1000)`
+fn resolve_tail_ms(fade_out_ms: i32, tail_ms: Option<i32>) -> i32 {
+    tail_ms.unwrap_or_else(|| fade_out_ms.max(1000)).max(1)
+}
+
+pub(crate) struct ExportTimings {
+    pub(crate) durations_s: Vec<f64>,
+    pub(crate) start_s: f64,
+    pub(crate) duration_s: f64,
+    // Partie de `duration_s` après le dernier sous-titre (fond seul)
+    pub(crate) end_padding_s: f64,
+}
+
+// Timeline d'un export, calée sur la grille des frames : durée de chaque sous-titre
+// (jusqu'au suivant, ou `tail_ms` pour le dernier), début et durée totale.
+pub(crate) fn calculate_export_timings(
+    timestamps_ms: &[i32],
+    fps: FrameRate,
+    fade_out_ms: i32,
+    start_time_ms: i32,
+    duration_ms: Option<i32>,
+    tail_ms: Option<i32>,
+    end_padding_ms: Option<i32>,
+) -> ExportTimings {
+    let n = timestamps_ms.len();
+    let tail_ms = resolve_tail_ms(fade_out_ms, tail_ms);
+    let end_padding_ms = end_padding_ms.unwrap_or(0).max(0);
+    let frame_duration = 1.0 / (fps.as_f64());
+    
+    let snap_time = |ms: i32| -> f64 {
+        let seconds = ms as f64 / 1000.0;
+        let frames = (seconds / frame_duration).round();
+        frames * frame_duration
+    };
+
+    let start_s = snap_time(start_time_ms);
+    let (end_ms, end_padding_ms) = if let Some(dur_ms) = duration_ms {
+        (start_time_ms + dur_ms, 0)
+    } else {
+        (timestamps_ms[n - 1] + tail_ms + end_padding_ms, end_padding_ms)
+    };
+    let end_s = snap_time(end_ms);
+    let end_padding_s = (end_s - snap_time(end_ms - end_padding_ms)).max(0.0);
+    let duration_s_total = (end_s - start_s).max(frame_duration);
+    
+    // Même découpage dans les deux modes : build_filter_complex a besoin d'une durée par sous-titre
+    // (le mode Fast regroupe ensuite les frames répétées dans `group_clips`)
+    let mut durations_s = Vec::new();
+    for i in 0..n {
+        let t_curr = timestamps_ms[i];
+        let t_next = if i < n - 1 { timestamps_ms[i + 1] } else { timestamps_ms[i] + tail_ms };
+        let dur = (snap_time(t_next) - snap_time(t_curr)).max(0.001);
+        durations_s.push(dur);
+    }
+    
+    ExportTimings {
+        durations_s,
+        start_s,
+        duration_s: duration_s_total,
+        end_padding_s,
+    }
+}
+
+// Durées des entrées ffconcat de la piste d'images. Chaque image dure exactement son clip :
+// les trims du graphe découpent la piste aux positions cumulées, donc tout rembourrage
+// (ex. `+ fade`) décalerait l'image i de i × rembourrage. Le dernier clip est ajusté pour
+// que la piste couvre exactement `duration_s` (le cap `-t`).
+fn image_track_durations(durations_s: &[f64], duration_s: f64) -> Vec<f64> {
+    let mut out = durations_s.to_vec();
+    if out.is_empty() {
+        return out;
+    }
+    let before_last: f64 = out[..out.len() - 1].iter().sum();
+    let fitted = duration_s - before_last;
+    if fitted >= 0.001 {
+        *out.last_mut().unwrap() = fitted;
+    }
+    out
+}
+
+// Nombre de frames de chaque sous-titre pour le rendu WGPU. Les frontières sont celles des trims
+// du graphe FFmpeg (durées cumulées de la piste d'images, arrondies à la frame) : les deux chemins
+// d'export changent de sous-titre sur la même frame. Le rembourrage de fin n'appartient à aucun sous-titre.
+fn streaming_segment_frames(timings: &ExportTimings, fps: FrameRate) -> Vec<u32> {
+    let track = image_track_durations(&timings.durations_s, timings.duration_s - timings.end_padding_s);
+    let mut frames = Vec::with_capacity(track.len());
+    let (mut end_s, mut prev) = (0.0, 0u64);
+    for d in track {
+        end_s += d;
+        let boundary = (end_s * fps.as_f64()).round() as u64;
+        frames.push(boundary.saturating_sub(prev) as u32);
+        prev = boundary;
+    }
+    frames
+}
+
+// Fondus d'entrée et de sortie des sous-titres, résolus par clip
+#[derive(Clone, Copy, Debug)]
+struct ClipFade {
+    in_s: f64,
+    out_s: f64,
+    min_clip_s: f64,
+    frame_s: f64,
+}
+
+impl ClipFade {
+    fn new((in_s, out_s): (f64, f64), fps: FrameRate, min_clip_ms: Option<u64>) -> Self {
+        ClipFade {
+            in_s: in_s.max(0.0),
+            out_s: out_s.max(0.0),
+            min_clip_s: min_clip_ms.unwrap_or(0) as f64 / 1000.0,
+            frame_s: 1.0 / fps.as_f64(),
+        }
+    }
+
+    // (entrée, sortie) d'un clip de `d` secondes : réduits en proportion pour tenir ensemble dans
+    // le clip (la moitié chacun s'ils sont égaux). Un fondu de moins d'une image, qui ne ferait
+    // que clignoter, est nul ; un clip trop court passe en coupe franche
+    fn for_clip(&self, d: f64) -> (f64, f64) {
+        if d < self.min_clip_s {
+            return (0.0, 0.0);
+        }
+        let scale = (d / (self.in_s + self.out_s)).min(1.0);
+        let fit = |fade: f64| if fade * scale < self.frame_s { 0.0 } else { fade * scale };
+        (fit(self.in_s), fit(self.out_s))
+    }
+}
+
+const INVALID_REVEAL_MASK_ERROR: &str = "INVALID_REVEAL_MASK";
+
+fn load_reveal_mask(path: &str) -> Result<image::GrayImage, String> {
+    image::open(path_utils::normalize_existing_path(path))
+        .map(|img| img.to_luma8())
+        .map_err(|e| format!("{}: {}: {}", INVALID_REVEAL_MASK_ERROR, path, e))
+}
+
+// Filtres `fade` d'entrée et de sortie d'un clip de `d` secondes (vide en coupe franche)
+fn clip_fade_filters(fade: ClipFade, d: f64) -> String {
+    let (in_s, out_s) = fade.for_clip(d);
+    let mut filters = String::new();
+    if in_s > 0.0 {
+        filters.push_str(&format!(",fade=t=in:st=0:d={:.6}:alpha=1", in_s));
+    }
+    if out_s > 0.0 {
+        filters.push_str(&format!(",fade=t=out:st={:.6}:d={:.6}:alpha=1", (d - out_s).max(0.0), out_s));
+    }
+    filters
+}
+
+// Opacité de la frame `i` sur `count` d'un sous-titre, comme les filtres `fade` du graphe :
+// entrée depuis 0 et sortie vers 0, chacune sur sa propre durée.
+fn clip_fade_alpha(i: u32, count: u32, fps: FrameRate, fade: ClipFade) -> f32 {
+    let d = count as f64 / fps.as_f64();
+    let (in_s, out_s) = fade.for_clip(d);
+    let t = i as f64 / fps.as_f64();
+    let ramp = |elapsed: f64, fade: f64| if fade > 0.0 { elapsed / fade } else { 1.0 };
+    ramp(t, in_s).min(ramp(d - t, out_s)).min(1.0) as f32
+}
+
+// Clip logique de la piste d'images : un trim et un fondu d'entrée/sortie
+struct GroupedClip {
+    input_indices: Vec<usize>,
+    pure_duration: f64,
+    pipe_start: f64,
+}
+
+// Sous-titres consécutifs identiques (`repeated[i]` : image i identique à i-1) fusionnés en
+// un seul clip, sans fondu de sortie/entrée entre deux frames répétées
+fn group_clips(durations_s: &[f64], repeated: &[bool]) -> Vec<GroupedClip> {
+    let mut groups: Vec<GroupedClip> = Vec::new();
+    let mut pipe_pos = 0.0;
+    for (i, &dur) in durations_s.iter().enumerate() {
+        match groups.last_mut() {
+            Some(group) if repeated.get(i).copied().unwrap_or(false) => {
+                group.input_indices.push(i);
+                group.pure_duration += dur;
+            }
+            _ => groups.push(GroupedClip { input_indices: vec![i], pure_duration: dur, pipe_start: pipe_pos }),
+        }
+        pipe_pos += dur;
+    }
+    groups
+}
+
+// Chaîne de sous-titres (fondus par clip) pour une entrée PNG donnée.
+// `prefix` distingue les labels quand plusieurs couches sont composées (ex. traduction).
+#[allow(clippy::too_many_arguments)]
+fn push_caption_chain(
+    filter_lines: &mut Vec<String>,
+    input: &str,
+    prefix: &str,
+    w: i32,
+    h: i32,
+    fps: FrameRate,
+    fade: ClipFade,
+    n: usize,
+    durations_s: &[f64],
+    repeated: &[bool],
+    is_streaming: bool,
+    is_high_fidelity: bool,
+) -> String {
+    if is_streaming && is_high_fidelity {
+        // Mode Linéaire (Fidélité Totale) : Le flux pipe contient déjà la séquence complète capturée à 30fps
+        filter_lines.push(format!(
+            "[{}]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva420p[{}lin_overlay]",
+            input, w, h, w, h, fps, prefix
+        ));
+        format!("{}lin_overlay", prefix)
+    } else if n == 1 {
+        // Un seul sous-titre : pas de split/concat, un unique trim avec fondu d'entrée,
+        // maintien (tail_ms) puis fondu de sortie
+        let d = durations_s[0];
+        filter_lines.push(format!(
+            "[{}]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva420p,trim=start=0:end={:.6},setpts=PTS-STARTPTS{}[{}comp_overlay]",
+            input, w, h, w, h, fps, d, clip_fade_filters(fade, d), prefix
+        ));
+        format!("{}comp_overlay", prefix)
+    } else {
+        // Mode Rapide (Fade Linéaire) : Découpage intelligent par CLIPS logiques
+        // Mode Fidélité : un clip par sous-titre, même répété
+        let groups = group_clips(durations_s, if is_high_fidelity { &[] } else { repeated });
+        let head = format!(
+            "[{}]format=rgba,scale=w={}:h={}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setpts=PTS-STARTPTS,setsar=1,format=yuva420p",
+            input, w, h, w, h, fps
+        );
+
+        if groups.len() <= CAPTION_SPLIT_MAX_OUTPUTS {
+            filter_lines.push(format!("{},{}", head, split_labels(prefix, "b", 0..groups.len())));
+            let concat_inputs = push_clip_trims(filter_lines, prefix, &groups, 0, 0.0, fade);
+            filter_lines.push(format!("{}concat=n={}:v=1:a=0[{}comp_overlay]", concat_inputs, groups.len(), prefix));
+            return format!("{}comp_overlay", prefix);
+        }
+
+        // Milliers de sous-titres : un split à N sorties dépasse vite les limites de FFmpeg.
+        // Sous-graphes par lots : chaque sortie du premier split est coupée à la fenêtre de son lot,
+        // puis redécoupée par clip, et les lots sont concaténés à leur tour
+        let batches: Vec<&[GroupedClip]> = groups.chunks(CAPTION_SPLIT_MAX_OUTPUTS).collect();
+        log_out!("[filter] {} clips de sous-titres répartis en {} sous-graphes", groups.len(), batches.len());
+        filter_lines.push(format!("{},{}", head, split_labels(prefix, "B", 0..batches.len())));
+        let mut batch_outputs = String::new();
+        let mut first_idx = 0;
+        for (batch_idx, batch) in batches.iter().enumerate() {
+            let last = &batch[batch.len() - 1];
+            let (start, end) = (batch[0].pipe_start, last.pipe_start + last.pure_duration);
+            filter_lines.push(format!(
+                "[{}B{}]trim=start={:.6}:end={:.6},setpts=PTS-STARTPTS,{}",
+                prefix, batch_idx, start, end, split_labels(prefix, "b", first_idx..first_idx + batch.len())
+            ));
+            let concat_inputs = push_clip_trims(filter_lines, prefix, batch, first_idx, start, fade);
+            filter_lines.push(format!("{}concat=n={}:v=1:a=0[{}c{}]", concat_inputs, batch.len(), prefix, batch_idx));
+            batch_outputs.push_str(&format!("[{}c{}]", prefix, batch_idx));
+            first_idx += batch.len();
+        }
+        filter_lines.push(format!("{}concat=n={}:v=1:a=0[{}comp_overlay]", batch_outputs, batches.len(), prefix));
+        format!("{}comp_overlay", prefix)
+    }
+}
+
+// Sorties d'un split par nœud du graphe ; au-delà, les clips sont répartis en sous-graphes
+const CAPTION_SPLIT_MAX_OUTPUTS: usize = 256;
+
+// `split=N[{prefix}{tag}i]...` pour les index donnés
+fn split_labels(prefix: &str, tag: &str, indices: std::ops::Range<usize>) -> String {
+    let mut labels = String::new();
+    for i in indices.clone() {
+        labels.push_str(&format!("[{}{}{}]", prefix, tag, i));
+    }
+    format!("split={}{}", indices.len(), labels)
+}
+
+// Un trim + fondus par clip, lus sur les sorties `[{prefix}b{first_idx + i}]` d'un flux qui
+// commence à `offset` secondes de la piste. Renvoie les labels à concaténer.
+fn push_clip_trims(
+    filter_lines: &mut Vec<String>,
+    prefix: &str,
+    groups: &[GroupedClip],
+    first_idx: usize,
+    offset: f64,
+    fade: ClipFade,
+) -> String {
+    let mut concat_inputs = String::new();
+    for (i, group) in groups.iter().enumerate() {
+        let idx = first_idx + i;
+        let s = group.pipe_start - offset;
+        let e = s + group.pure_duration;
+        let d = group.pure_duration;
+
+        // Une sortie du split par groupe : le trim couvre toutes les images du groupe
+        filter_lines.push(format!(
+            "[{}b{}]trim=start={:.6}:end={:.6},setpts=PTS-STARTPTS{}[{}s{}]",
+            prefix, idx, s.max(0.0), e, clip_fade_filters(fade, d), prefix, idx
+        ));
+
+        concat_inputs.push_str(&format!("[{}s{}]", prefix, idx));
+    }
+    concat_inputs
+}
+
+// Habillage des sous-titres et du fond dans le graphe FFmpeg, résolu depuis `ExportOptions`
+struct CaptionStyle {
+    blend: crate::renderer::BlendMode,
+    opacity: f64,
+    animation: crate::renderer::EnterAnimation,
+    reveal: crate::renderer::Reveal,
+    // Index de l'entrée du masque de révélation, s'il y en a un
+    reveal_mask_idx: Option<i32>,
+    // Fondus d'entrée et de sortie du fond (s)
+    bg_fade_s: (f64, f64),
+}
+
+impl CaptionStyle {
+    fn from_options(options: &ExportOptions, reveal_mask_idx: Option<i32>) -> Self {
+        CaptionStyle {
+            blend: options.blend_mode,
+            opacity: options.subtitle_opacity(),
+            animation: options.enter_animation,
+            reveal: options.reveal,
+            reveal_mask_idx,
+            bg_fade_s: options.background_fade_s(),
+        }
+    }
+}
+
+struct FilterContext {
+    filter_complex: String,
+    have_audio: bool,
+    current_idx: i32,
+    bg_start_idx: i32,
+    audio_start_idx: i32,
+    total_bg_s: f64,
+}
+
+fn scale_caption_alpha(filter_lines: &mut Vec<String>, label: String, opacity: f64) -> String {
+    if opacity >= 1.0 {
+        return label;
+    }
+    filter_lines.push(format!("[{}]format=rgba,colorchannelmixer=aa={:.4}[{}_op]", label, opacity, label));
+    format!("{}_op", label)
+}
+
+// Prolonge la couche de sous-titres par des frames transparentes jusqu'à `duration_s`
+// (rembourrage de fin) : sans cela, `overlay=shortest=1` couperait la vidéo au dernier sous-titre.
+fn pad_caption_tail(filter_lines: &mut Vec<String>, label: String, pad_s: f64) -> String {
+    if pad_s <= 1e-3 {
+        return label;
+    }
+    filter_lines.push(format!("[{}]tpad=stop_mode=add:stop_duration={:.6}:color=black@0[{}_pad]", label, pad_s, label));
+    format!("{}_pad", label)
+}
+
+// Fondu au noir du fond seul (entrée, sortie en secondes), avant la composition des sous-titres
+fn push_background_fade(filter_lines: &mut Vec<String>, label: String, (fade_in, fade_out): (f64, f64), duration_s: f64) -> String {
+    let mut fades = Vec::new();
+    if fade_in > 0.0 {
+        fades.push(format!("fade=t=in:st=0:d={:.6}", fade_in));
+    }
+    if fade_out > 0.0 {
+        fades.push(format!("fade=t=out:st={:.6}:d={:.6}", (duration_s - fade_out).max(0.0), fade_out));
+    }
+    if fades.is_empty() {
+        return label;
+    }
+    filter_lines.push(format!("[{}]{}[bgfade]", label, fades.join(",")));
+    "bgfade".to_string()
+}
+
+// Opacité du fond à l'instant `t` pour le rendu WGPU, comme `push_background_fade`
+fn background_fade_alpha(t: f64, (fade_in, fade_out): (f64, f64), duration_s: f64) -> f32 {
+    let mut alpha: f64 = 1.0;
+    if fade_in > 0.0 {
+        alpha = alpha.min(t / fade_in);
+    }
+    if fade_out > 0.0 {
+        alpha = alpha.min((duration_s - t) / fade_out);
+    }
+    alpha.clamp(0.0, 1.0) as f32
+}
+
+// Position `x=..:y=..` de l'overlay des sous-titres. Pour les glissements, le décalage suit la même
+// rampe que les fondus de chaque clip (somme de termes bornés, sans imbrication de if).
+fn caption_overlay_xy(animation: crate::renderer::EnterAnimation, durations_s: &[f64], fade: ClipFade, w: i32, h: i32) -> String {
+    use crate::renderer::{EnterAnimation, SLIDE_DISTANCE};
+    let distance = match animation {
+        EnterAnimation::Fade => return "x=0:y=0".to_string(),
+        EnterAnimation::SlideUp => h as f64 * SLIDE_DISTANCE as f64,
+        EnterAnimation::SlideLeft => w as f64 * SLIDE_DISTANCE as f64,
+    };
+    let terms = caption_fade_terms(durations_s, fade, "t");
+    if terms.is_empty() {
+        return "x=0:y=0".to_string();
+    }
+    let expr = format!("'{:.2}*({})'", distance, terms.join("+"));
+    match animation {
+        EnterAnimation::SlideLeft => format!("x={}:y=0", expr),
+        _ => format!("x=0:y={}", expr),
+    }
+}
+
+// Termes (un par clip, en fonction du temps `t`) valant 1 - l'opacité du fondu dans les fenêtres
+// de fondu, 0 ailleurs : leur somme suit l'entrée et la sortie de chaque clip
+fn caption_fade_terms(durations_s: &[f64], fade: ClipFade, t: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut s = 0.0;
+    for &d in durations_s {
+        let e = s + d;
+        // Les deux fenêtres tiennent ensemble dans le clip : leurs termes ne se chevauchent pas
+        let (in_s, out_s) = fade.for_clip(d);
+        if in_s > 0.0 {
+            terms.push(format!("gte({t},{:.6})*lt({t},{:.6})*max(0,1-({t}-{:.6})/{:.6})", s, e, s, in_s, t = t));
+        }
+        if out_s > 0.0 {
+            terms.push(format!("gte({t},{:.6})*lt({t},{:.6})*max(0,1-({:.6}-{t})/{:.6})", s, e, e, out_s, t = t));
+        }
+        s = e;
+    }
+    terms
+}
+
+// Révélation par balayage ou par masque (`mask_idx`, zones sombres d'abord) des couches de
+// sous-titres. La progression des fondus est rendue sur une petite image unie (l'expression,
+// longue, n'est évaluée que sur quelques pixels), seuillée par le masque avec `blend`, puis
+// multipliée à l'alpha de chaque couche (`alphaextract` / `alphamerge`)
+#[allow(clippy::too_many_arguments)]
+fn push_caption_reveal(
+    filter_lines: &mut Vec<String>,
+    layers: Vec<(String, &[f64])>,
+    (reveal, mask_idx): (crate::renderer::Reveal, Option<i32>),
+    fade: ClipFade,
+    w: i32,
+    h: i32,
+    fps: FrameRate,
+) -> Vec<String> {
+    use crate::renderer::{Reveal, REVEAL_SOFTNESS};
+    let mask = match (mask_idx, reveal) {
+        (Some(idx), _) => format!("[{}:v]scale={}:{},format=gray", idx, w, h),
+        (None, Reveal::Fade) => return layers.into_iter().map(|(label, _)| label).collect(),
+        (None, wipe) => {
+            let threshold = match wipe {
+                Reveal::WipeUp => "1-Y/H",
+                Reveal::WipeDown => "Y/H",
+                Reveal::WipeLeft => "1-X/W",
+                _ => "X/W",
+            };
+            format!("color=c=black:s={}x{}:r={},format=gray,geq=lum='255*({})'", w, h, fps, threshold)
+        }
+    };
+    // Un masque commun ; la progression suit les clips de chaque couche (groupements distincts)
+    filter_lines.push(format!("{},{}", mask, split_labels("rv_", "m", 0..layers.len())));
+    let s = REVEAL_SOFTNESS as f64;
+    layers
+        .into_iter()
+        .enumerate()
+        .map(|(i, (label, clip_durations))| {
+            let terms = caption_fade_terms(clip_durations, fade, "T");
+            let progress = if terms.is_empty() { "1".to_string() } else { format!("1-({})", terms.join("+")) };
+            let total_s: f64 = clip_durations.iter().sum();
+            filter_lines.push(format!(
+                "color=c=black:s=16x16:r={}:d={:.6},format=gray,geq=lum='255*({})',scale={}:{}:flags=neighbor[rv_p{}]",
+                fps, total_s, progress, w, h, i
+            ));
+            filter_lines.push(format!(
+                "[rv_p{}][rv_m{}]blend=all_expr='255*clip((A/255*{:.4}-B/255)/{:.4},0,1)':shortest=1[rv_t{}]",
+                i, i, 1.0 + s, s, i
+            ));
+            filter_lines.push(format!("[{}]format=yuva420p,split[{}_rc][{}_ra]", label, label, label));
+            filter_lines.push(format!("[{}_ra]alphaextract[{}_a]", label, label));
+            filter_lines.push(format!("[{}_a][rv_t{}]blend=all_mode=multiply:shortest=1[{}_ma]", label, i, label));
+            filter_lines.push(format!("[{}_rc][{}_ma]alphamerge[{}_rv]", label, label, label));
+            format!("{}_rv", label)
+        })
+        .collect()
+}
+
+// Compose une couche de sous-titres sur `base` vers `out` (`tail` : filtres ajoutés en fin de chaîne).
+// Hors mode normal, la couche est d'abord aplatie sur un fond neutre (noir pour screen/add,
+// blanc pour multiply) afin que ses zones transparentes laissent le fond intact, puis `blend` en RGB.
+#[allow(clippy::too_many_arguments)]
+fn push_caption_composite(
+    filter_lines: &mut Vec<String>,
+    base: &str,
+    caption: &str,
+    out: &str,
+    tail: &str,
+    xy: &str,
+    blend: crate::renderer::BlendMode,
+    w: i32,
+    h: i32,
+    fps: FrameRate,
+) {
+    use crate::renderer::BlendMode;
+    let (mode, neutral) = match blend {
+        BlendMode::Normal => {
+            filter_lines.push(format!("[{}][{}]overlay=shortest=1:{}{}[{}]", base, caption, xy, tail, out));
+            return;
+        }
+        BlendMode::Screen => ("screen", "black"),
+        BlendMode::Multiply => ("multiply", "white"),
+        BlendMode::Add => ("addition", "black"),
+    };
+    filter_lines.push(format!("color=c={}:s={}x{}:r={},format=gbrap[{}_n]", neutral, w, h, fps, out));
+    filter_lines.push(format!("[{}_n][{}]overlay=shortest=1:{},format=gbrp[{}_c]", out, caption, xy, out));
+    filter_lines.push(format!("[{}]format=gbrp[{}_b]", base, out));
+    filter_lines.push(format!("[{}_b][{}_c]blend=all_mode={}:shortest=1{}[{}]", out, out, mode, tail, out));
+}
+
+fn build_filter_complex_content(
+    w: i32,
+    h: i32,
+    fps: FrameRate,
+    fade: ClipFade,
+    n: usize,
+    durations_s: &[f64],
+    repeated: &[bool],
+    start_s: f64,
+    duration_s: f64,
+    pre_videos: &[String],
+    total_bg_s: f64,
+    audio_paths: &[String],
+    audio_start_idx: i32,
+    bg_start_idx: i32,
+    current_idx: i32,
+    is_streaming: bool,
+    is_high_fidelity: bool,
+    translation: Option<(i32, &[bool])>,
+    background_mode: BackgroundMode,
+    chroma: Option<(i32, &ChromaKey)>,
+    segment_backgrounds: &[(usize, i32)],
+    audio: &crate::renderer::AudioOptions,
+    style: &CaptionStyle,
+) -> FilterContext {
+    let mut filter_lines = Vec::new();
+    let mut cur_idx = current_idx;
+
+    // En mode linéaire, l'animation est déjà capturée dans le flux
+    let linear = is_streaming && is_high_fidelity;
+    // Un clip par groupe : les frames répétées ne rejouent ni le fondu ni l'animation
+    // Chaque couche a ses propres répétitions : une traduction qui change dans un groupe garde son fondu
+    let layer_clip_durations = |repeated: &[bool]| -> Vec<f64> {
+        group_clips(durations_s, if is_high_fidelity { &[] } else { repeated })
+            .iter()
+            .map(|g| g.pure_duration)
+            .collect()
+    };
+    let clip_durations = layer_clip_durations(repeated);
+    let translation_clip_durations = translation.map(|(_, t_repeated)| layer_clip_durations(t_repeated));
+    // Balayage ou masque : les clips restent opaques, la révélation remplace leurs fondus
+    let revealing = !linear && (style.reveal != crate::renderer::Reveal::Fade || style.reveal_mask_idx.is_some());
+    let chain_fade = if revealing { ClipFade { in_s: 0.0, out_s: 0.0, ..fade } } else { fade };
+
+    let overlay_label = push_caption_chain(
+        &mut filter_lines, "0:v", "", w, h, fps, chain_fade, n, durations_s, repeated, is_streaming, is_high_fidelity,
+    );
+    let translation_label = translation.map(|(idx, t_repeated)| {
+        push_caption_chain(
+            &mut filter_lines, &format!("{}:v", idx), "t_", w, h, fps, chain_fade, n, durations_s, t_repeated, is_streaming, is_high_fidelity,
+        )
+    });
+    let (overlay_label, translation_label) = if revealing {
+        let layers = std::iter::once((overlay_label, clip_durations.as_slice()))
+            .chain(translation_label.zip(translation_clip_durations.as_deref()))
+            .collect();
+        let mut revealed = push_caption_reveal(&mut filter_lines, layers, (style.reveal, style.reveal_mask_idx), fade, w, h, fps).into_iter();
+        (revealed.next().unwrap_or_default(), revealed.next())
+    } else {
+        (overlay_label, translation_label)
+    };
+    // Opacité globale : l'alpha des sous-titres (fondus compris) est mis à l'échelle
+    let overlay_label = scale_caption_alpha(&mut filter_lines, overlay_label, style.opacity);
+    let translation_label = translation_label.map(|l| scale_caption_alpha(&mut filter_lines, l, style.opacity));
+    let pad_s = duration_s - durations_s.iter().sum::<f64>();
+    let overlay_label = pad_caption_tail(&mut filter_lines, overlay_label, pad_s);
+    let translation_label = translation_label.map(|l| pad_caption_tail(&mut filter_lines, l, pad_s));
+    
+    let bg_label = if pre_videos.is_empty() || total_bg_s <= 1e-6 {
+        let color_full_idx = cur_idx;
+        cur_idx += 1;
+        // On ne peut pas mettre le -f lavfi ici, il sera ajouté dans le cmd builder
+        format!("{}:v", color_full_idx)
+    } else if background_mode == BackgroundMode::Loop {
+        // Fond en boucle : les clips forment une seule entrée bouclée (-stream_loop -1),
+        // il suffit de couper à la durée de l'export
+        filter_lines.push(format!("[{}:v]setpts=PTS-STARTPTS,setsar=1,trim=duration={:.6}[bg]", bg_start_idx, duration_s));
+        "bg".to_string()
+    } else {
+        let prev = if pre_videos.len() > 1 {
+            let mut ins = String::new();
+            for (i, _) in pre_videos.iter().enumerate() {
+                ins.push_str(&format!("[{}:v]", bg_start_idx + i as i32));
+            }
+            filter_lines.push(format!("{}concat=n={}:v=1:a=0[bgcat]", ins, pre_videos.len()));
+            "bgcat".to_string()
+        } else {
+            format!("{}:v", bg_start_idx)
+        };
+        
+        filter_lines.push(format!("[{}]setpts=PTS-STARTPTS,setsar=1[bgtrim]", prev));
+        let mut bl = "bgtrim".to_string();
+        
+        if total_bg_s + 1e-6 < duration_s {
+            let remain = duration_s - total_bg_s;
+            let color_pad_idx = cur_idx;
+            cur_idx += 1;
+            filter_lines.push(format!("[{}:v]setsar=1[colorpad]", color_pad_idx));
+            filter_lines.push(format!("[bgtrim][colorpad]concat=n=2:v=1:a=0[bg]"));
+            bl = "bg".to_string();
+        }
+        bl
+    };
+    
+    // Fonds par sous-titre : chaque clip est décalé au début de la fenêtre du sous-titre
+    // et n'est visible que pendant celle-ci
+    let mut bg_label = bg_label;
+    for &(image_idx, input_idx) in segment_backgrounds {
+        let Some(&seg_d) = durations_s.get(image_idx) else { continue };
+        let seg_start: f64 = durations_s[..image_idx].iter().sum();
+        let seg_end = seg_start + seg_d;
+        filter_lines.push(format!(
+            "[{}:v]scale={}:{}:force_original_aspect_ratio=increase,crop={}:{},fps={},setsar=1,trim=duration={:.6},setpts=PTS-STARTPTS+{:.6}/TB[segbg{}]",
+            input_idx, w, h, w, h, fps, seg_d, seg_start, image_idx
+        ));
+        filter_lines.push(format!(
+            "[{}][segbg{}]overlay=x=0:y=0:eof_action=pass:enable='between(t,{:.6},{:.6})'[bgseg{}]",
+            bg_label, image_idx, seg_start, seg_end, image_idx
+        ));
+        bg_label = format!("bgseg{}", image_idx);
+    }
+
+    // Premier plan détouré : au-dessus du fond, sous les sous-titres. Le fond continue
+    // si le premier plan se termine avant (eof_action=pass)
+    let bg_label = if let Some((key_idx, key)) = chroma {
+        filter_lines.push(format!(
+            "[{}:v]setpts=PTS-STARTPTS,scale=w={}:h={}:force_original_aspect_ratio=decrease,format=yuva420p,chromakey=color={}:similarity={:.4}:blend={:.4},pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0,fps={},setsar=1[keyed]",
+            key_idx, w, h, key.color, key.similarity, key.blend, w, h, fps
+        ));
+        filter_lines.push(format!("[{}][keyed]overlay=x=0:y=0:eof_action=pass[bgkeyed]", bg_label));
+        "bgkeyed".to_string()
+    } else {
+        bg_label
+    };
+
+    let bg_label = push_background_fade(&mut filter_lines, bg_label, style.bg_fade_s, duration_s);
+
+    let overlay_xy = |clip_durations: &[f64]| {
+        if linear {
+            "x=0:y=0".to_string()
+        } else {
+            caption_overlay_xy(style.animation, clip_durations, fade, w, h)
+        }
+    };
+    let xy = overlay_xy(&clip_durations);
+    if let (Some(t_label), Some(t_clip_durations)) = (translation_label, &translation_clip_durations) {
+        push_caption_composite(&mut filter_lines, &bg_label, &overlay_label, "vmain", "", &xy, style.blend, w, h, fps);
+        let t_xy = overlay_xy(t_clip_durations);
+        push_caption_composite(&mut filter_lines, "vmain", &t_label, "vout", ",format=yuv420p", &t_xy, style.blend, w, h, fps);
+    } else {
+        push_caption_composite(&mut filter_lines, &bg_label, &overlay_label, "vout", ",format=yuv420p", &xy, style.blend, w, h, fps);
+    }
+    
+    let mut total_audio_s = 0.0;
+    for p in audio_paths {
+        total_audio_s += ffprobe_duration_sec(p);
+    }
+    // Les décalages allongent l'audio disponible
+    total_audio_s += audio.track_delay_ms.iter().take(audio_paths.len()).map(|d| *d as f64 / 1000.0).sum::<f64>();
+    // En boucle, une piste courte couvre tout de même la plage exportée
+    let have_audio = !audio_paths.is_empty()
+        && if audio.loop_audio { total_audio_s > 1e-6 } else { start_s < total_audio_s - 1e-6 };
+
+    if have_audio {
+        if let Some(audio_graph) = crate::renderer::audio_filter_complex(audio_paths, audio_start_idx as usize, start_s, duration_s, audio) {
+            filter_lines.push(audio_graph);
+        }
+    }
+    
+    FilterContext {
+        filter_complex: filter_lines.join(";"),
+        have_audio,
+        current_idx: cur_idx,
+        bg_start_idx,
+        audio_start_idx,
+        total_bg_s,
+    }
+}
+
+// Dossier de travail propre à un export (graphes de filtres, listes concat) : deux exports
+// lancés en même temps n'écrivent jamais dans les mêmes fichiers. Supprimé en fin d'export,
+// succès comme échec (sauf `keep_intermediates`).
+struct ExportWorkDir {
+    dir: PathBuf,
+    keep: bool,
+}
+
+impl ExportWorkDir {
+    fn create(key: &str, keep: bool) -> std::io::Result<Self> {
+        let dir = app_temp_dir("exports").join(sanitize_file_name(key));
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, keep })
+    }
+
+    fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for ExportWorkDir {
+    fn drop(&mut self) {
+        if self.keep {
+            log_out!("[debug] Dossier de travail conservé: {}", self.dir.display());
+            return;
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_and_run_ffmpeg_filter_complex(
+    export_id: &str,
+    out_path: &str,
+    image_paths: &[String],
+    timestamps_ms: &[i32],
+    target_size: (i32, i32),
+    fps: FrameRate,
+    fade_duration_ms: i32,
+    start_time_ms: i32,
+    audio_paths: &[String],
+    bg_videos: &[String],
+    prefer_hw: bool,
+    imgs_cwd: Option<&str>,
+    duration_ms: Option<i32>,
+    chunk_index: Option<i32>,
+    blur: Option<f64>,
+    options: &ExportOptions,
+    app_handle: tauri::AppHandle,
+) -> Result<ExportReport, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let (w, h) = target_size;
+    let fade = options.clip_fade(fade_duration_ms, fps);
+    
+    let n = image_paths.len();
+    if n == 0 {
+        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Aucune image fournie")));
+    }
+    
+    let sample_rate = options.audio_sample_rate()?;
+    let mut audio_codec_args = resolve_audio_codec_args(out_path, options.audio_codec, options.audio_channels(), sample_rate)?;
+    audio_codec_args.extend_from_slice(&["-ar".to_string(), sample_rate.to_string()]);
+    let fade_out_ms = options.caption_fade_ms(fade_duration_ms).1;
+    let timings = calculate_export_timings(timestamps_ms, fps, fade_out_ms, start_time_ms, duration_ms, options.tail_ms, options.end_padding_ms);
+    let start_s = timings.start_s;
+    check_audio_durations(&app_handle, export_id, audio_paths, options.fail_on_invalid_audio)?;
+    let coverage = MediaCoverage::probe(audio_paths, bg_videos, start_s, options);
+    let duration_s = check_media_coverage(&app_handle, export_id, &coverage, timings.duration_s, options.clamp_to_media);
+    // Le rembourrage de fin reste hors de la piste d'images : le graphe le comble en transparent
+    let durations_s = image_track_durations(&timings.durations_s, duration_s - timings.end_padding_s);
+
+    // Même choix que `start_streaming_export` : le codec demandé (ou déduit du conteneur) prime
+    let video_codec = options.video_codec(out_path);
+    let mut cpu_fallback = false;
+    let (vcodec, mut vparams, vextra) = if chunk_index.is_some() && options.lossless_chunks {
+        lossless_chunk_codec()
+    } else if video_codec != VideoCodec::H264 {
+        software_codec(video_codec)
+    } else {
+        let best = choose_best_codec(prefer_hw);
+        cpu_fallback = prefer_hw && best.0.starts_with("lib");
+        best
+    };
+    emit_codec_selected(&app_handle, export_id, &vcodec, cpu_fallback);
+    apply_bframes(&mut vparams, options.bframes);
+    apply_tune(&vcodec, &mut vparams, options.tune()?);
+    
+    let preprocess_started = Instant::now();
+    let bg_videos = video_backgrounds(bg_videos, options)?;
+    let mut pre_videos = Vec::new();
+    // Durée totale des fonds prétraités : celle du plan si les durées sont fournies, sinon sondée
+    let mut planned_bg_s = None;
+    if !bg_videos.is_empty() {
+        let plan = preprocess_background_videos(&bg_videos, w, h, fps, prefer_hw, start_time_ms, duration_ms, blur, options)?;
+        emit_background_plan(&app_handle, export_id, &plan);
+        if options.background_durations_ms.is_some() {
+            planned_bg_s = Some(plan.iter().map(|s| s.take_ms as f64 / 1000.0).sum::<f64>());
+        }
+        pre_videos = plan.into_iter().map(|s| s.output).collect();
+    }
+    let mut total_bg_s = planned_bg_s.unwrap_or_else(|| ffprobe_durations_sec(&pre_videos).iter().sum());
+    let preprocess_s = preprocess_started.elapsed().as_secs_f64();
+    let setup_started = Instant::now();
+    
+    // Préparer le fichier concat
+    // Les chunks d'un même export peuvent tourner en parallèle : un dossier chacun
+    let work_dir = ExportWorkDir::create(&match chunk_index {
+        Some(idx) => format!("{}-chunk{}", export_id, idx),
+        None => export_id.to_string(),
+    }, options.keep_intermediates)?;
+    if options.keep_intermediates {
+        for (i, p) in pre_videos.iter().enumerate() {
+            log_out!("[debug] Segment de fond prétraité {}: {}", i, p);
+        }
+    }
+    let base_dir = if let Some(cwd) = imgs_cwd {
+        PathBuf::from(cwd)
+    } else {
+        work_dir.path().to_path_buf()
+    };
+    fs::create_dir_all(&base_dir).ok();
+    
+    let concat_content = image_paths.join("|");
+    let concat_hash = format!("{:x}", md5::compute(concat_content.as_bytes()));
+    let concat_path = base_dir.join(format!("images-{}.ffconcat", &concat_hash[..8]));
+    
+    // Une image corrompue ne doit pas faire échouer tout l'export : on la remplace par une frame transparente.
+    // Frames répétées (contenu identique à l'image précédente) : un seul clip dans le graphe
+    let blank = || blank_frame_path(&base_dir, w, h);
+    let (image_paths, repeated, _) = scan_caption_frames(image_paths.iter().map(|p| Some(p.as_str())), "Image corrompue ignorée", blank)?;
+    let repeated_count = repeated.iter().filter(|r| **r).count();
+    if repeated_count > 0 {
+        log_out!("[timeline] {} image(s) répétée(s) regroupée(s) avec la précédente", repeated_count);
+    }
+
+    let mut concat_file = fs::File::create(&concat_path)?;
+    writeln!(concat_file, "ffconcat version 1.0")?;
+    for (i, p) in image_paths.iter().enumerate() {
+        let escaped = path_utils::escape_ffconcat_path(p);
+        writeln!(concat_file, "file '{}'", escaped)?;
+        writeln!(concat_file, "duration {:.6}", durations_s[i])?;
+    }
+    let escaped_last = path_utils::escape_ffconcat_path(&image_paths[n - 1]);
+    writeln!(concat_file, "file '{}'", escaped_last)?;
+    if options.keep_intermediates {
+        log_out!("[debug] Fichier ffconcat: {}", concat_path.display());
+    }
+
+    // Seconde couche (traduction) : même timeline, PNG transparent pour les index sans traduction
+    let translation_paths: Vec<Option<String>> = image_paths
+        .iter()
+        .map(|p| translation_path_for(p, options.translation_suffix()?))
+        .collect();
+    let (resolved, t_repeated, found) = if translation_paths.iter().any(Option::is_some) {
+        scan_caption_frames(translation_paths.iter().map(|p| p.as_deref()), "Traduction corrompue ignorée", blank)?
+    } else {
+        (Vec::new(), Vec::new(), 0)
+    };
+    let translation_concat = if found > 0 {
+        log_out!("[translation] {} traduction(s) trouvée(s) sur {} image(s)", found, n);
+
+        let t_path = base_dir.join(format!("translations-{}.ffconcat", &concat_hash[..8]));
+        let mut t_file = fs::File::create(&t_path)?;
+        writeln!(t_file, "ffconcat version 1.0")?;
+        for (i, p) in resolved.iter().enumerate() {
+            writeln!(t_file, "file '{}'", path_utils::escape_ffconcat_path(p))?;
+            writeln!(t_file, "duration {:.6}", durations_s[i])?;
+        }
+        writeln!(t_file, "file '{}'", path_utils::escape_ffconcat_path(&resolved[n - 1]))?;
+        Some((t_path, t_repeated))
+    } else {
+        None
+    };
+    
+    let mut cmd = Vec::new();
+    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    cmd.extend_from_slice(&[
+        ffmpeg_exe.clone(),
+        "-y".to_string(),
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(), options.log_level()?,
+        "-fflags".to_string(), "+genpts".to_string(),
+        "-avoid_negative_ts".to_string(), "make_zero".to_string(),
+        "-vsync".to_string(), "cfr".to_string(),
+        "-stats".to_string(),
+        "-progress".to_string(), "pipe:2".to_string(),
+    ]);
+    
+    let concat_name = concat_path.to_string_lossy().to_string();
+    cmd.extend_from_slice(&[
+        "-safe".to_string(), "0".to_string(),
+        "-f".to_string(), "concat".to_string(),
+        "-i".to_string(), concat_name,
+    ]);
+    
+    let mut current_idx = 1;
+    let translation = translation_concat.as_ref().map(|(t_path, t_repeated)| {
+        cmd.extend_from_slice(&[
+            "-safe".to_string(), "0".to_string(),
+            "-f".to_string(), "concat".to_string(),
+            "-i".to_string(), t_path.to_string_lossy().to_string(),
+        ]);
+        current_idx += 1;
+        (current_idx - 1, t_repeated.as_slice())
+    });
+    let bg_start_idx = current_idx;
+    if options.background_mode == BackgroundMode::Loop && options.loop_crossfade_ms > 0 && !pre_videos.is_empty() {
+        // Remplacer les clips par un clip bouclable (fondu fin → début) ; coupe franche en cas d'échec
+        let crossfade_s = options.loop_crossfade_ms as f64 / 1000.0;
+        match make_seamless_loop(&pre_videos, total_bg_s, crossfade_s, prefer_hw) {
+            Ok(loop_clip) => {
+                pre_videos = vec![loop_clip];
+                total_bg_s -= crossfade_s;
+            }
+            Err(e) => log_out!("[preproc][ERREUR] Fondu de boucle impossible, boucle simple: {:?}", e),
+        }
+    }
+    if options.background_mode == BackgroundMode::Loop && !pre_videos.is_empty() {
+        // Une liste ffconcat des clips de fond, rebouclée indéfiniment
+        let bg_list = pre_videos.join("|");
+        let bg_hash = format!("{:x}", md5::compute(bg_list.as_bytes()));
+        let bg_concat_path = base_dir.join(format!("backgrounds-{}.ffconcat", &bg_hash[..8]));
+        let mut bg_file = fs::File::create(&bg_concat_path)?;
+        writeln!(bg_file, "ffconcat version 1.0")?;
+        for p in &pre_videos {
+            writeln!(bg_file, "file '{}'", path_utils::escape_ffconcat_path(p))?;
+        }
+        cmd.extend_from_slice(&[
+            "-stream_loop".to_string(), "-1".to_string(),
+            "-safe".to_string(), "0".to_string(),
+            "-f".to_string(), "concat".to_string(),
+            "-i".to_string(), bg_concat_path.to_string_lossy().to_string(),
+        ]);
+        current_idx += 1;
+    } else {
+        for p in &pre_videos {
+            cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
+            current_idx += 1;
+        }
+    }
+    
+    // Vidéo à détourer, calée sur le début de l'export
+    let chroma = options.chroma_key.as_ref().filter(|k| !k.video.is_empty()).map(|key| {
+        cmd.extend_from_slice(&[
+            "-ss".to_string(), format!("{:.3}", start_s),
+            "-i".to_string(), path_utils::normalize_existing_path(&key.video).to_string_lossy().to_string(),
+        ]);
+        current_idx += 1;
+        (current_idx - 1, key)
+    });
+
+    // Fonds par sous-titre (une entrée par index associé ; les images sont bouclées)
+    let mut segment_backgrounds: Vec<(usize, i32)> = Vec::new();
+    if let Some(map) = &options.ayah_backgrounds {
+        let mut entries: Vec<(&usize, &String)> = map.iter().filter(|(i, _)| **i < n).collect();
+        entries.sort();
+        for (image_idx, bg_path) in entries {
+            let resolved = path_utils::normalize_existing_path(bg_path).to_string_lossy().to_string();
+            if is_image_file(&resolved) {
+                cmd.extend_from_slice(&["-loop".to_string(), "1".to_string()]);
+            }
+            cmd.extend_from_slice(&["-i".to_string(), resolved]);
+            segment_backgrounds.push((*image_idx, current_idx));
+            current_idx += 1;
+        }
+    }
+
+    // Masque de révélation, bouclé sur toute la durée
+    let reveal_mask_idx = match options.reveal_mask.as_deref() {
+        Some(path) => {
+            load_reveal_mask(path)?;
+            cmd.extend_from_slice(&[
+                "-loop".to_string(), "1".to_string(),
+                "-framerate".to_string(), fps.to_string(),
+                "-i".to_string(), path_utils::normalize_existing_path(path).to_string_lossy().to_string(),
+            ]);
+            current_idx += 1;
+            Some(current_idx - 1)
+        }
+        None => None,
+    };
+
+    let audio_start_idx = current_idx;
+    // On ne sait pas encore si on a de l'audio avant build_filter_complex_content
+    // mais on ajoute les entrées quand même si audio_paths n'est pas vide
+    if !audio_paths.is_empty() {
+        for p in audio_paths {
+            cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
+            current_idx += 1;
+        }
+    }
+
+    let audio = options.audio_options();
+    let filter_ctx = build_filter_complex_content(
+        w, h, fps, fade, n, &durations_s, &repeated, start_s, duration_s, 
+        &pre_videos, total_bg_s, audio_paths, audio_start_idx, bg_start_idx, current_idx, false, false, translation, options.background_mode, chroma,
+        &segment_backgrounds, &audio, &CaptionStyle::from_options(options, reveal_mask_idx),
+    );
+    
+    // Habillage animé : entrée ajoutée après les fonds de couleur, à l'index suivant
+    let overlay_video = options.overlay_video.as_ref().filter(|o| !o.video.is_empty());
+    let (filter_complex, vout_label) = match overlay_video {
+        Some(ov) => (
+            format!("{};{}", filter_ctx.filter_complex, ov.filter(filter_ctx.current_idx, "[vout]", w, h, fps, duration_s)),
+            "[vmotion]",
+        ),
+        None => (filter_ctx.filter_complex, "[vout]"),
+    };
+    // Tampon texte incrusté en sortie de graphe
+    let (filter_complex, vout_label) = match &options.stamp {
+        Some(stamp) => (format!("{};{}{}[vstamp]", filter_complex, vout_label, stamp.drawtext_filter(w, h)?), "[vstamp]"),
+        None => (filter_complex, vout_label),
+    };
+    let have_audio = filter_ctx.have_audio;
+    let _final_idx = filter_ctx.current_idx;
+
+    if pre_videos.is_empty() || filter_ctx.total_bg_s <= 1e-6 {
+        cmd.extend_from_slice(&[
+            "-f".to_string(), "lavfi".to_string(),
+            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, duration_s),
+        ]);
+    } else if filter_ctx.total_bg_s + 1e-6 < duration_s && options.background_mode == BackgroundMode::Pad {
+        let remain = duration_s - filter_ctx.total_bg_s;
+        cmd.extend_from_slice(&[
+            "-f".to_string(), "lavfi".to_string(),
+            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, remain),
+        ]);
+    }
+    if let Some(ov) = overlay_video {
+        log_out!("[overlay_video] Habillage animé: {} (boucle: {})", ov.video, ov.repeat);
+        cmd.extend(ov.input_args());
+    }
+    
+    let fg_path = work_dir.path().join(format!("filter-{}.ffgraph", &format!("{:x}", md5::compute(filter_complex.as_bytes()))[..8]));
     fs::write(&fg_path, &filter_complex)?;
+    if options.keep_intermediates {
+        log_out!("[debug] Graphe de filtres: {}", fg_path.display());
+    }
+    
+    cmd.extend_from_slice(&["-filter_complex_script".to_string(), fg_path.to_string_lossy().to_string()]);
+    let sequence_pattern = image_sequence_pattern(out_path, options);
+    cmd.extend_from_slice(&["-map".to_string(), vout_label.to_string()]);
+    if have_audio && sequence_pattern.is_none() {
+        // Une entrée -map (et sa langue) par piste audio de sortie
+        cmd.extend(audio.map_args(audio_paths.len()));
+    }
+    
+    if let Some(pattern) = &sequence_pattern {
+        // Séquence PNG : une image par frame, l'audio part dans un fichier séparé (2e sortie)
+        if let Some(dir) = Path::new(pattern).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        cmd.extend_from_slice(&[
+            "-r".to_string(), fps.to_string(),
+            "-c:v".to_string(), "png".to_string(),
+            "-t".to_string(), format!("{:.6}", duration_s),
+            "-f".to_string(), "image2".to_string(),
+        ]);
+        cmd.extend(options.extra_ffmpeg_args.iter().flatten().cloned());
+        cmd.push(pattern.clone());
+        if have_audio {
+            // Un seul fichier audio à côté de la séquence : la première piste
+            let first_track = audio.map_args(audio_paths.len())[1].clone();
+            cmd.extend_from_slice(&[
+                "-map".to_string(), first_track,
+                "-c:a".to_string(), "aac".to_string(),
+                "-b:a".to_string(), (if options.audio_channels() == 1 { "160k" } else { "320k" }).to_string(),
+                "-ac".to_string(), options.audio_channels().to_string(),
+                "-ar".to_string(), options.audio_sample_rate()?.to_string(),
+                "-t".to_string(), format!("{:.6}", duration_s),
+                image_sequence_audio_path(pattern).to_string_lossy().to_string(),
+            ]);
+        }
+    } else {
+        // Codec vidéo + audio
+        let gop = options.gop_size(fps)?;
+        cmd.extend_from_slice(&[
+            "-r".to_string(), fps.to_string(), 
+            "-g".to_string(), gop.to_string(),
+            "-c:v".to_string(), vcodec.clone()
+        ]);
+        if let Some(Some(preset)) = vextra.get("preset") {
+            cmd.extend_from_slice(&["-preset".to_string(), preset.clone()]);
+        }
+        cmd.extend(vparams);
+
+        if have_audio {
+            // HYPOTHESE 1 : Si c'est un "Chunk" intermédiaire, on utilise du LOSSLESS (ALAC)
+            // pour éviter la dégradation lors de la concaténation.
+            // Si c'est un export final (direct), on utilise du AAC 320k standard.
+            // ALAC est supporté dans le conteneur MP4/M4A.
+            if chunk_index.is_some() {
+                cmd.extend_from_slice(&[
+                    "-c:a".to_string(), "alac".to_string(), 
+                    "-ac".to_string(), options.audio_channels().to_string(),
+                    "-ar".to_string(), options.audio_sample_rate()?.to_string(),
+                ]);
+            } else {
+                cmd.extend(audio_codec_args); // AAC 320k stéréo par défaut (qualité max)
+            }
+        }
+
+        // Assure la durée exacte
+        cmd.extend_from_slice(&["-t".to_string(), format!("{:.6}", duration_s)]);
+
+        // Faststart pour formats MP4/MOV (désactivable : réécriture complète du fichier)
+        let ext = Path::new(out_path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        if out_path == crate::renderer::STDOUT_OUTPUT {
+            // Un pipe ne se relit pas pour écrire le moov : MP4 fragmenté, comme en WGPU
+            cmd.extend(["-f", "mp4", "-movflags", "+frag_keyframe+empty_moov"].map(String::from));
+        } else if matches!(ext.as_str(), "mp4" | "mov" | "m4v") && options.faststart.unwrap_or(true) {
+            cmd.extend_from_slice(&["-movflags".to_string(), "+faststart".to_string()]);
+        }
+
+        // Arguments personnalisés (non validés), juste avant la sortie
+        cmd.extend(options.extra_ffmpeg_args.iter().flatten().cloned());
+
+        // Fichier de sortie
+        cmd.push(out_path.to_string());
+    }
+    
+    log_out!("[ffmpeg] Commande:");
+    let has_custom_args = options.extra_ffmpeg_args.as_ref().is_some_and(|a| !a.is_empty());
+    let preview = if cmd.len() > 14 && !has_custom_args {
+        format!("{} ...", cmd[..14].join(" "))
+    } else {
+        cmd.join(" ")
+    };
+    log_out!("  {}", preview);
+    
+    // Exécution avec capture de la progression
+    let mut command = Command::new(&cmd[0]);
+    command.args(&cmd[1..]);
+    command.stderr(Stdio::piped());
+    
+    // Configurer la commande pour cacher les fenêtres CMD sur Windows
+    configure_command_no_window(&mut command);
+    
+    let setup_s = setup_started.elapsed().as_secs_f64();
+    let encode_started = Instant::now();
+    let child = command.spawn()?;
+    
+    // Enregistrer le processus dans les exports actifs
+    let process_ref = Arc::new(Mutex::new(Some(child)));
+    {
+        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+        active_exports.insert(export_id.to_string(), ExportHandle::Process(process_ref.clone()));
+    }
+    track_export_pids(export_id, Some(ExportHandle::Process(process_ref.clone()).pids()));
+    
+    let stderr = {
+        let mut child_guard = process_ref.lock().map_err(|_| "Failed to lock child process")?;
+        if let Some(ref mut child) = child_guard.as_mut() {
+            child.stderr.take().ok_or("Failed to capture stderr")?
+        } else {
+            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "Process was cancelled")));
+        }
+    };
+    
+    // Chien de garde : tue FFmpeg si aucune ligne de progression n'arrive à temps
+    let watchdog = Watchdog::spawn(options.watchdog_timeout(), vec![process_ref.clone()]);
+    let armed = watchdog.arm();
+
+    // Lire la sortie stderr pour capturer la progression
+    let reader = BufReader::new(stderr);
+    let mut stderr_content = String::new();
+    // `frame=` arrive avant `out_time_ms=` avec -progress : on garde la dernière valeur lue
+    let total_frames = (duration_s * fps.as_f64()).round() as u64;
+    let mut current_frame: Option<u64> = None;
+    let mut throttle = ProgressThrottle::new(options.progress_interval());
+
+    // Journal en direct (option verbose) : lignes envoyées par lots
+    let mut log_batch: Vec<String> = Vec::new();
+    let mut last_log_flush = Instant::now();
+    let flush_log = |batch: &mut Vec<String>| {
+        if batch.is_empty() {
+            return;
+        }
+        let mut log_data = serde_json::json!({
+            "export_id": export_id,
+            "lines": std::mem::take(batch)
+        });
+        if let Some(chunk_idx) = chunk_index {
+            log_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+        }
+        let _ = app_handle.emit("export-log", log_data);
+    };
+    
+    for line in reader.lines() {
+        if let Ok(line) = line {
+            log_out!("[ffmpeg] {}", line); // Debug: afficher toutes les lignes
+
+            if options.verbose {
+                log_batch.push(line.clone());
+                if log_batch.len() >= LOG_BATCH_MAX_LINES || last_log_flush.elapsed() >= LOG_BATCH_INTERVAL {
+                    flush_log(&mut log_batch);
+                    last_log_flush = Instant::now();
+                }
+            }
+            
+            // Sauvegarder toutes les lignes stderr pour le debugging
+            stderr_content.push_str(&line);
+            stderr_content.push('\n');
+            
+            if let Some(frame) = extract_frame_from_ffmpeg_line(&line) {
+                current_frame = Some(frame);
+            }
+
+            // Chercher les lignes de progression FFmpeg qui contiennent "time=" ou "out_time_ms="
+            if line.contains("time=") || line.contains("out_time_ms=") {
+                watchdog.beat();
+                if let Some(time_str) = extract_time_from_ffmpeg_line(&line) {
+                    let current_time_s = parse_ffmpeg_time(&time_str);
+                    let progress = if duration_s > 0.0 {
+                        (current_time_s / duration_s * 100.0).min(100.0)
+                    } else {
+                        0.0
+                    };
+                    
+                    if !throttle.ready(progress >= 100.0) {
+                        continue;
+                    }
+                    log_out!("[progress] {}% ({:.1}s / {:.1}s)", progress.round(), current_time_s, duration_s);
+                    
+                    // Émettre l'événement de progression vers le frontend
+                    let progress_data = progress_event(
+                        export_id, chunk_index, progress, current_time_s, duration_s, current_frame, total_frames,
+                    );
+                    let _ = app_handle.emit("export-progress", progress_data);
+                }
+            }
+        }
+    }
     
-    cmd.extend_from_slice(&["-filter_complex_script".to_string(), fg_path.to_string_lossy().to_string()]);
-    cmd.extend_from_slice(&["-map".to_string(), "[vout]".to_string()]);
-    if have_audio {
-        cmd.extend_from_slice(&["-map".to_string(), "[aout]".to_string()]);
+    drop(armed);
+    flush_log(&mut log_batch);
+
+    // Attendre la fin du processus
+    let status = {
+        let mut child_guard = process_ref.lock().map_err(|_| "Failed to lock child process")?;
+        if let Some(mut child) = child_guard.take() {
+            child.wait()?
+        } else if watchdog.fired() {
+            // Tué par le chien de garde
+            if let Ok(mut active_exports) = ACTIVE_EXPORTS.lock() {
+                active_exports.remove(export_id);
+            }
+            track_export_pids(export_id, None);
+            let error_msg = format!(
+                "{}: FFmpeg n'a plus progressé depuis {:?}, processus arrêté.\n\nStandard Error Output:\n{}",
+                TIMEOUT_ERROR, options.watchdog_timeout().unwrap_or_default(), stderr_content
+            );
+            let mut error_data = serde_json::json!({
+                "export_id": export_id,
+                "error": error_msg
+            });
+            if let Some(chunk_idx) = chunk_index {
+                error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+            }
+            let _ = app_handle.emit("export-error", error_data);
+            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::TimedOut, error_msg)));
+        } else {
+            // Le processus a été annulé
+            let error_msg = format!("Export {} was cancelled", export_id);
+            let mut error_data = serde_json::json!({
+                "export_id": export_id,
+                "error": error_msg
+            });
+            
+            // Ajouter chunk_index si fourni
+            if let Some(chunk_idx) = chunk_index {
+                error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+            }
+            
+            let _ = app_handle.emit("export-error", error_data);
+            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Interrupted, error_msg)));
+        }
+    };
+    
+    // Nettoyer les exports actifs
+    {
+        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+        active_exports.remove(export_id);
+    }
+    track_export_pids(export_id, None);
+    
+    if !status.success() {
+        // Créer un fichier de log avec la date d'aujourd'hui
+        let now = std::time::SystemTime::now();
+        let timestamp = now.duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let log_filename = format!("ffmpeg_failed_{}.txt", timestamp);
+
+        if options.json_failure_log {
+            write_json_failure_log(timestamp, export_id, status.code(), &cmd, &stderr_content);
+        }
+        
+        let log_content = format!(
+            "FFmpeg Export Failure Log\n\
+             =========================\n\
+             Timestamp: {}\n\
+             Export ID: {}\n\
+             Exit Code: {:?}\n\
+             \n\
+             FFmpeg Command:\n\
+             {}\n\
+             \n\
+             Standard Error Output:\n\
+             {}\n",
+            timestamp,
+            export_id,
+            status.code(),
+            cmd.join(" "),
+            if stderr_content.is_empty() {
+                "No stderr output captured".to_string()
+            } else {
+                stderr_content
+            }
+        );
+        
+        // Écrire le fichier de log
+        if let Err(log_err) = std::fs::write(&log_filename, &log_content) {
+            eprintln!("Failed to write log file {}: {}", log_filename, log_err);
+        } else {
+            log_out!("FFmpeg error details saved to: {}", log_filename);
+        }
+        
+        let error_msg = format!(
+            "ffmpeg failed during video exportation (exit code: {:?})\n\nSee the log file: {}\n\nLog details:\n{}", 
+            status.code(), 
+            log_filename,
+            log_content
+        );
+        let mut error_data = serde_json::json!({
+            "export_id": export_id,
+            "error": error_msg
+        });
+        
+        // Ajouter chunk_index si fourni
+        if let Some(chunk_idx) = chunk_index {
+            error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+        }
+        
+        let _ = app_handle.emit("export-error", error_data);
+        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, error_msg)));
+    }
+
+    let encode_s = encode_started.elapsed().as_secs_f64();
+    let finalize_started = Instant::now();
+
+    // FFmpeg peut sortir en 0 avec un fichier tronqué : on relit la sortie avant d'annoncer le succès
+    let to_stdout = out_path == crate::renderer::STDOUT_OUTPUT;
+    if sequence_pattern.is_none() && !to_stdout {
+        if let Err(error_msg) = verify_output(out_path, duration_s) {
+            emit_empty_output(&app_handle, export_id, &error_msg);
+            let mut error_data = serde_json::json!({
+                "export_id": export_id,
+                "error": error_msg
+            });
+            if let Some(chunk_idx) = chunk_index {
+                error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+            }
+            let _ = app_handle.emit("export-error", error_data);
+            return Err(error_msg.into());
+        }
+    }
+
+    let mut report = ExportReport {
+        output_path: out_path.to_string(),
+        codec: vcodec.to_string(),
+        hardware_accelerated: !vcodec.starts_with("lib"),
+        duration_s,
+        frames: (duration_s * fps.as_f64()).round() as u64,
+        fps: fps.as_f64(),
+        file_size_bytes: fs::metadata(out_path).ok().map(|m| m.len()),
+        ..Default::default()
+    };
+    report.record_duration("preprocess", preprocess_s);
+    report.record_duration("setup", setup_s);
+    report.record_duration("encode", encode_s);
+    report.record_stage("finalize", finalize_started);
+    if options.measure_loudness && sequence_pattern.is_none() && !to_stdout {
+        // Un chunk n'est qu'une partie de l'audio : mesure reportée après la concaténation
+        if chunk_index.is_some() {
+            report.loudness_after_concat = true;
+        } else {
+            report.record_loudness();
+        }
+    }
+    if options.write_success_log && sequence_pattern.is_none() && !to_stdout {
+        write_success_log(out_path, export_id, &cmd, Some(&report));
+    }
+
+    // Dernier chunk terminé : le plan n'a plus lieu d'être
+    if let (Some(chunk_idx), Ok(mut plans)) = (chunk_index, CHUNK_PLANS.lock()) {
+        if plans.get(export_id).is_some_and(|plan| chunk_idx as usize + 1 >= plan.weights.len()) {
+            plans.remove(export_id);
+        }
+    }
+    
+    Ok(report)
+}
+
+const FAILURE_LOG_STDERR_TAIL_LINES: usize = 50;
+
+// Journal d'échec structuré (à côté du `.txt`), exploitable par un rapport de crash automatique
+fn write_json_failure_log(timestamp: u64, export_id: &str, exit_code: Option<i32>, cmd: &[String], stderr: &str) {
+    let lines: Vec<&str> = stderr.lines().collect();
+    let stderr_tail = lines[lines.len().saturating_sub(FAILURE_LOG_STDERR_TAIL_LINES)..].join("\n");
+    let codec = cmd
+        .iter()
+        .position(|a| a == "-c:v")
+        .and_then(|i| cmd.get(i + 1))
+        .cloned();
+    let ffmpeg_version = cmd.first().and_then(|exe| ffmpeg_version_line(exe));
+
+    let report = serde_json::json!({
+        "timestamp": timestamp,
+        "export_id": export_id,
+        "exit_code": exit_code,
+        "command": cmd,
+        "stderr_tail": stderr_tail,
+        "host": {
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "ffmpeg_version": ffmpeg_version,
+            "codec": codec,
+        },
+    });
+    let json_filename = format!("ffmpeg_failed_{}.json", timestamp);
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => match std::fs::write(&json_filename, json) {
+            Ok(()) => log_out!("FFmpeg structured failure log saved to: {}", json_filename),
+            Err(e) => eprintln!("Failed to write log file {}: {}", json_filename, e),
+        },
+        Err(e) => eprintln!("Failed to serialize failure log: {}", e),
+    }
+}
+
+// Première ligne de `ffmpeg -version`
+fn ffmpeg_version_line(exe: &str) -> Option<String> {
+    let mut version_cmd = Command::new(exe);
+    version_cmd.arg("-version");
+    configure_command_no_window(&mut version_cmd);
+    let out = version_cmd.output().ok()?;
+    String::from_utf8_lossy(&out.stdout).lines().next().map(|l| l.trim().to_string())
+}
+
+// Date civile UTC `AAAA-MM-JJ` d'un horodatage Unix (algorithme de H. Hinnant, sans dépendance)
+fn utc_date(unix_s: u64) -> String {
+    let z = (unix_s / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Caractères refusés par Windows (et `/` partout) remplacés par `_` ; ni point ni espace final
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches(['.', ' ']);
+    if cleaned.is_empty() { "export".to_string() } else { cleaned.to_string() }
+}
+
+// Nom de fichier issu de `output_name_template` ; `.mp4` si le modèle ne donne pas d'extension
+fn render_output_name(template: &str, title: &str, (w, h): (i32, i32), codec: VideoCodec, unix_s: u64) -> String {
+    let name = template
+        .replace("{title}", title)
+        .replace("{resolution}", &format!("{}p", w.min(h)))
+        .replace("{date}", &utc_date(unix_s))
+        .replace("{codec}", codec.name());
+    let name = sanitize_file_name(&name);
+    if Path::new(&name).extension().is_some() { name } else { format!("{}.mp4", name) }
+}
+
+// Journal de succès `<sortie>.export.json` (option `write_success_log`) ; un échec d'écriture
+// n'invalide pas l'export
+fn write_success_log(out_path: &str, export_id: &str, cmd: &[String], report: Option<&ExportReport>) {
+    let codec = cmd
+        .iter()
+        .position(|a| a == "-c:v")
+        .and_then(|i| cmd.get(i + 1))
+        .cloned();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let log = serde_json::json!({
+        "timestamp": timestamp,
+        "export_id": export_id,
+        "output": out_path,
+        "command": cmd,
+        "codec": codec,
+        "report": report,
+        "host": {
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "ffmpeg_version": cmd.first().and_then(|exe| ffmpeg_version_line(exe)),
+        },
+    });
+    let log_path = format!("{}.export.json", out_path);
+    match serde_json::to_string_pretty(&log) {
+        Ok(json) => match std::fs::write(&log_path, json) {
+            Ok(()) => log_out!("[log] Journal d'export écrit : {}", log_path),
+            Err(e) => eprintln!("[log] ⚠️ Écriture de {} impossible : {}", log_path, e),
+        },
+        Err(e) => eprintln!("[log] ⚠️ Sérialisation du journal d'export impossible : {}", e),
+    }
+}
+
+/// Arrondit une taille au pair supérieur (yuv420p refuse les dimensions impaires).
+pub(crate) fn even_dimensions(w: i32, h: i32) -> (i32, i32) {
+    ((w + 1) & !1, (h + 1) & !1)
+}
+
+const INSUFFICIENT_DISK_SPACE_ERROR: &str = "INSUFFICIENT_DISK_SPACE";
+
+// Les estimations de taille sont grossières : on exige une marge avant de refuser l'export
+const DISK_SPACE_SAFETY_FACTOR: f64 = 1.5;
+
+const OUTPUT_NOT_WRITABLE_ERROR: &str = "OUTPUT_NOT_WRITABLE";
+const INVALID_BACKGROUND_ERROR: &str = "INVALID_BACKGROUND";
+
+const INVALID_BACKGROUND_DURATIONS_ERROR: &str = "INVALID_BACKGROUND_DURATIONS";
+
+// Écarte les fonds sans flux vidéo (ex. un MP3 glissé par erreur) avec un avertissement ;
+// si aucun fond n'est exploitable, l'export échoue en listant les fichiers fautifs.
+// Avec `background_durations_ms`, l'appelant garantit ses fonds : rien n'est sondé ni écarté,
+// pour que les durées restent alignées sur les fichiers.
+fn video_backgrounds(bg_videos: &[String], options: &ExportOptions) -> Result<Vec<String>, String> {
+    if let Some(durations) = &options.background_durations_ms {
+        if durations.len() != bg_videos.len() {
+            return Err(format!(
+                "{}: {} durées pour {} fonds",
+                INVALID_BACKGROUND_DURATIONS_ERROR, durations.len(), bg_videos.len()
+            ));
+        }
+        if let Some(ms) = durations.iter().find(|ms| **ms <= 0) {
+            return Err(format!("{}: durée invalide {} ms", INVALID_BACKGROUND_DURATIONS_ERROR, ms));
+        }
+        return Ok(bg_videos.to_vec());
+    }
+    let (valid, invalid): (Vec<String>, Vec<String>) = bg_videos.iter().cloned().partition(|p| has_stream(p, "V"));
+    for p in &invalid {
+        eprintln!("[bg] ⚠️ Aucun flux vidéo, fond ignoré: {}", p);
+    }
+    if valid.is_empty() && !invalid.is_empty() {
+        return Err(format!("{}: aucun flux vidéo dans {}", INVALID_BACKGROUND_ERROR, invalid.join(", ")));
+    }
+    Ok(valid)
+}
+
+const TIMELINE_EXCEEDS_MEDIA_WARNING: &str = "TIMELINE_EXCEEDS_MEDIA";
+// Écart toléré (s) entre la fin de la timeline et celle des médias avant d'avertir
+const MEDIA_OVERRUN_TOLERANCE_S: f64 = 0.5;
+
+/// Durée disponible après `start_s` pour chaque média ; `None` si absent ou rebouclé.
+#[derive(Debug, Clone, Copy, Default)]
+struct MediaCoverage {
+    audio_s: Option<f64>,
+    background_s: Option<f64>,
+}
+
+impl MediaCoverage {
+    fn probe(audio_paths: &[String], bg_videos: &[String], start_s: f64, options: &ExportOptions) -> Self {
+        let available = |durations: Vec<f64>| (durations.iter().sum::<f64>() - start_s).max(0.0);
+        // Une image fixe en fond couvre toute la durée
+        let still = bg_videos.iter().any(|p| is_image_file(p));
+        MediaCoverage {
+            audio_s: (!audio_paths.is_empty() && !options.audio_loop).then(|| available(ffprobe_durations_sec(audio_paths))),
+            background_s: (!bg_videos.is_empty() && !still && options.background_mode == BackgroundMode::Pad)
+                .then(|| available(options.background_durations_s(bg_videos))),
+        }
+    }
+
+    // (média, durée disponible) pour chaque média nettement plus court que la timeline
+    fn overruns(&self, duration_s: f64) -> Vec<(&'static str, f64)> {
+        [("audio", self.audio_s), ("fond", self.background_s)]
+            .into_iter()
+            .filter_map(|(name, s)| s.filter(|s| duration_s - s > MEDIA_OVERRUN_TOLERANCE_S).map(|s| (name, s)))
+            .collect()
+    }
+
+    /// Durée ramenée au média le plus court (inchangée s'il n'y a rien à quoi se caler).
+    fn clamp(&self, duration_s: f64) -> f64 {
+        [self.audio_s, self.background_s]
+            .into_iter()
+            .flatten()
+            .filter(|s| *s > 1e-6)
+            .fold(duration_s, f64::min)
+    }
+}
+
+const INVALID_AUDIO_ERROR: &str = "INVALID_AUDIO";
+
+// Pistes dont ffprobe ne donne aucune durée (fichier vide, corrompu ou sans flux audio)
+fn unreadable_audio<'a>(audio_paths: &'a [String], durations_s: &[f64]) -> Vec<&'a str> {
+    audio_paths
+        .iter()
+        .zip(durations_s)
+        .filter(|(_, d)| d.is_nan() || **d <= 1e-6)
+        .map(|(p, _)| p.as_str())
+        .collect()
+}
+
+// Une piste illisible disparaît du mixage : on la signale (log + `export-warning` par fichier)
+// plutôt que de livrer une vidéo muette sans explication, voire on refuse l'export
+fn check_audio_durations(
+    app_handle: &tauri::AppHandle,
+    export_id: &str,
+    audio_paths: &[String],
+    fail: bool,
+) -> Result<(), String> {
+    let unreadable = unreadable_audio(audio_paths, &ffprobe_durations_sec(audio_paths));
+    if unreadable.is_empty() {
+        return Ok(());
+    }
+    if fail {
+        return Err(format!("{}: durée nulle ou illisible pour {}", INVALID_AUDIO_ERROR, unreadable.join(", ")));
+    }
+    for path in unreadable {
+        let message = format!("Durée audio nulle ou illisible, piste ignorée : {}", path);
+        eprintln!("[media] ⚠️ {}", message);
+        let _ = app_handle.emit("export-warning", serde_json::json!({
+            "exportId": export_id,
+            "code": INVALID_AUDIO_ERROR,
+            "message": message,
+            "path": path,
+        }));
+    }
+    Ok(())
+}
+
+// Applique `max_dimension` à la taille cible ; une réduction est signalée (log + `export-warning`)
+fn check_resolution_cap(
+    app_handle: &tauri::AppHandle,
+    export_id: &str,
+    options: &ExportOptions,
+    target_size: (i32, i32),
+) -> Result<(i32, i32), String> {
+    let capped = options.capped_size(target_size)?;
+    if capped != target_size {
+        let message = format!(
+            "Résolution {}x{} au-delà du plafond, réduite à {}x{}",
+            target_size.0, target_size.1, capped.0, capped.1
+        );
+        eprintln!("[image] ⚠️ {}", message);
+        let _ = app_handle.emit("export-warning", serde_json::json!({
+            "exportId": export_id,
+            "code": crate::renderer::RESOLUTION_TOO_LARGE_ERROR,
+            "message": message,
+        }));
+    }
+    Ok(capped)
+}
+
+// Avertit (log + `export-warning`) si la timeline dépasse l'audio ou le fond, et renvoie
+// la durée à exporter : raccourcie avec `clamp_to_media`, inchangée sinon
+fn check_media_coverage(
+    app_handle: &tauri::AppHandle,
+    export_id: &str,
+    coverage: &MediaCoverage,
+    duration_s: f64,
+    clamp: bool,
+) -> f64 {
+    let overruns = coverage.overruns(duration_s);
+    if overruns.is_empty() {
+        return duration_s;
+    }
+    let details: Vec<String> = overruns.iter().map(|(name, s)| format!("{} {:.2}s", name, s)).collect();
+    let clamped_s = if clamp { coverage.clamp(duration_s) } else { duration_s };
+    let message = if clamp {
+        format!("La timeline ({:.2}s) dépasse les médias ({}), export raccourci à {:.2}s", duration_s, details.join(", "), clamped_s)
+    } else {
+        format!("La timeline ({:.2}s) dépasse les médias ({}) : fin silencieuse ou noire", duration_s, details.join(", "))
+    };
+    eprintln!("[media] ⚠️ {}", message);
+    let _ = app_handle.emit("export-warning", serde_json::json!({
+        "exportId": export_id,
+        "code": TIMELINE_EXCEEDS_MEDIA_WARNING,
+        "message": message,
+    }));
+    clamped_s
+}
+
+// Crée puis supprime un petit fichier témoin pour vérifier les droits d'écriture
+fn ensure_dir_writable(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("{}: impossible de créer {:?}: {}", OUTPUT_NOT_WRITABLE_ERROR, dir, e))?;
+    let probe = dir.join(format!(".qurancaption-write-test-{}", std::process::id()));
+    fs::write(&probe, b"")
+        .map_err(|e| format!("{}: pas de droit d'écriture dans {:?}: {}", OUTPUT_NOT_WRITABLE_ERROR, dir, e))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+    if mb >= 1024.0 {
+        format!("{:.1} GB", mb / 1024.0)
+    } else {
+        format!("{:.0} MB", mb)
+    }
+}
+
+// Vérifie l'espace libre du disque de sortie (vidéo finale) et du disque temporaire
+// (cache de prétraitement). Si la requête d'espace échoue, la vérification est ignorée.
+fn check_disk_space(out_dir: &Path, output_bytes: u64, cache_bytes: u64) -> Result<(), String> {
+    let temp_dir = std::env::temp_dir();
+    let out_free = fs2::available_space(out_dir).ok();
+    let temp_free = fs2::available_space(&temp_dir).ok();
+
+    // Même volume (approximation : même espace libre et même taille totale) : les besoins s'additionnent
+    let same_volume = out_free.is_some()
+        && out_free == temp_free
+        && fs2::total_space(out_dir).ok() == fs2::total_space(&temp_dir).ok();
+
+    let mut checks = Vec::new();
+    if same_volume {
+        checks.push((out_dir.to_path_buf(), out_free, output_bytes + cache_bytes));
+    } else {
+        checks.push((out_dir.to_path_buf(), out_free, output_bytes));
+        if cache_bytes > 0 {
+            checks.push((temp_dir, temp_free, cache_bytes));
+        }
+    }
+
+    for (dir, free, needed) in checks {
+        let Some(free) = free else {
+            log_out!("[disk] Espace libre inconnu pour {:?}, vérification ignorée", dir);
+            continue;
+        };
+        let required = (needed as f64 * DISK_SPACE_SAFETY_FACTOR) as u64;
+        log_out!("[disk] {:?}: ~{} requis, {} disponibles", dir, format_bytes(required), format_bytes(free));
+        if free < required {
+            return Err(format!(
+                "{}: ~{} nécessaires sur {:?}, seulement {} disponibles",
+                INSUFFICIENT_DISK_SPACE_ERROR, format_bytes(required), dir, format_bytes(free)
+            ));
+        }
+    }
+    Ok(())
+}
+
+const CORRUPT_OUTPUT_ERROR: &str = "CORRUPT_OUTPUT";
+// Écart de durée toléré entre la sortie et la durée demandée : absolu (s) ou relatif
+const OUTPUT_DURATION_TOLERANCE_S: f64 = 0.5;
+const OUTPUT_DURATION_TOLERANCE_RATIO: f64 = 0.02;
+
+const EMPTY_OUTPUT_ERROR: &str = "EMPTY_OUTPUT";
+// En dessous, la sortie ne contient au mieux que des en-têtes de conteneur
+const MIN_OUTPUT_BYTES: u64 = 256;
+
+// Supprime une sortie vide (FFmpeg sorti en 0 sans rien écrire, ex. entrées vides)
+fn reject_empty_output(path: &str) -> Result<(), String> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(());
+    };
+    if metadata.len() >= MIN_OUTPUT_BYTES {
+        return Ok(());
+    }
+    let _ = fs::remove_file(path);
+    eprintln!("[verify] Sortie vide supprimée: {} ({} octet(s))", path, metadata.len());
+    Err(format!("{}: {} ne fait que {} octet(s), fichier supprimé", EMPTY_OUTPUT_ERROR, path, metadata.len()))
+}
+
+// Événement dédié `export-empty-output`, en plus de l'erreur habituelle, si l'échec vient d'une sortie vide
+fn emit_empty_output(app_handle: &tauri::AppHandle, export_id: &str, error: &str) {
+    if error.starts_with(EMPTY_OUTPUT_ERROR) {
+        let _ = app_handle.emit("export-empty-output", serde_json::json!({
+            "exportId": export_id,
+            "code": EMPTY_OUTPUT_ERROR,
+            "message": error,
+        }));
+    }
+}
+
+// Fin effective d'un export (ou d'un chunk) : `export-complete` avec le rapport final
+fn emit_export_complete(app_handle: &tauri::AppHandle, export_id: &str, report: &ExportReport, chunk_index: Option<i32>) {
+    if chunk_index.is_some() {
+        if let Ok(mut reports) = CHUNK_REPORTS.lock() {
+            match reports.get_mut(export_id) {
+                Some(total) => total.accumulate(report),
+                None => {
+                    let first = ExportReport { integrated_lufs: None, true_peak_dbtp: None, ..report.clone() };
+                    reports.insert(export_id.to_string(), first);
+                }
+            }
+        }
+    }
+    *LAST_EXPORT_TIME_S.lock().unwrap() = Some(report.wall_clock_s);
+    log_out!("[done] Export terminé en {:.2}s", report.wall_clock_s);
+    log_out!("[metric] export_time_seconds={:.3}", report.wall_clock_s);
+    log_out!("[report] {}", serde_json::to_string(report).unwrap_or_default());
+    let mut completion_data = serde_json::json!({
+        "filename": Path::new(&report.output_path).file_name().unwrap_or_default().to_string_lossy(),
+        "exportId": export_id,
+        "fullPath": report.output_path,
+        "report": report
+    });
+    if let Some(chunk_idx) = chunk_index {
+        completion_data["chunkIndex"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+    }
+    let _ = app_handle.emit("export-complete", completion_data);
+}
+
+// Vérifie via ffprobe que la sortie contient un flux vidéo et dure à peu près `expected_s`
+fn verify_output(path: &str, expected_s: f64) -> Result<(), String> {
+    reject_empty_output(path)?;
+    let info = probe_media(path)
+        .ok_or_else(|| format!("{}: {} illisible par ffprobe", CORRUPT_OUTPUT_ERROR, path))?;
+    if info.video.is_none() {
+        return Err(format!("{}: aucun flux vidéo dans {}", CORRUPT_OUTPUT_ERROR, path));
+    }
+    let tolerance = OUTPUT_DURATION_TOLERANCE_S.max(expected_s * OUTPUT_DURATION_TOLERANCE_RATIO);
+    if (info.duration_s - expected_s).abs() > tolerance {
+        return Err(format!(
+            "{}: {} dure {:.3}s au lieu de {:.3}s (fichier tronqué ?)",
+            CORRUPT_OUTPUT_ERROR, path, info.duration_s, expected_s
+        ));
+    }
+    log_out!("[verify] ✓ Sortie valide: {:.3}s (attendu {:.3}s)", info.duration_s, expected_s);
+    Ok(())
+}
+
+// Fonds par image d'une sélection `first..=last`, réindexés depuis la première image gardée
+fn select_ayah_backgrounds(map: HashMap<usize, String>, first: usize, last: usize) -> HashMap<usize, String> {
+    map.into_iter()
+        .filter(|(idx, _)| (first..=last).contains(idx))
+        .map(|(idx, path)| (idx - first, path))
+        .collect()
+}
+
+// Timestamp (ms) porté par le nom du fichier : `1500.png`, ou fractionnaire `123.5.png`
+fn stem_timestamp_ms(path: &Path) -> Option<f64> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|ms| ms.is_finite())
+}
+
+#[tauri::command]
+pub async fn export_video(
+    export_id: String,
+    imgs_folder: String,
+    final_file_path: String,
+    fps: FrameRate,
+    fade_duration: i32,
+    start_time: i32,
+    duration: Option<i32>,
+    audios: Option<Vec<String>>,
+    videos: Option<Vec<String>>,
+    chunk_index: Option<i32>,
+    blur: Option<f64>,
+    options: Option<ExportOptions>,
+    app: tauri::AppHandle,
+) -> Result<ExportReport, String> {
+    let t0 = Instant::now();
+    // Avant tout log : la sortie standard porte la vidéo
+    if final_file_path == crate::renderer::STDOUT_OUTPUT {
+        crate::renderer::reserve_stdout();
     }
     
-    // Codec vidéo + audio
-    let gop = fps * 2;
-    cmd.extend_from_slice(&[
-        "-r".to_string(), fps.to_string(), 
-        "-g".to_string(), gop.to_string(),
-        "-c:v".to_string(), vcodec
-    ]);
-    if let Some(Some(preset)) = vextra.get("preset") {
-        cmd.extend_from_slice(&["-preset".to_string(), preset.clone()]);
+    // Logs init
+    log_out!("[start_export] export_id={}", export_id);
+    log_out!("[start_export] imgs_folder={}", imgs_folder);
+    log_out!("[start_export] final_file_path={}", final_file_path);
+    log_out!("[start_export] fps={}, fade_duration(ms)={}", fps, fade_duration);
+    log_out!("[env] CPU cores: {:?}", std::thread::available_parallelism().map(|n| n.get()));
+    require_ffmpeg_binary()?;
+    
+    if let Some(ref audios) = audios {
+        log_out!("[audio] {} fichier(s) audio fourni(s)", audios.len());
+    } else {
+        log_out!("[audio] aucun fichier audio fourni");
     }
-    cmd.extend(vparams);
     
-    if have_audio {
-        // HYPOTHESE 1 : Si c'est un "Chunk" intermédiaire, on utilise du LOSSLESS (ALAC)
-        // pour éviter la dégradation lors de la concaténation.
-        // Si c'est un export final (direct), on utilise du AAC 320k standard.
-        // ALAC est supporté dans le conteneur MP4/M4A.
-        if chunk_index.is_some() {
-            cmd.extend_from_slice(&[
-                "-c:a".to_string(), "alac".to_string(), 
-                "-ac".to_string(), "2".to_string()      // Force stéréo
-            ]);
-        } else {
-            cmd.extend_from_slice(&[
-                "-c:a".to_string(), "aac".to_string(), 
-                "-b:a".to_string(), "320k".to_string(), // Qualité MAX pour éviter perte
-                "-ac".to_string(), "2".to_string()      // Force stéréo
-            ]);
-        }
+    if let Some(ref videos) = videos {
+        log_out!("[video] {} fichier(s) vidéo fourni(s)", videos.len());
+    } else {
+        log_out!("[video] aucune vidéo de fond fournie");
     }
     
-    // Assure la durée exacte
-    cmd.extend_from_slice(&["-t".to_string(), format!("{:.6}", duration_s)]);
+    // Vérifier dès le départ que la sortie et le cache sont inscriptibles,
+    // plutôt que d'échouer après le prétraitement
+    // `-` : MP4 fragmenté écrit sur la sortie standard, sans fichier
+    let to_stdout = final_file_path == crate::renderer::STDOUT_OUTPUT;
+    let out_path = if to_stdout {
+        PathBuf::from(crate::renderer::STDOUT_OUTPUT)
+    } else {
+        path_utils::normalize_output_path(&final_file_path)
+    };
+    // Avec un modèle de nom, la sortie donnée est le dossier ; le nom est résolu une fois la taille connue
+    let name_template = options
+        .as_ref()
+        .and_then(|o| o.output_name_template.clone())
+        .filter(|t| !to_stdout && !t.trim().is_empty());
+    let out_dir = if name_template.is_some() { Some(out_path.as_path()) } else { out_path.parent() };
+    if let Some(parent) = out_dir.filter(|_| !to_stdout) {
+        log_out!("[fs] Création du dossier de sortie si besoin: {:?}", parent);
+        fs::create_dir_all(parent).map_err(|e| format!("Erreur création dossier: {}", e))?;
+        ensure_dir_writable(parent)?;
+    }
+    ensure_dir_writable(&preproc_cache_dir())?;
     
-    // Faststart pour formats MP4/MOV
-    let ext = Path::new(out_path)
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_lowercase();
+    // Liste des PNG triés par timestamp
+    let folder = path_utils::normalize_existing_path(&imgs_folder);
+    log_out!("[scan] Parcours du dossier: {:?}", folder.canonicalize().unwrap_or_else(|_| folder.clone()));
     
-    if matches!(ext.as_str(), "mp4" | "mov" | "m4v") {
-        cmd.extend_from_slice(&["-movflags".to_string(), "+faststart".to_string()]);
+    let mut options = options.unwrap_or_default();
+    if name_template.is_none() && image_sequence_pattern(&out_path.to_string_lossy(), &options).is_none() {
+        check_codec_container(&out_path.to_string_lossy(), &options)?;
     }
+    let fps = resolve_export_fps(&app, &export_id, &options, fps, videos.as_deref().unwrap_or_default());
+    let translation_suffix = options.translation_suffix().map(str::to_string);
+    let mut files: Vec<_> = fs::read_dir(&folder)
+        .map_err(|e| format!("Erreur lecture dossier: {}", e))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            // Les PNG de traduction (`{idx}_t.png`) sont chargés à côté de leur image principale
+            let stem = path.file_stem()?.to_str()?;
+            let is_translation = translation_suffix.as_deref().is_some_and(|s| stem.ends_with(s));
+            if path.extension()?.to_str()?.to_lowercase() == "png" && !is_translation {
+                Some(path)
+            } else {
+                None
+            }
+        })
+        .collect();
     
-    // Fichier de sortie
-    cmd.push(out_path.to_string());
+    files.sort_by(|a, b| stem_timestamp_ms(a).unwrap_or(0.0).total_cmp(&stem_timestamp_ms(b).unwrap_or(0.0)));
+    let files: Vec<PathBuf> = files
+        .into_iter()
+        .map(|p| p.canonicalize().unwrap_or(p))
+        .collect();
     
-    println!("[ffmpeg] Commande:");
-    let preview = if cmd.len() > 14 {
-        format!("{} ...", cmd[..14].join(" "))
-    } else {
-        cmd.join(" ")
-    };
-    println!("  {}", preview);
+    log_out!("[scan] {} image(s) trouvée(s)", files.len());
     
-    // Exécution avec capture de la progression
-    let mut command = Command::new(&cmd[0]);
-    command.args(&cmd[1..]);
-    command.stderr(Stdio::piped());
+    if files.is_empty() {
+        return Err("Aucune image .png trouvée dans imgs_folder".to_string());
+    }
     
-    // Configurer la commande pour cacher les fenêtres CMD sur Windows
-    configure_command_no_window(&mut command);
+    let _first_stem = stem_timestamp_ms(&files[0]).map(|ms| ms.round() as i32).unwrap_or(-1);
     
-    let child = command.spawn()?;
+
     
-    // Enregistrer le processus dans les exports actifs
-    let process_ref = Arc::new(Mutex::new(Some(child)));
-    {
-        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
-        active_exports.insert(export_id.to_string(), process_ref.clone());
-    }
+    // Timeline et chemins
+    let ts: Vec<i32> = files
+        .iter()
+        .map(|p| stem_timestamp_ms(p).map(|ms| ms.round() as i32).unwrap_or(0))
+        .collect();
     
-    let stderr = {
-        let mut child_guard = process_ref.lock().map_err(|_| "Failed to lock child process")?;
-        if let Some(ref mut child) = child_guard.as_mut() {
-            child.stderr.take().ok_or("Failed to capture stderr")?
-        } else {
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "Process was cancelled")));
+    // Sélection par indices : on garde les images choisies et on cale début/durée sur leurs timestamps
+    let (files, ts, start_time, duration) = match options.index_range {
+        None => (files, ts, start_time, duration),
+        Some((first, last)) => {
+            if first > last || last >= files.len() {
+                return Err(format!(
+                    "{}: [{}, {}] hors de [0, {}]",
+                    INVALID_INDEX_RANGE_ERROR, first, last, files.len() - 1
+                ));
+            }
+            let start_ms = ts[first];
+            // Hors fin de projet, la dernière image dure jusqu'au timestamp de la suivante
+            // (sauf `tail_ms` explicite)
+            let duration = match ts.get(last + 1) {
+                Some(&next_ms) => {
+                    options.tail_ms.get_or_insert(next_ms - ts[last]);
+                    Some(next_ms - start_ms)
+                }
+                None => None,
+            };
+            options.ayah_backgrounds = options.ayah_backgrounds.take().map(|map| select_ayah_backgrounds(map, first, last));
+            log_out!(
+                "[scan] Sélection des images {}..={} : start_time={} ms, duration={:?} ms",
+                first, last, start_ms, duration
+            );
+            (files[first..=last].to_vec(), ts[first..=last].to_vec(), start_ms, duration)
         }
     };
+
+    let path_strs: Vec<String> = files
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
     
-    // Lire la sortie stderr pour capturer la progression
-    let reader = BufReader::new(stderr);
-    let mut stderr_content = String::new();
+    let ts_preview: Vec<i32> = ts.iter().take(10).cloned().collect();
+    log_out!("[timeline] Premiers timestamps: {:?}{}", ts_preview, if ts.len() > 10 { " ..." } else { "" });
+    log_out!("[timeline] Nombre d'images: {}", ts.len());
     
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            println!("[ffmpeg] {}", line); // Debug: afficher toutes les lignes
-            
-            // Sauvegarder toutes les lignes stderr pour le debugging
-            stderr_content.push_str(&line);
-            stderr_content.push('\n');
-            
-            // Chercher les lignes de progression FFmpeg qui contiennent "time=" ou "out_time_ms="
-            if line.contains("time=") || line.contains("out_time_ms=") {
-                if let Some(time_str) = extract_time_from_ffmpeg_line(&line) {
-                    let current_time_s = parse_ffmpeg_time(&time_str);
-                    let progress = if duration_s > 0.0 {
-                        (current_time_s / duration_s * 100.0).min(100.0)
-                    } else {
-                        0.0
-                    };
-                    
-                    println!("[progress] {}% ({:.1}s / {:.1}s)", progress.round(), current_time_s, duration_s);
-                    
-                    // Préparer les données de progression
-                    let mut progress_data = serde_json::json!({
-                        "export_id": export_id,
-                        "progress": progress,
-                        "current_time": current_time_s,
-                        "total_time": duration_s
-                    });
-                    
-                    // Ajouter chunk_index si fourni
-                    if let Some(chunk_idx) = chunk_index {
-                        progress_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
-                    }
-                    
-                    // Émettre l'événement de progression vers le frontend
-                    let _ = app_handle.emit("export-progress", progress_data);
-                }
+    // Taille cible = taille de 0.png
+    // Une image illisible est ignorée (frame transparente côté rendu) : on prend la première image décodable
+    log_out!("[image] Ouverture de la première image pour taille cible...");
+    let target_size = files
+        .iter()
+        .find_map(|p| match fs::read(p).map_err(|e| e.to_string()).and_then(|d| image::load_from_memory(&d).map_err(|e| e.to_string())) {
+            Ok(img) => Some((img.width() as i32, img.height() as i32)),
+            Err(e) => {
+                eprintln!("[image] ⚠️ Image corrompue ignorée {}: {}", p.display(), e);
+                None
             }
-        }
-    }
+        })
+        .ok_or("Aucune image .png décodable dans imgs_folder".to_string())?;
     
-    // Attendre la fin du processus
-    let status = {
-        let mut child_guard = process_ref.lock().map_err(|_| "Failed to lock child process")?;
-        if let Some(mut child) = child_guard.take() {
-            child.wait()?
-        } else {
-            // Le processus a été annulé
-            let error_msg = format!("Export {} was cancelled", export_id);
-            let mut error_data = serde_json::json!({
-                "export_id": export_id,
-                "error": error_msg
-            });
-            
-            // Ajouter chunk_index si fourni
-            if let Some(chunk_idx) = chunk_index {
-                error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+    log_out!("[image] Taille cible: {}x{}", target_size.0, target_size.1);
+
+    // Aperçu : résolution plafonnée, les sous-titres sont réduits au décodage
+    let target_size = if options.preview && target_size.1 > PREVIEW_MAX_HEIGHT {
+        let w = (target_size.0 as i64 * PREVIEW_MAX_HEIGHT as i64 / target_size.1 as i64) as i32;
+        log_out!("[preview] Taille réduite à {}x{}", w, PREVIEW_MAX_HEIGHT);
+        (w, PREVIEW_MAX_HEIGHT)
+    } else {
+        target_size
+    };
+    // Plafond appliqué ici pour le mode Fast, le nom de sortie et l'estimation disque ; la taille
+    // arrive plafonnée à `start_streaming_export`, dont le contrôle ne change alors plus rien
+    let target_size = check_resolution_cap(&app, &export_id, &options, target_size)?;
+
+    // yuv420p exige des dimensions paires : on arrondit au pair supérieur (le pad du filtre comble)
+    let target_size = {
+        let even = even_dimensions(target_size.0, target_size.1);
+        if even != target_size {
+            log_out!(
+                "[image] Dimensions impaires {}x{} ajustées à {}x{} pour yuv420p",
+                target_size.0, target_size.1, even.0, even.1
+            );
+        }
+        even
+    };
+
+    let out_path = match &name_template {
+        Some(template) => {
+            let unix_s = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let title = options.title.as_deref().unwrap_or("export");
+            let name = render_output_name(template, title, target_size, options.video_codec(template), unix_s);
+            let path = out_path.join(name);
+            log_out!("[fs] Fichier de sortie (modèle {:?}): {:?}", template, path);
+            if image_sequence_pattern(&path.to_string_lossy(), &options).is_none() {
+                check_codec_container(&path.to_string_lossy(), &options)?;
             }
-            
-            let _ = app_handle.emit("export-error", error_data);
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Interrupted, error_msg)));
+            path
         }
+        None => out_path,
     };
+
+    // Durée totale
+    let fade_ms = fade_duration;
+    let tail_ms = resolve_tail_ms(options.caption_fade_ms(fade_ms).1, options.tail_ms);
+    let total_duration_ms = ts[ts.len() - 1] + tail_ms + options.end_padding_ms.unwrap_or(0).max(0);
+    let duration_s = total_duration_ms as f64 / 1000.0;
+    log_out!("[timeline] Durée totale: {} ms ({:.3} s)", total_duration_ms, duration_s);
+    log_out!("[perf] Préparation terminée en {:.0} ms", t0.elapsed().as_millis());
     
-    // Nettoyer les exports actifs
-    {
-        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
-        active_exports.remove(export_id);
-    }
+    let imgs_folder_resolved = folder.canonicalize()
+        .unwrap_or_else(|_| folder.clone())
+        .to_string_lossy()
+        .to_string();
     
-    if !status.success() {
-        // Créer un fichier de log avec la date d'aujourd'hui
-        let now = std::time::SystemTime::now();
-        let timestamp = now.duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let log_filename = format!("ffmpeg_failed_{}.txt", timestamp);
-        
-        let log_content = format!(
-            "FFmpeg Export Failure Log\n\
-             =========================\n\
-             Timestamp: {}\n\
-             Export ID: {}\n\
-             Exit Code: {:?}\n\
-             \n\
-             FFmpeg Command:\n\
-             {}\n\
-             \n\
-             Standard Error Output:\n\
-             {}\n",
-            timestamp,
-            export_id,
-            status.code(),
-            cmd.join(" "),
-            if stderr_content.is_empty() {
-                "No stderr output captured".to_string()
-            } else {
-                stderr_content
+    let out_path_str = out_path.to_string_lossy().to_string();
+    let out_path_str_for_task = out_path_str.clone();
+    let audios_vec: Vec<String> = audios
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| path_utils::normalize_existing_path(&p).to_string_lossy().to_string())
+        .collect();
+    let videos_vec: Vec<String> = videos
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| path_utils::normalize_existing_path(&p).to_string_lossy().to_string())
+        .collect();
+
+    let (start_time, duration) = match audios_vec.first() {
+        Some(first) if options.trim_silence => {
+            let delay_ms = options.audio_delays_ms.as_ref().and_then(|d| d.first().copied()).unwrap_or(0);
+            let (start, dur) = skip_leading_silence(start_time, duration, leading_silence_s(first), delay_ms);
+            if start != start_time {
+                log_out!("[audio] Silence de début sauté : start_time {} → {} ms, duration={:?} ms", start_time, start, dur);
             }
-        );
-        
-        // Écrire le fichier de log
-        if let Err(log_err) = std::fs::write(&log_filename, &log_content) {
-            eprintln!("Failed to write log file {}: {}", log_filename, log_err);
+            (start, dur)
+        }
+        _ => (start_time, duration),
+    };
+
+    // Pré-vérification de l'espace disque : sortie finale + clips de fond prétraités
+    {
+        let export_s = duration.map(|d| d as f64 / 1000.0).unwrap_or(duration_s);
+        let (w, h) = target_size;
+        let output_bytes = if to_stdout {
+            0
+        } else {
+            estimate_output_bytes(export_s, "libx264", w, h, fps.rounded() as i32, None, None, !audios_vec.is_empty())
+        };
+        let cache_bytes = if videos_vec.is_empty() {
+            0
         } else {
-            println!("FFmpeg error details saved to: {}", log_filename);
+            estimate_output_bytes(export_s, "libx264", w, h, fps.rounded() as i32, None, None, false)
+        };
+        let out_dir = out_path.parent().unwrap_or_else(|| Path::new("."));
+        check_disk_space(out_dir, output_bytes, cache_bytes)?;
+    }
+
+    let app_handle = app.clone();
+    let export_id_clone = export_id.clone();
+    
+    let scan_s = t0.elapsed().as_secs_f64();
+    if options.mode == ExportMode::Fast {
+        // Graphe FFmpeg seul, sans GPU : l'export est terminé au retour
+        log_out!("[start_export] Mode fast : pipeline FFmpeg sans rendu WGPU");
+        if options.karaoke.as_ref().is_some_and(|cues| !cues.is_empty()) {
+            eprintln!("[start_export] ⚠️ Karaoké ignoré : le texte n'est rendu que par le pipeline WGPU");
         }
-        
-        let error_msg = format!(
-            "ffmpeg failed during video exportation (exit code: {:?})\n\nSee the log file: {}\n\nLog details:\n{}", 
-            status.code(), 
-            log_filename,
-            log_content
-        );
-        let mut error_data = serde_json::json!({
-            "export_id": export_id,
-            "error": error_msg
-        });
-        
-        // Ajouter chunk_index si fourni
-        if let Some(chunk_idx) = chunk_index {
-            error_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+        let prefer_hw = !options.preview && should_prefer_hw_encoding();
+        // Seul le graphe FFmpeg reçoit un sigma : la session WGPU convertit elle-même la valeur brute
+        let blur = options.blur_sigma(blur, target_size.1);
+        let (id, out, app_task) = (export_id.clone(), out_path_str_for_task.clone(), app.clone());
+        // Médias mis en cache libérés une fois FFmpeg terminé ; un chunk les laisse aux suivants
+        let _staged = chunk_index.is_none().then(|| StagedMediaRelease(&export_id));
+        let mut report = tokio::task::spawn_blocking(move || {
+            build_and_run_ffmpeg_filter_complex(
+                &id, &out, &path_strs, &ts, target_size, fps, fade_ms, start_time, &audios_vec, &videos_vec,
+                prefer_hw, Some(&imgs_folder_resolved), duration, chunk_index, blur, &options, app_task,
+            )
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| format!("FFmpeg export interrompu: {}", e))?
+        .map_err(|e| format!("FFmpeg Export error: {}", e))?;
+        report.stage_timings.insert("scan".to_string(), scan_s);
+        report.wall_clock_s = t0.elapsed().as_secs_f64();
+        emit_export_complete(&app, &export_id, &report, chunk_index);
+        return Ok(report);
+    }
+
+    let is_high_fidelity = true; // Assuming this is the intended value for the new variable
+    let report = start_streaming_export(
+        export_id.clone(),
+        out_path_str_for_task,
+        ts,
+        target_size,
+        fps,
+        fade_ms,
+        start_time,
+        audios_vec,
+        videos_vec,
+        !options.preview && should_prefer_hw_encoding(),
+        duration,
+        chunk_index,
+        blur,
+        None, // overlay_color
+        None, // overlay_opacity
+        None, // overlay_enable
+        is_high_fidelity,
+        Some(path_strs),
+        // Cadence déjà résolue ci-dessus : pas de seconde sonde du fond
+        Some(ExportOptions { match_source_fps: false, ..options }),
+        app_handle,
+    ).await.map_err(|e| format!("WGPU Export error: {}", e))?;
+    // Session prête mais aucune frame encore reçue : `export-complete` et le rapport final
+    // viennent de `finish_streaming_export`, dont le rapport reçoit aussi le scan
+    if let Some(session) = WGPU_STREAMS.lock().unwrap().get(&export_id) {
+        if let Ok(mut session_report) = session.report.lock() {
+            session_report.stage_timings.insert("scan".to_string(), scan_s);
         }
-        
-        let _ = app_handle.emit("export-error", error_data);
-        return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, error_msg)));
     }
-    
-    Ok(())
+    log_out!("[start_export] Session WGPU prête en {:.2}s", t0.elapsed().as_secs_f64());
+
+    Ok(report)
 }
 
+const PREVIEW_MAX_HEIGHT: i32 = 480;
+const PREVIEW_DEFAULT_SECONDS: u32 = 15;
+
+// Aperçu basse résolution des premières secondes : même pipeline que `export_video`
+// (fonds, sous-titres, effets), encodé vite dans un fichier temporaire dont le chemin est renvoyé
 #[tauri::command]
-pub async fn export_video(
+pub async fn export_preview(
     export_id: String,
     imgs_folder: String,
-    final_file_path: String,
-    fps: i32,
+    fps: FrameRate,
     fade_duration: i32,
     start_time: i32,
-    duration: Option<i32>,
+    seconds: Option<u32>,
     audios: Option<Vec<String>>,
     videos: Option<Vec<String>>,
-    chunk_index: Option<i32>,
     blur: Option<f64>,
+    options: Option<ExportOptions>,
     app: tauri::AppHandle,
 ) -> Result<String, String> {
-    let t0 = Instant::now();
-    
-    // Logs init
-    println!("[start_export] export_id={}", export_id);
-    println!("[start_export] imgs_folder={}", imgs_folder);
-    println!("[start_export] final_file_path={}", final_file_path);
-    println!("[start_export] fps={}, fade_duration(ms)={}", fps, fade_duration);
-    println!("[env] CPU cores: {:?}", std::thread::available_parallelism().map(|n| n.get()));
-    
-    if let Some(ref audios) = audios {
-        println!("[audio] {} fichier(s) audio fourni(s)", audios.len());
-    } else {
-        println!("[audio] aucun fichier audio fourni");
+    let seconds = seconds.unwrap_or(PREVIEW_DEFAULT_SECONDS).max(1);
+    let out = app_temp_dir("preview").join(format!("{}.mp4", sanitize_file_name(&export_id)));
+    fs::create_dir_all(out.parent().unwrap_or_else(|| Path::new("."))).map_err(|e| format!("Erreur création dossier: {}", e))?;
+    let options = ExportOptions { preview: true, ..options.unwrap_or_default() };
+    log_out!("[preview] {} s -> {}", seconds, out.display());
+    let report = export_video(
+        export_id, imgs_folder, out.to_string_lossy().to_string(), fps, fade_duration, start_time,
+        Some((seconds * 1000) as i32), audios, videos, None, blur, Some(options), app,
+    ).await?;
+    Ok(report.output_path)
+}
+
+/// Données d'un événement `export-progress`, communes aux chemins FFmpeg et WGPU.
+/// `frame` est absent tant que FFmpeg n'a annoncé aucune frame.
+fn progress_event(
+    export_id: &str,
+    chunk_index: Option<i32>,
+    progress: f64,
+    current_time_s: f64,
+    duration_s: f64,
+    frame: Option<u64>,
+    total_frames: u64,
+) -> serde_json::Value {
+    let mut progress_data = serde_json::json!({
+        "export_id": export_id,
+        "progress": progress,
+        "current_time": current_time_s,
+        "total_time": duration_s,
+        "frame": frame,
+        "total_frames": total_frames
+    });
+
+    // Ajouter chunk_index si fourni
+    if let Some(chunk_idx) = chunk_index {
+        progress_data["chunk_index"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+        let overall = CHUNK_PLANS.lock().ok().and_then(|plans| {
+            plans.get(export_id)?.overall_progress(chunk_idx.max(0) as usize, progress)
+        });
+        if let Some(overall) = overall {
+            progress_data["overall_progress"] = serde_json::json!(overall);
+        }
     }
-    
-    if let Some(ref videos) = videos {
-        println!("[video] {} fichier(s) vidéo fourni(s)", videos.len());
-    } else {
-        println!("[video] aucune vidéo de fond fournie");
+    progress_data
+}
+
+// Fonctions utilitaires pour parser la progression FFmpeg
+// `frame=  123 fps=...` (statistiques) ou `frame=123` (-progress)
+fn extract_frame_from_ffmpeg_line(line: &str) -> Option<u64> {
+    let start = line.find("frame=")? + 6; // Longueur de "frame="
+    let rest = line[start..].trim_start();
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+fn extract_time_from_ffmpeg_line(line: &str) -> Option<String> {
+    // Chercher "time=" dans la ligne et extraire la valeur
+    if let Some(start) = line.find("time=") {
+        let start = start + 5; // Longueur de "time="
+        if let Some(end) = line[start..].find(char::is_whitespace) {
+            return Some(line[start..start + end].to_string());
+        } else {
+            // Si pas d'espace trouvé, prendre jusqu'à la fin
+            return Some(line[start..].to_string());
+        }
     }
     
-    // Liste des PNG triés par timestamp
-    let folder = path_utils::normalize_existing_path(&imgs_folder);
-    println!("[scan] Parcours du dossier: {:?}", folder.canonicalize().unwrap_or_else(|_| folder.clone()));
-    
-    let mut files: Vec<_> = fs::read_dir(&folder)
-        .map_err(|e| format!("Erreur lecture dossier: {}", e))?
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let path = entry.path();
-            if path.extension()?.to_str()?.to_lowercase() == "png" {
-                Some(path)
-            } else {
-                None
+    // Aussi chercher le format "out_time_ms=" pour -progress pipe
+    if let Some(start) = line.find("out_time_ms=") {
+        let start = start + 12; // Longueur de "out_time_ms="
+        if let Some(end) = line[start..].find(char::is_whitespace) {
+            if let Ok(ms) = line[start..start + end].parse::<i64>() {
+                let seconds = ms as f64 / 1_000_000.0; // microseconds to seconds
+                return Some(format!("{:.3}", seconds));
             }
-        })
-        .collect();
-    
-    files.sort_by_key(|p| {
-        p.file_stem()
-            .and_then(|s| s.to_str())
-            .and_then(|s| s.parse::<i32>().ok())
-            .unwrap_or(0)
-    });
-    let files: Vec<PathBuf> = files
-        .into_iter()
-        .map(|p| p.canonicalize().unwrap_or(p))
-        .collect();
-    
-    println!("[scan] {} image(s) trouvée(s)", files.len());
-    
-    if files.is_empty() {
-        return Err("Aucune image .png trouvée dans imgs_folder".to_string());
+        }
     }
     
-    let _first_stem = files[0]
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .and_then(|s| s.parse::<i32>().ok())
-        .unwrap_or(-1);
-    
+    None
+}
 
+fn parse_ffmpeg_time(time_str: &str) -> f64 {
+    // Si c'est déjà en secondes (format décimal)
+    if let Ok(seconds) = time_str.parse::<f64>() {
+        return seconds;
+    }
     
-    // Timeline et chemins
-    let ts: Vec<i32> = files
-        .iter()
-        .map(|p| {
-            p.file_stem()
-                .and_then(|s| s.to_str())
-                .and_then(|s| s.parse::<i32>().ok())
-                .unwrap_or(0)
-        })
-        .collect();
-    
-    let path_strs: Vec<String> = files
-        .iter()
-        .map(|p| p.to_string_lossy().to_string())
+    // Format FFmpeg : HH:MM:SS.mmm
+    let parts: Vec<&str> = time_str.split(':').collect();
+    if parts.len() == 3 {
+        if let (Ok(hours), Ok(minutes), Ok(seconds)) = 
+            (parts[0].parse::<f64>(), parts[1].parse::<f64>(), parts[2].parse::<f64>()) {
+            return hours * 3600.0 + minutes * 60.0 + seconds;
+        }
+    }
+    0.0
+}
+
+// Débit audio de l'encodeur final (AAC 320k)
+const AUDIO_BITRATE_BPS: f64 = 320_000.0;
+
+/// Estimation grossière de la taille de sortie, en octets.
+/// Avec un débit cible, c'est `débit × durée` ; en mode CRF on part d'environ 0.08 bit/pixel
+/// pour H.264 à CRF 23, le débit étant divisé par deux tous les +6 de CRF.
+/// Le résultat vise le bon ordre de grandeur, pas la précision.
+#[allow(clippy::too_many_arguments)]
+fn estimate_output_bytes(
+    duration_s: f64,
+    codec: &str,
+    width: i32,
+    height: i32,
+    fps: i32,
+    crf: Option<i32>,
+    bitrate_kbps: Option<u32>,
+    has_audio: bool,
+) -> u64 {
+    let video_bps = match bitrate_kbps {
+        Some(kbps) => kbps as f64 * 1000.0,
+        None => {
+            let codec_factor = match codec {
+                c if c.contains("265") || c.contains("hevc") => 0.6,
+                c if c.contains("vp9") => 0.65,
+                c if c.contains("av1") => 0.5,
+                _ => 1.0,
+            };
+            let crf = crf.unwrap_or(23) as f64;
+            let bits_per_pixel = 0.08 * codec_factor * 2f64.powf((23.0 - crf) / 6.0);
+            bits_per_pixel * (width.max(0) as f64) * (height.max(0) as f64) * (fps.max(0) as f64)
+        }
+    };
+    let audio_bps = if has_audio { AUDIO_BITRATE_BPS } else { 0.0 };
+    ((video_bps + audio_bps) * duration_s.max(0.0) / 8.0).round() as u64
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_output_size(
+    duration_s: f64,
+    codec: String,
+    width: i32,
+    height: i32,
+    fps: i32,
+    crf: Option<i32>,
+    bitrate_kbps: Option<u32>,
+    has_audio: bool,
+) -> u64 {
+    estimate_output_bytes(duration_s, &codec, width, height, fps, crf, bitrate_kbps, has_audio)
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderBenchmark {
+    pub codec: String,
+    /// Images encodées par seconde.
+    pub fps: f64,
+    /// Durée du clip / temps d'encodage (ex. 8.0 = 8× le temps réel).
+    pub realtime_factor: f64,
+}
+
+const BENCHMARK_FPS: u32 = 30;
+
+// Mesure la vitesse de chaque encodeur disponible sur un clip `testsrc` 1080p de `seconds` secondes.
+// Les encodages s'enchaînent hors du fil async.
+#[tauri::command]
+pub async fn benchmark_encoders(seconds: u32) -> Result<Vec<EncoderBenchmark>, String> {
+    let exe = require_ffmpeg_binary()?;
+    tokio::task::spawn_blocking(move || run_encoder_benchmarks(&exe, seconds.max(1)))
+        .await
+        .map_err(|e| format!("Benchmark interrompu: {}", e))
+}
+
+fn run_encoder_benchmarks(exe: &str, seconds: u32) -> Vec<EncoderBenchmark> {
+    let mut codecs: Vec<String> = detect_hw_encoders(Some(exe))
+        .into_iter()
+        .filter(|c| c != "h264_nvenc" || test_nvenc_availability(Some(exe)))
         .collect();
-    
-    let ts_preview: Vec<i32> = ts.iter().take(10).cloned().collect();
-    println!("[timeline] Premiers timestamps: {:?}{}", ts_preview, if ts.len() > 10 { " ..." } else { "" });
-    println!("[timeline] Nombre d'images: {}", ts.len());
-    
-    // Taille cible = taille de 0.png
-    println!("[image] Ouverture de la première image pour taille cible...");
-    let target_size = {
-        let img_data = fs::read(&files[0]).map_err(|e| format!("Erreur lecture image: {}", e))?;
-        let img = image::load_from_memory(&img_data).map_err(|e| format!("Erreur décodage image: {}", e))?;
-        (img.width() as i32, img.height() as i32)
+    codecs.push("libx264".to_string());
+
+    let source = format!("testsrc=duration={}:size=1920x1080:rate={}", seconds, BENCHMARK_FPS);
+    let frames = (seconds * BENCHMARK_FPS) as f64;
+    let mut results = Vec::new();
+    for codec in codecs {
+        let mut cmd = Command::new(exe);
+        cmd.args(["-hide_banner", "-loglevel", "error", "-f", "lavfi", "-i", &source])
+            .args(["-c:v", &codec, "-pix_fmt", "yuv420p", "-f", "null", "-"]);
+        configure_command_no_window(&mut cmd);
+
+        let t0 = Instant::now();
+        match cmd.output() {
+            Ok(out) if out.status.success() => {
+                let elapsed = t0.elapsed().as_secs_f64().max(1e-6);
+                log_out!("[benchmark] {}: {:.1} fps ({:.2}x temps réel)", codec, frames / elapsed, seconds as f64 / elapsed);
+                results.push(EncoderBenchmark {
+                    codec,
+                    fps: frames / elapsed,
+                    realtime_factor: seconds as f64 / elapsed,
+                });
+            }
+            Ok(out) => eprintln!("[benchmark] {} a échoué: {}", codec, String::from_utf8_lossy(&out.stderr).trim()),
+            Err(e) => eprintln!("[benchmark] Impossible d'exécuter FFmpeg pour {}: {}", codec, e),
+        }
+    }
+    results
+}
+
+const INVALID_IMAGE_OUTPUT_ERROR: &str = "INVALID_IMAGE_OUTPUT";
+
+// Qualité WebP par défaut, visuellement sans perte (le JPEG prend la meilleure échelle, `-q:v 2`)
+const DEFAULT_WEBP_QUALITY: u8 = 90;
+
+// Arguments d'encodage d'une image selon l'extension de `out` ; `quality` va de 1 (pire) à 100.
+// Le PNG est sans perte et ignore la qualité
+fn image_quality_args(out: &str, quality: Option<u8>) -> Result<Vec<String>, String> {
+    if let Some(q) = quality.filter(|q| !(1..=100).contains(q)) {
+        return Err(format!("{}: qualité {} (valeurs acceptées : 1 à 100)", INVALID_IMAGE_OUTPUT_ERROR, q));
+    }
+    match output_extension(out).as_str() {
+        "png" => Ok(Vec::new()),
+        // Échelle MJPEG inversée : 2 (meilleure) à 31
+        "jpg" | "jpeg" => {
+            let qscale = quality.map_or(2, |q| 31 - (q as u32 - 1) * 29 / 99);
+            Ok(vec!["-q:v".to_string(), qscale.to_string()])
+        }
+        "webp" => Ok(vec!["-quality".to_string(), quality.unwrap_or(DEFAULT_WEBP_QUALITY).to_string()]),
+        other => Err(format!("{}: format d'image non supporté .{} (png, jpg, webp)", INVALID_IMAGE_OUTPUT_ERROR, other)),
+    }
+}
+
+// Miniature de forme d'onde pour l'éditeur (indépendant de l'export)
+#[tauri::command]
+pub async fn render_waveform(audio: String, width: u32, height: u32, out: String, quality: Option<u8>) -> Result<String, String> {
+    if width == 0 || height == 0 {
+        return Err("Dimensions de forme d'onde invalides".to_string());
+    }
+    let quality_args = image_quality_args(&out, quality)?;
+    let audio = path_utils::normalize_existing_path(&audio).to_string_lossy().to_string();
+    let exe = require_ffmpeg_binary()?;
+
+    let mut cmd = Command::new(&exe);
+    cmd.args([
+        "-y", "-hide_banner", "-loglevel", "error",
+        "-i", &audio,
+        "-filter_complex", &format!("showwavespic=s={}x{}", width, height),
+        "-frames:v", "1",
+    ]);
+    cmd.args(&quality_args).arg(&out);
+    configure_command_no_window(&mut cmd);
+
+    log_out!("[waveform] {} -> {} ({}x{})", audio, out, width, height);
+    let output = tokio::task::spawn_blocking(move || cmd.output())
+        .await
+        .map_err(|e| format!("FFmpeg interrompu: {}", e))?
+        .map_err(|e| format!("Impossible d'exécuter FFmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Erreur FFmpeg (forme d'onde): {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(out)
+}
+
+// Largeur (px) de chaque vignette de la planche contact
+const CONTACT_SHEET_THUMB_WIDTH: u32 = 320;
+
+// Planche contact : `cols`×`rows` images régulièrement espacées sur toute la durée de la vidéo
+#[tauri::command]
+pub async fn contact_sheet(video: String, cols: u32, rows: u32, out: String, quality: Option<u8>) -> Result<String, String> {
+    if cols == 0 || rows == 0 {
+        return Err("Grille de planche contact invalide".to_string());
+    }
+    let quality_args = image_quality_args(&out, quality)?;
+    let video = path_utils::normalize_existing_path(&video).to_string_lossy().to_string();
+    let exe = require_ffmpeg_binary()?;
+    // Sonde et extraction hors du fil async
+    tokio::task::spawn_blocking(move || render_contact_sheet(&exe, &video, cols, rows, out, &quality_args))
+        .await
+        .map_err(|e| format!("FFmpeg interrompu: {}", e))?
+}
+
+fn render_contact_sheet(exe: &str, video: &str, cols: u32, rows: u32, out: String, quality_args: &[String]) -> Result<String, String> {
+    let duration_s = ffprobe_duration_sec(video);
+    if duration_s <= 0.0 {
+        return Err(format!("Durée de la vidéo introuvable: {}", video));
+    }
+    let interval_s = duration_s / (cols * rows) as f64;
+
+    // Une image dès qu'au moins `interval_s` s'est écoulé depuis la précédente retenue
+    let filter = format!(
+        "select='isnan(prev_selected_t)+gte(t-prev_selected_t,{:.6})',scale={}:-2,tile={}x{}",
+        interval_s, CONTACT_SHEET_THUMB_WIDTH, cols, rows
+    );
+    let mut cmd = Command::new(exe);
+    cmd.args([
+        "-y", "-hide_banner", "-loglevel", "error",
+        "-i", video,
+        "-vf", &filter,
+        "-fps_mode", "vfr",
+        "-frames:v", "1",
+    ]);
+    cmd.args(quality_args).arg(&out);
+    configure_command_no_window(&mut cmd);
+
+    log_out!("[contact_sheet] {} -> {} ({}x{}, une image toutes les {:.2}s)", video, out, cols, rows, interval_s);
+    let output = cmd.output().map_err(|e| format!("Impossible d'exécuter FFmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Erreur FFmpeg (planche contact): {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(out)
+}
+
+/// Carton titre (dégradé + titre centré) rendu sur le GPU, à placer avant la récitation
+/// avec `concat_videos`.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IntroCard {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub duration_ms: u64,
+    /// Couleur `#RRGGBB` du haut ; seule, elle donne un fond uni.
+    pub top_color: String,
+    /// Couleur `#RRGGBB` du bas pour un dégradé vertical.
+    pub bottom_color: Option<String>,
+    pub text_color: String,
+    /// Taille de police en pixels (défaut : 1/15 de la hauteur).
+    pub font_size: Option<f32>,
+    /// Police TTF/OTF ou famille installée, essayée avant la police système.
+    pub font: Option<String>,
+    /// Fondu au noir en entrée et en sortie.
+    pub fade_ms: u64,
+    pub backend: Option<crate::renderer::GpuBackend>,
+}
+
+impl Default for IntroCard {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            duration_ms: 3000,
+            top_color: "#000000".to_string(),
+            bottom_color: None,
+            text_color: "#ffffff".to_string(),
+            font_size: None,
+            font: None,
+            fade_ms: 500,
+            backend: None,
+        }
+    }
+}
+
+const INVALID_INTRO_CARD_ERROR: &str = "INVALID_INTRO_CARD";
+
+type Rgb = [u8; 3];
+
+fn parse_hex_rgb(hex: &str) -> Option<Rgb> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+impl IntroCard {
+    // Haut, bas et texte
+    fn colors(&self) -> Result<(Rgb, Rgb, Rgb), String> {
+        let parse = |c: &str| parse_hex_rgb(c).ok_or_else(|| format!("{}: couleur invalide {}", INVALID_INTRO_CARD_ERROR, c));
+        let top = parse(&self.top_color)?;
+        let bottom = match &self.bottom_color {
+            Some(c) => parse(c)?,
+            None => top,
+        };
+        Ok((top, bottom, parse(&self.text_color)?))
+    }
+}
+
+// Carton d'introduction : une seule image rendue sur le GPU, bouclée par FFmpeg sur la durée
+// demandée avec une piste silencieuse pour que la concaténation garde l'audio. Codecs, canaux et
+// fréquence suivent les `options` de l'export : `concat_videos` peut alors copier les flux.
+#[tauri::command]
+pub async fn render_intro_card(card: IntroCard, out: String, options: Option<ExportOptions>) -> Result<String, String> {
+    if card.width == 0 || card.height == 0 || card.fps == 0 || card.duration_ms == 0 {
+        return Err(format!("{}: dimensions, fps et durée doivent être positifs", INVALID_INTRO_CARD_ERROR));
+    }
+    let options = options.unwrap_or_default();
+    let out = path_utils::normalize_output_path(&out).to_string_lossy().to_string();
+    let (video_codec, video_params, preset) = options.video_codec(&out).software_encoder();
+    let channels = options.audio_channels();
+    let sample_rate = options.audio_sample_rate()?;
+    let audio_args = resolve_audio_codec_args(&out, options.audio_codec, channels, sample_rate)?;
+    let (top, bottom, text) = card.colors()?;
+    let ffmpeg_bin = require_ffmpeg_binary()?;
+
+    let mut renderer = crate::renderer::Renderer::new(card.width, card.height, card.backend).await?;
+    if let Some(font) = &card.font {
+        renderer.set_font_fallback(vec![font.clone()])?;
+    }
+    let font_size = card.font_size.unwrap_or(renderer.height as f32 / 15.0);
+    renderer.set_text_metrics(font_size, font_size * 1.3)?;
+    renderer.set_text_style(crate::renderer::TextStyle {
+        color: glyphon::Color::rgb(text[0], text[1], text[2]),
+        align: Some(glyphon::cosmic_text::Align::Center),
+        center_vertically: true,
+        ..Default::default()
+    });
+    renderer.fill_gradient(top, bottom);
+    renderer.render_text(&card.title)?;
+    let frame = renderer.read_frame().await?;
+    let (w, h) = (renderer.width, renderer.height);
+    let image = image::RgbaImage::from_raw(w, h, frame)
+        .ok_or_else(|| "Image du carton incomplète".to_string())?;
+
+    let stem = Path::new(&out).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let work_dir = ExportWorkDir::create(&format!("intro-{}", stem), false)
+        .map_err(|e| format!("Erreur création dossier temporaire: {}", e))?;
+    let card_path = work_dir.path().join("intro_card.png");
+    image.save(&card_path).map_err(|e| format!("Erreur écriture du carton: {}", e))?;
+
+    let duration_s = card.duration_ms as f64 / 1000.0;
+    let fade_s = (card.fade_ms as f64 / 1000.0).min(duration_s / 2.0);
+    let mut filters = vec!["format=yuv420p".to_string()];
+    if fade_s > 0.0 {
+        filters.push(format!("fade=t=in:d={:.3},fade=t=out:st={:.3}:d={:.3}", fade_s, duration_s - fade_s, fade_s));
+    }
+    let layout = if channels == 1 { "mono" } else { "stereo" };
+    let mut cmd = Command::new(&ffmpeg_bin);
+    cmd.args(["-y", "-hide_banner", "-loglevel", "error", "-loop", "1", "-framerate", &card.fps.to_string(), "-i"])
+        .arg(&card_path)
+        .args([
+            "-f", "lavfi", "-i", &format!("anullsrc=r={}:cl={}", sample_rate, layout),
+            "-t", &format!("{:.3}", duration_s),
+            "-vf", &filters.join(","),
+            "-c:v", video_codec,
+        ])
+        .args(&video_params);
+    if let Some(preset) = &preset {
+        cmd.args(["-preset", preset]);
+    }
+    cmd.args(&audio_args).args(["-ar", &sample_rate.to_string(), "-shortest", &out]);
+    configure_command_no_window(&mut cmd);
+
+    log_out!("[intro_card] \"{}\" -> {} ({}x{}, {:.2}s, {})", card.title, out, w, h, duration_s, video_codec);
+    let output = tokio::task::spawn_blocking(move || cmd.output())
+        .await
+        .map_err(|e| format!("FFmpeg interrompu: {}", e))?
+        .map_err(|e| format!("Impossible d'exécuter FFmpeg: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&out);
+        return Err(format!("Erreur FFmpeg (carton d'introduction): {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(out)
+}
+
+const INVALID_FILTER_ERROR: &str = "INVALID_FILTER";
+
+// Exécution à blanc du graphe de filtres : mêmes timings et même graphe que l'export,
+// mais sous-titres et fonds remplacés par des mires lavfi (de même durée) et une seule frame
+// décodée vers `-f null`. Détourage et fonds par sous-titre ne sont pas vérifiés.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn validate_filter(
+    timestamps_ms: Vec<i32>,
+    target_size: (i32, i32),
+    fps: FrameRate,
+    fade_duration_ms: i32,
+    start_time_ms: i32,
+    duration_ms: Option<i32>,
+    audios: Option<Vec<String>>,
+    videos: Option<Vec<String>>,
+    options: Option<ExportOptions>,
+) -> Result<(), String> {
+    let exe = require_ffmpeg_binary()?;
+    let options = options.unwrap_or_default();
+    let (w, h) = target_size;
+    let n = timestamps_ms.len();
+    if n == 0 {
+        return Err(format!("{}: aucun timestamp fourni", INVALID_FILTER_ERROR));
+    }
+    let fade = options.clip_fade(fade_duration_ms, fps);
+    let fade_out_ms = options.caption_fade_ms(fade_duration_ms).1;
+    let timings = calculate_export_timings(&timestamps_ms, fps, fade_out_ms, start_time_ms, duration_ms, options.tail_ms, options.end_padding_ms);
+    let duration_s = timings.duration_s;
+    let durations_s = image_track_durations(&timings.durations_s, duration_s - timings.end_padding_s);
+    let audio_paths = audios.unwrap_or_default();
+    let bg_videos = video_backgrounds(&videos.unwrap_or_default(), &options)?;
+
+    let mut cmd: Vec<String> = vec!["-hide_banner".to_string(), "-loglevel".to_string(), "error".to_string()];
+    // Entrée 0 : piste de sous-titres transparente couvrant toute la durée
+    cmd.extend_from_slice(&[
+        "-f".to_string(), "lavfi".to_string(),
+        "-i".to_string(), format!("color=c=black@0.0:s={}x{}:r={}:d={:.6},format=rgba", w, h, fps, duration_s),
+    ]);
+    let mut current_idx = 1;
+    let bg_start_idx = current_idx;
+    // Les fonds réels restent sondés par le graphe (durées) mais sont lus sous forme de mires
+    let bg_durations_s = if bg_videos.is_empty() { Vec::new() } else { options.background_durations_s(&bg_videos) };
+    let bg_inputs: Vec<f64> = if bg_videos.is_empty() {
+        Vec::new()
+    } else if options.background_mode == BackgroundMode::Loop {
+        vec![duration_s]
+    } else {
+        bg_durations_s.clone()
     };
-    
-    println!("[image] Taille cible: {}x{}", target_size.0, target_size.1);
-    
-    // Durée totale
-    let fade_ms = fade_duration;
-    let tail_ms = fade_ms.max(1000);
-    let total_duration_ms = ts[ts.len() - 1] + tail_ms;
-    let duration_s = total_duration_ms as f64 / 1000.0;
-    println!("[timeline] Durée totale: {} ms ({:.3} s)", total_duration_ms, duration_s);
-    println!("[perf] Préparation terminée en {:.0} ms", t0.elapsed().as_millis());
-    
-    let out_path = path_utils::normalize_output_path(&final_file_path);
-    if let Some(parent) = out_path.parent() {
-        println!("[fs] Création du dossier de sortie si besoin: {:?}", parent);
-        fs::create_dir_all(parent).map_err(|e| format!("Erreur création dossier: {}", e))?;
+    for d in &bg_inputs {
+        cmd.extend_from_slice(&[
+            "-f".to_string(), "lavfi".to_string(),
+            "-i".to_string(), format!("color=c=gray:s={}x{}:r={}:d={:.6}", w, h, fps, d.max(1.0 / fps.as_f64())),
+        ]);
+        current_idx += 1;
+    }
+    // Masque de révélation : vérifié ici, lu sous forme de mire
+    let reveal_mask_idx = options.reveal_mask.as_deref().map(|path| {
+        load_reveal_mask(path)?;
+        cmd.extend_from_slice(&["-f".to_string(), "lavfi".to_string(), "-i".to_string(), format!("color=c=gray:s={}x{}:r={}", w, h, fps)]);
+        current_idx += 1;
+        Ok::<_, String>(current_idx - 1)
+    }).transpose()?;
+    let audio_start_idx = current_idx;
+    for p in &audio_paths {
+        cmd.extend_from_slice(&["-i".to_string(), p.clone()]);
+        current_idx += 1;
+    }
+
+    let audio = options.audio_options();
+    let filter_ctx = build_filter_complex_content(
+        w, h, fps, fade, n, &durations_s, &[], timings.start_s, duration_s,
+        &bg_videos, bg_durations_s.iter().sum(), &audio_paths, audio_start_idx, bg_start_idx, current_idx, false, false, None, options.background_mode, None,
+        &[], &audio, &CaptionStyle::from_options(&options, reveal_mask_idx),
+    );
+    let overlay_video = options.overlay_video.as_ref().filter(|o| !o.video.is_empty());
+    let (filter_complex, vout_label) = match overlay_video {
+        Some(ov) => (
+            format!("{};{}", filter_ctx.filter_complex, ov.filter(filter_ctx.current_idx, "[vout]", w, h, fps, duration_s)),
+            "[vmotion]",
+        ),
+        None => (filter_ctx.filter_complex, "[vout]"),
+    };
+    let (filter_complex, vout_label) = match &options.stamp {
+        Some(stamp) => (format!("{};{}{}[vstamp]", filter_complex, vout_label, stamp.drawtext_filter(w, h)?), "[vstamp]"),
+        None => (filter_complex, vout_label),
+    };
+    if bg_videos.is_empty() || filter_ctx.total_bg_s <= 1e-6 {
+        cmd.extend_from_slice(&[
+            "-f".to_string(), "lavfi".to_string(),
+            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, duration_s),
+        ]);
+    } else if filter_ctx.total_bg_s + 1e-6 < duration_s && options.background_mode == BackgroundMode::Pad {
+        cmd.extend_from_slice(&[
+            "-f".to_string(), "lavfi".to_string(),
+            "-i".to_string(), format!("color=c=black:s={}x{}:r={}:d={:.6}", w, h, fps, duration_s - filter_ctx.total_bg_s),
+        ]);
     }
-    
-    let imgs_folder_resolved = folder.canonicalize()
-        .unwrap_or_else(|_| folder.clone())
-        .to_string_lossy()
-        .to_string();
-    
-    let out_path_str = out_path.to_string_lossy().to_string();
-    let out_path_str_for_task = out_path_str.clone();
-    let audios_vec: Vec<String> = audios
-        .unwrap_or_default()
-        .into_iter()
-        .map(|p| path_utils::normalize_existing_path(&p).to_string_lossy().to_string())
-        .collect();
-    let videos_vec: Vec<String> = videos
-        .unwrap_or_default()
-        .into_iter()
-        .map(|p| path_utils::normalize_existing_path(&p).to_string_lossy().to_string())
-        .collect();
-    let app_handle = app.clone();
-    let export_id_clone = export_id.clone();
-    
-    let is_high_fidelity = true; // Assuming this is the intended value for the new variable
-    start_streaming_export(
-        export_id.clone(),
-        out_path_str_for_task,
-        ts,
-        target_size,
-        fps,
-        fade_ms,
-        start_time,
-        audios_vec,
-        videos_vec,
-        should_prefer_hw_encoding(),
-        duration,
-        chunk_index,
-        blur,
-        None, // overlay_color
-        None, // overlay_opacity
-        None, // overlay_enable
-        is_high_fidelity,
-        app_handle,
-    ).await.map_err(|e| format!("WGPU Export error: {}", e))?;
-    
-    let export_time_s = t0.elapsed().as_secs_f64();
-    *LAST_EXPORT_TIME_S.lock().unwrap() = Some(export_time_s);
-    println!("[done] Export terminé en {:.2}s", export_time_s);
-    println!("[metric] export_time_seconds={:.3}", export_time_s);
-    
-    // Extraire le nom de fichier de sortie
-    let output_file_name = out_path
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
-    
-    // Préparer les données de completion
-    let mut completion_data = serde_json::json!({
-        "filename": output_file_name,
-        "exportId": export_id,
-        "fullPath": out_path_str
-    });
-    
-    // Ajouter chunk_index si fourni
-    if let Some(chunk_idx) = chunk_index {
-        completion_data["chunkIndex"] = serde_json::Value::Number(serde_json::Number::from(chunk_idx));
+    if let Some(ov) = overlay_video {
+        cmd.extend(ov.input_args());
     }
-    
-    // Émettre l'événement de succès
-    let _ = app.emit("export-complete", completion_data);
-    
-    Ok(out_path_str)
+
+    // Graphe écrit dans un script comme pour l'export : un long projet dépasserait la limite
+    // de longueur de la ligne de commande (environ 32 Ko sous Windows)
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let work_dir = ExportWorkDir::create(&format!("validate-{}", nanos), options.keep_intermediates)
+        .map_err(|e| format!("Erreur création dossier de travail: {}", e))?;
+    let fg_path = work_dir.path().join("filter.ffgraph");
+    fs::write(&fg_path, &filter_complex).map_err(|e| format!("Erreur écriture du graphe: {}", e))?;
+    cmd.extend_from_slice(&["-filter_complex_script".to_string(), fg_path.to_string_lossy().to_string(), "-map".to_string(), vout_label.to_string()]);
+    if filter_ctx.have_audio {
+        cmd.extend(audio.map_args(audio_paths.len()));
+    }
+    cmd.extend_from_slice(&["-frames:v".to_string(), "1".to_string(), "-f".to_string(), "null".to_string(), "-".to_string()]);
+
+    let mut command = Command::new(&exe);
+    command.args(&cmd);
+    configure_command_no_window(&mut command);
+    log_out!("[validate_filter] {} image(s), {:.2}s, {} fond(s), {} audio(s)", n, duration_s, bg_videos.len(), audio_paths.len());
+    let output = tokio::task::spawn_blocking(move || command.output())
+        .await
+        .map_err(|e| format!("FFmpeg interrompu: {}", e))?
+        .map_err(|e| format!("Impossible d'exécuter FFmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        log_out!("[validate_filter][ERREUR] {}", stderr);
+        return Err(format!("{}: {}", INVALID_FILTER_ERROR, stderr));
+    }
+    Ok(())
 }
 
-// Fonctions utilitaires pour parser la progression FFmpeg
-fn extract_time_from_ffmpeg_line(line: &str) -> Option<String> {
-    // Chercher "time=" dans la ligne et extraire la valeur
-    if let Some(start) = line.find("time=") {
-        let start = start + 5; // Longueur de "time="
-        if let Some(end) = line[start..].find(char::is_whitespace) {
-            return Some(line[start..start + end].to_string());
-        } else {
-            // Si pas d'espace trouvé, prendre jusqu'à la fin
-            return Some(line[start..].to_string());
-        }
+// Annonce le découpage d'un export en chunks : `overall_progress` est alors ajouté aux
+// événements `export-progress`. Poids égaux si `per_chunk_weights` est absent ou incohérent.
+#[tauri::command]
+pub fn set_chunk_plan(export_id: String, total_chunks: usize, per_chunk_weights: Option<Vec<f64>>) -> Result<(), String> {
+    if total_chunks == 0 {
+        return Err("total_chunks doit être supérieur à 0".to_string());
     }
-    
-    // Aussi chercher le format "out_time_ms=" pour -progress pipe
-    if let Some(start) = line.find("out_time_ms=") {
-        let start = start + 12; // Longueur de "out_time_ms="
-        if let Some(end) = line[start..].find(char::is_whitespace) {
-            if let Ok(ms) = line[start..start + end].parse::<i64>() {
-                let seconds = ms as f64 / 1_000_000.0; // microseconds to seconds
-                return Some(format!("{:.3}", seconds));
-            }
+    let weights = match per_chunk_weights {
+        Some(w) if w.len() == total_chunks && w.iter().all(|x| x.is_finite() && *x >= 0.0) && w.iter().sum::<f64>() > 0.0 => w,
+        Some(_) => {
+            log_out!("[chunks] Poids invalides pour {}, répartition uniforme", export_id);
+            vec![1.0; total_chunks]
         }
-    }
-    
-    None
+        None => vec![1.0; total_chunks],
+    };
+    log_out!("[chunks] Plan de {} chunk(s) pour {}", total_chunks, export_id);
+    CHUNK_PLANS.lock().map_err(|_| "Failed to lock chunk plans")?.insert(export_id, ChunkPlan { weights });
+    Ok(())
 }
 
-fn parse_ffmpeg_time(time_str: &str) -> f64 {
-    // Si c'est déjà en secondes (format décimal)
-    if let Ok(seconds) = time_str.parse::<f64>() {
-        return seconds;
+// Médias reçus en mémoire (`stage_media_buffer`), écrits sur disque le temps d'un export
+static STAGED_MEDIA: LazyLock<Mutex<HashMap<String, Vec<PathBuf>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn staged_media_dir(export_id: &str) -> PathBuf {
+    app_temp_dir("staged").join(sanitize_file_name(export_id))
+}
+
+/// Écrit un fond ou un audio fourni en mémoire dans le cache géré et renvoie son chemin, à passer
+/// ensuite à `export_video`. `file_name` ne sert qu'à garder l'extension (détection image/vidéo).
+/// Les fichiers sont supprimés à la fin de l'export (après la concaténation pour un export en chunks)
+/// ou à son annulation.
+#[tauri::command]
+pub async fn stage_media_buffer(export_id: String, file_name: String, data: Vec<u8>) -> Result<String, String> {
+    if data.is_empty() {
+        return Err(format!("Média vide pour {}", file_name));
     }
-    
-    // Format FFmpeg : HH:MM:SS.mmm
-    let parts: Vec<&str> = time_str.split(':').collect();
-    if parts.len() == 3 {
-        if let (Ok(hours), Ok(minutes), Ok(seconds)) = 
-            (parts[0].parse::<f64>(), parts[1].parse::<f64>(), parts[2].parse::<f64>()) {
-            return hours * 3600.0 + minutes * 60.0 + seconds;
+    let dir = staged_media_dir(&export_id);
+    fs::create_dir_all(&dir).map_err(|e| format!("Erreur création dossier: {}", e))?;
+    let name = Path::new(&file_name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(sanitize_file_name)
+        .unwrap_or_else(|| "media".to_string());
+
+    let mut staged = STAGED_MEDIA.lock().map_err(|_| "Failed to lock staged media")?;
+    let files = staged.entry(export_id.clone()).or_default();
+    // Préfixe d'ordre : deux buffers de même nom ne s'écrasent pas
+    let path = dir.join(format!("{}-{}", files.len(), name));
+    fs::write(&path, &data).map_err(|e| format!("Erreur écriture {}: {}", path.display(), e))?;
+    log_out!("[staging] {} ({} octets) -> {}", file_name, data.len(), path.display());
+    files.push(path.clone());
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Supprime les médias mis en cache pour cet export ; sans effet s'il n'y en a pas
+fn release_staged_media(export_id: &str) {
+    let files = STAGED_MEDIA.lock().ok().and_then(|mut staged| staged.remove(export_id));
+    let Some(files) = files else { return };
+    for path in &files {
+        if let Err(e) = fs::remove_file(path) {
+            eprintln!("[staging] ⚠️ Suppression de {} impossible : {}", path.display(), e);
         }
     }
-    0.0
+    let _ = fs::remove_dir(staged_media_dir(export_id));
+    log_out!("[staging] {} média(s) supprimé(s) pour {}", files.len(), export_id);
+}
+
+// Libère les médias mis en cache en quittant la portée, une fois décodeur et encodeur fermés
+struct StagedMediaRelease<'a>(&'a str);
+
+impl Drop for StagedMediaRelease<'_> {
+    fn drop(&mut self) {
+        release_staged_media(self.0);
+    }
 }
 
 #[tauri::command]
 pub async fn cancel_export(export_id: String) -> Result<String, String> {
-    println!("[cancel_export] Demande d'annulation pour export_id: {}", export_id);
+    log_out!("[cancel_export] Demande d'annulation pour export_id: {}", export_id);
+    if let Ok(mut plans) = CHUNK_PLANS.lock() {
+        plans.remove(&export_id);
+    }
+    if let Ok(mut reports) = CHUNK_REPORTS.lock() {
+        reports.remove(&export_id);
+    }
 
     // 1. Fermer le flux de streaming si il existe
     {
         let mut lock = WGPU_STREAMS.lock().map_err(|e| e.to_string())?;
         if let Some(_session) = lock.remove(&export_id) {
-            println!("[cancel_export] Fermeture du flux WGPU pour {}", export_id);
+            log_out!("[cancel_export] Fermeture du flux WGPU pour {}", export_id);
         }
     }
 
-    // 2. Tuer le processus
-    let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
-    if let Some(process_ref) = active_exports.remove(&export_id) {
-        println!("[cancel_export] Found active process for {}, locking...", export_id);
-        let mut child_guard = process_ref.lock().unwrap();
-        if let Some(mut child) = child_guard.take() {
-            println!("[cancel_export] Suppression forcée du processus FFmpeg {}", export_id);
-            let _ = child.kill();
-            let _ = child.wait(); // Nettoyer
+    // 2. Tuer le(s) processus (FFmpeg direct ou décodeur/encodeur du streaming)
+    let handle = {
+        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+        active_exports.remove(&export_id)
+    };
+    track_export_pids(&export_id, None);
+    let _staged = StagedMediaRelease(&export_id);
+    if let Some(handle) = handle {
+        log_out!("[cancel_export] Found active export for {}, killing...", export_id);
+        if handle.cancel() {
+            log_out!("[cancel_export] Suppression forcée du processus FFmpeg {}", export_id);
             Ok(format!("Export {} annulé avec succès", export_id))
         } else {
-            println!("[cancel_export] Processus déjà terminé ou pris par un autre fil pour {}", export_id);
+            log_out!("[cancel_export] Processus déjà terminé ou pris par un autre fil pour {}", export_id);
             Ok(format!("Export {} déjà terminé", export_id))
         }
     } else {
-        println!("[cancel_export] Export_id non trouvé dans les exports actifs: {}", export_id);
+        log_out!("[cancel_export] Export_id non trouvé dans les exports actifs: {}", export_id);
         Err(format!("Export {} non trouvé ou déjà terminé", export_id))
     }
 }
 
+/// Annule tous les exports en cours (fermeture de l'app, réinitialisation) et renvoie
+/// le nombre d'exports arrêtés.
+#[tauri::command]
+pub async fn cancel_all_exports() -> Result<usize, String> {
+    if let Ok(mut plans) = CHUNK_PLANS.lock() {
+        plans.clear();
+    }
+    if let Ok(mut reports) = CHUNK_REPORTS.lock() {
+        reports.clear();
+    }
+
+    let mut stopped: HashSet<String> = HashSet::new();
+    {
+        let mut lock = WGPU_STREAMS.lock().map_err(|e| e.to_string())?;
+        for (export_id, _session) in lock.drain() {
+            log_out!("[cancel_all_exports] Fermeture du flux WGPU pour {}", export_id);
+            stopped.insert(export_id);
+        }
+    }
+
+    let handles: Vec<(String, ExportHandle)> = {
+        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+        active_exports.drain().collect()
+    };
+    for (export_id, handle) in handles {
+        track_export_pids(&export_id, None);
+        if handle.cancel() {
+            log_out!("[cancel_all_exports] Suppression forcée du processus FFmpeg {}", export_id);
+            stopped.insert(export_id);
+        }
+    }
+    let staged: Vec<String> = STAGED_MEDIA.lock().map(|s| s.keys().cloned().collect()).unwrap_or_default();
+    for export_id in staged {
+        release_staged_media(&export_id);
+    }
+
+    log_out!("[cancel_all_exports] {} export(s) annulé(s)", stopped.len());
+    Ok(stopped.len())
+}
+
+// Graphe audio des chunks (entrées 1..n) avec un fondu enchaîné de `d` secondes à chaque jonction.
+// Le chunk sortant s'éteint sur ses `d` dernières secondes puis est prolongé de `d` secondes de
+// silence, que `acrossfade` recouvre avec l'entrée du suivant : chaque chunk démarre exactement
+// à sa jonction vidéo, la durée totale et la synchro sont conservées.
+// Une chaîne par piste audio (`tracks`, labels préfixés `t{k}_`) ; renvoie le graphe et ses sorties.
+fn concat_audio_crossfade_graph(durations_s: &[f64], d: f64, tracks: usize) -> (String, Vec<String>) {
+    let n = durations_s.len();
+    let mut lines = Vec::new();
+    let mut outputs = Vec::new();
+    for track in 0..tracks {
+        let p = format!("t{}_", track);
+        for (i, len) in durations_s.iter().enumerate() {
+            let mut chain = "aresample=async=1:first_pts=0".to_string();
+            if i + 1 < n {
+                let fade = d.min(*len);
+                chain.push_str(&format!(",afade=t=out:st={:.6}:d={:.6},apad=pad_dur={:.6}", (len - fade).max(0.0), fade, d));
+            }
+            lines.push(format!("[{}:a:{}]{}[{}ca{}]", i + 1, track, chain, p, i));
+        }
+        let out = format!("{}aout", p);
+        let mut current = format!("{}ca0", p);
+        for i in 1..n {
+            let next = if i + 1 == n { out.clone() } else { format!("{}cx{}", p, i) };
+            lines.push(format!("[{}][{}ca{}]acrossfade=d={:.6}:c1=tri:c2=tri[{}]", current, p, i, d, next));
+            current = next;
+        }
+        if n == 1 {
+            lines.push(format!("[{}ca0]anull[{}]", p, out));
+        }
+        outputs.push(out);
+    }
+    (lines.join(";"), outputs)
+}
+
+/// Conduite à tenir quand la concaténation échoue à cause de chunks invalides.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcatRecovery {
+    /// Échec avec la liste des chunks fautifs.
+    #[default]
+    Fail,
+    /// Nouvelle concaténation sans les chunks fautifs, signalés par `export-warning`.
+    Skip,
+    /// Ré-encodage des chunks fautifs puis nouvelle concaténation (vidéo ré-encodée).
+    Reencode,
+}
+
+const CONCAT_FAILED_ERROR: &str = "CONCAT_FAILED";
+const CONCAT_INPUT_INVALID_ERROR: &str = "CONCAT_INPUT_INVALID";
+
+// Paramètres vidéo partagés par le plus de chunks : la référence des autres
+fn majority_video(infos: &[Option<MediaInfo>]) -> Option<&VideoStreamInfo> {
+    let videos: Vec<&VideoStreamInfo> = infos.iter().flatten().filter_map(|i| i.video.as_ref()).collect();
+    let key = |v: &VideoStreamInfo| (v.codec.clone(), v.width, v.height);
+    videos.iter().copied().max_by_key(|v| videos.iter().filter(|o| key(o) == key(v)).count())
+}
+
+// Défaut visible sans décoder : illisible, sans vidéo, ou codec/taille différents de la référence
+fn concat_input_issue(info: Option<&MediaInfo>, reference: Option<&VideoStreamInfo>) -> Option<String> {
+    let Some(info) = info else {
+        return Some("illisible par ffprobe".to_string());
+    };
+    let Some(video) = &info.video else {
+        return Some("aucun flux vidéo".to_string());
+    };
+    match reference {
+        Some(r) if (&r.codec, r.width, r.height) != (&video.codec, video.width, video.height) => Some(format!(
+            "{} {}x{} au lieu de {} {}x{}",
+            video.codec, video.width, video.height, r.codec, r.width, r.height
+        )),
+        _ => None,
+    }
+}
+
+// Décodage complet du flux vidéo : première erreur remontée par FFmpeg
+fn decode_error(ffmpeg_exe: &str, path: &str) -> Option<String> {
+    let mut cmd = Command::new(ffmpeg_exe);
+    cmd.args(["-v", "error", "-xerror", "-i", path, "-map", "0:v:0", "-f", "null", "-"]);
+    configure_command_no_window(&mut cmd);
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(e) => return Some(format!("décodage impossible: {}", e)),
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let first_line = stderr.lines().map(str::trim).find(|l| !l.is_empty());
+    match (output.status.success(), first_line) {
+        (true, None) => None,
+        (_, Some(line)) => Some(line.to_string()),
+        (false, None) => Some(format!("décodage échoué (code {:?})", output.status.code())),
+    }
+}
+
+// Après un échec, sonde puis décode chaque chunk pour nommer ceux qui posent problème
+fn diagnose_concat_inputs(ffmpeg_exe: &str, paths: &[String]) -> Vec<(String, String)> {
+    let infos: Vec<Option<MediaInfo>> = paths.iter().map(|p| probe_media(p)).collect();
+    let reference = majority_video(&infos);
+    paths
+        .iter()
+        .zip(&infos)
+        .filter_map(|(path, info)| {
+            concat_input_issue(info.as_ref(), reference)
+                .or_else(|| decode_error(ffmpeg_exe, path))
+                .map(|why| (path.clone(), why))
+        })
+        .collect()
+}
+
+// Ré-encode un chunk fautif (erreurs de décodage ignorées) à la taille de référence
+fn repair_concat_input(ffmpeg_exe: &str, path: &str, size: Option<(i64, i64)>, dst: &Path) -> Result<(), String> {
+    let mut cmd = Command::new(ffmpeg_exe);
+    cmd.args(["-y", "-hide_banner", "-loglevel", "error", "-err_detect", "ignore_err", "-i", path]);
+    if let Some((w, h)) = size {
+        cmd.args(["-vf", &format!("scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2,setsar=1", w, h, w, h)]);
+    }
+    cmd.args(["-c:v", "libx264", "-crf", "18", "-preset", "fast", "-pix_fmt", "yuv420p", "-c:a", "aac", "-b:a", "320k"])
+        .arg(dst);
+    configure_command_no_window(&mut cmd);
+    let output = cmd.output().map_err(|e| format!("Impossible d'exécuter FFmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("{}: ré-encodage de {} impossible: {}", CONCAT_INPUT_INVALID_ERROR, path, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+// Sortie en cours d'écriture : supprimée si on quitte avant `complete` (annulation, échec),
+// pour qu'une concaténation tronquée ne passe pas pour une vidéo terminée
+struct IncompleteOutput<'a> {
+    path: &'a Path,
+    complete: bool,
+}
+
+impl<'a> IncompleteOutput<'a> {
+    fn new(path: &'a Path) -> Self {
+        Self { path, complete: false }
+    }
+
+    fn complete(mut self) {
+        self.complete = true;
+    }
+}
+
+impl Drop for IncompleteOutput<'_> {
+    fn drop(&mut self) {
+        if !self.complete && self.path.exists() {
+            log_out!("[concat_videos] Sortie incomplète supprimée: {}", self.path.display());
+            let _ = fs::remove_file(self.path);
+        }
+    }
+}
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn concat_videos(
     export_id: String,
     video_paths: Vec<String>,
     output_path: String,
+    reencode_video: Option<bool>,
+    audio_codec: Option<AudioCodec>,
+    audio_crossfade_ms: Option<u32>,
+    poll_interval_ms: Option<u64>,
+    on_invalid_chunk: Option<ConcatRecovery>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let concat_started = Instant::now();
+    let failure = match run_concat(&export_id, video_paths.clone(), &output_path, reencode_video, audio_codec, audio_crossfade_ms, poll_interval_ms).await {
+        Err(e) if e.starts_with(CONCAT_FAILED_ERROR) => e,
+        Err(e) => {
+            emit_empty_output(&app_handle, &export_id, &e);
+            return Err(e);
+        }
+        Ok(path) => return Ok(complete_chunked_report(&app_handle, &export_id, path, concat_started).await),
+    };
+
+    // Échec de FFmpeg : on cherche le ou les chunks responsables
+    let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
+    let paths: Vec<String> = video_paths
+        .iter()
+        .map(|p| path_utils::normalize_existing_path(p).to_string_lossy().to_string())
+        .collect();
+    // Sondes et décodage complet de chaque chunk : hors du fil async
+    let (invalid, reference) = {
+        let (exe, paths) = (ffmpeg_exe.clone(), paths.clone());
+        tokio::task::spawn_blocking(move || {
+            let probes: Vec<Option<MediaInfo>> = paths.iter().map(|p| probe_media(p)).collect();
+            let reference = majority_video(&probes).map(|v| (v.width, v.height));
+            (diagnose_concat_inputs(&exe, &paths), reference)
+        })
+        .await
+        .map_err(|e| format!("Diagnostic des chunks interrompu: {}", e))?
+    };
+    if invalid.is_empty() {
+        return Err(failure);
+    }
+    let details = invalid.iter().map(|(p, why)| format!("{} ({})", p, why)).collect::<Vec<_>>().join(", ");
+    eprintln!("[concat_videos] ⚠️ Chunks invalides: {}", details);
+    let recovery = on_invalid_chunk.unwrap_or_default();
+    if recovery == ConcatRecovery::Fail {
+        return Err(format!("{}: {}", CONCAT_INPUT_INVALID_ERROR, details));
+    }
+
+    let repair_dir = ExportWorkDir::create(&format!("{}-repair", export_id), false)
+        .map_err(|e| format!("Erreur création dossier de travail: {}", e))?;
+    let mut retry_paths = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        if !invalid.iter().any(|(p, _)| p == path) {
+            retry_paths.push(path.clone());
+        } else if recovery == ConcatRecovery::Reencode {
+            let repaired = repair_dir.path().join(format!("repaired_{}.mp4", i));
+            log_out!("[concat_videos] Ré-encodage du chunk {}: {}", i, path);
+            let (exe, src, dst) = (ffmpeg_exe.clone(), path.clone(), repaired.clone());
+            tokio::task::spawn_blocking(move || repair_concat_input(&exe, &src, reference, &dst))
+                .await
+                .map_err(|e| format!("Ré-encodage interrompu: {}", e))??;
+            retry_paths.push(repaired.to_string_lossy().to_string());
+        } else {
+            log_out!("[concat_videos] Chunk {} écarté: {}", i, path);
+            skipped.push((i, path.clone()));
+        }
+    }
+    if retry_paths.is_empty() {
+        return Err(format!("{}: aucun chunk valide ({})", CONCAT_INPUT_INVALID_ERROR, details));
+    }
+    log_out!("[concat_videos] Nouvelle tentative avec {} chunk(s) ({:?})", retry_paths.len(), recovery);
+    // Un chunk ré-encodé n'a plus les paramètres des autres : la vidéo est ré-encodée
+    let reencode_video = reencode_video.unwrap_or(false) || recovery == ConcatRecovery::Reencode;
+    match run_concat(&export_id, retry_paths, &output_path, Some(reencode_video), audio_codec, audio_crossfade_ms, poll_interval_ms).await {
+        Ok(path) => {
+            emit_skipped_chunks(&app_handle, &export_id, &skipped);
+            Ok(complete_chunked_report(&app_handle, &export_id, path, concat_started).await)
+        }
+        Err(e) => {
+            emit_empty_output(&app_handle, &export_id, &e);
+            Err(format!("{} (chunks invalides : {})", e, details))
+        }
+    }
+}
+
+// La vidéo concaténée sans certains chunks (`ConcatRecovery::Skip`) est incomplète : on le signale
+// (log + `export-warning` avec les index et chemins écartés)
+fn emit_skipped_chunks(app_handle: &tauri::AppHandle, export_id: &str, skipped: &[(usize, String)]) {
+    if skipped.is_empty() {
+        return;
+    }
+    let names = skipped.iter().map(|(i, p)| format!("{} ({})", i, p)).collect::<Vec<_>>().join(", ");
+    let message = format!("Chunk(s) écarté(s) de la vidéo finale : {}", names);
+    eprintln!("[concat_videos] ⚠️ {}", message);
+    let _ = app_handle.emit("export-warning", serde_json::json!({
+        "exportId": export_id,
+        "code": CONCAT_INPUT_INVALID_ERROR,
+        "message": message,
+        "chunks": skipped.iter().map(|(i, _)| i).collect::<Vec<_>>(),
+        "paths": skipped.iter().map(|(_, p)| p).collect::<Vec<_>>(),
+    }));
+}
+
+// Concaténation réussie : le rapport cumulé des chunks reçoit l'étape `concat`, la sortie finale
+// et, si demandée, la sonie de la vidéo complète
+async fn complete_chunked_report(app_handle: &tauri::AppHandle, export_id: &str, output_path: String, concat_started: Instant) -> String {
+    release_staged_media(export_id);
+    let Some(mut report) = CHUNK_REPORTS.lock().ok().and_then(|mut reports| reports.remove(export_id)) else {
+        return output_path;
+    };
+    report.record_duration("concat", concat_started.elapsed().as_secs_f64());
+    report.file_size_bytes = fs::metadata(&output_path).ok().map(|m| m.len());
+    report.output_path = output_path.clone();
+    if report.loudness_after_concat {
+        let mut measured = report.clone();
+        match tokio::task::spawn_blocking(move || {
+            measured.record_loudness();
+            measured
+        })
+        .await
+        {
+            Ok(measured) => report = measured,
+            Err(e) => eprintln!("[loudness] ⚠️ Mesure interrompue: {}", e),
+        }
+    }
+    report.wall_clock_s += concat_started.elapsed().as_secs_f64();
+    emit_export_complete(app_handle, export_id, &report, None);
+    output_path
+}
+
+// Une passe de concaténation ; un échec de FFmpeg est préfixé `CONCAT_FAILED` pour le diagnostic
+async fn run_concat(
+    export_id: &str,
+    video_paths: Vec<String>,
+    output_path: &str,
+    reencode_video: Option<bool>,
+    audio_codec: Option<AudioCodec>,
+    audio_crossfade_ms: Option<u32>,
+    poll_interval_ms: Option<u64>,
 ) -> Result<String, String> {
+    let concat_started = Instant::now();
+    // Intervalle de vérification de fin du processus (défaut : celui de la progression)
+    let poll_interval = Duration::from_millis(poll_interval_ms.unwrap_or(DEFAULT_PROGRESS_INTERVAL_MS).max(10));
+    // Ré-encodage final de la vidéo, utile quand les chunks sont sans perte (`lossless_chunks`)
+    let reencode_video = reencode_video.unwrap_or(false);
     let normalized_video_paths: Vec<String> = video_paths
         .into_iter()
         .map(|p| path_utils::normalize_existing_path(&p).to_string_lossy().to_string())
         .collect();
-    let output_path_buf = path_utils::normalize_output_path(&output_path);
+    let output_path_buf = path_utils::normalize_output_path(output_path);
     let output_path_str = output_path_buf.to_string_lossy().to_string();
+    // Avec FLAC/ALAC, toute la chaîne chunkée reste sans perte (chunks ALAC → master).
+    // Sortie stéréo ; la fréquence des chunks est gardée (FFmpeg rééchantillonne pour Opus)
+    let audio_codec_args = resolve_audio_codec_args(&output_path_str, audio_codec, 2, 48000)?;
 
-    println!("[concat_videos] Début de la concaténation de {} vidéos", normalized_video_paths.len());
-    println!("[concat_videos] Fichier de sortie: {}", output_path_str);
+    log_out!("[concat_videos] Début de la concaténation de {} vidéos", normalized_video_paths.len());
+    log_out!("[concat_videos] Fichier de sortie: {}", output_path_str);
     
     if normalized_video_paths.is_empty() {
         return Err("Aucune vidéo fournie pour la concaténation".to_string());
     }
     
-    if normalized_video_paths.len() == 1 {
+    if normalized_video_paths.len() == 1 && !reencode_video {
         // Si une seule vidéo, on peut simplement la copier ou la renommer
-        println!("[concat_videos] Une seule vidéo, copie vers le fichier final");
+        log_out!("[concat_videos] Une seule vidéo, copie vers le fichier final");
         std::fs::copy(&normalized_video_paths[0], &output_path_str)
             .map_err(|e| format!("Erreur lors de la copie: {}", e))?;
         return Ok(output_path_str);
@@ -1448,13 +5610,10 @@ pub async fn concat_videos(
             .map_err(|e| format!("Erreur création dossier de sortie: {}", e))?;
     }
     
-    // Créer un fichier de liste temporaire pour FFmpeg
-    let temp_dir = std::env::temp_dir();
-    let list_file_path = temp_dir.join(format!("concat_list_{}.txt", 
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()));
+    // Créer un fichier de liste temporaire pour FFmpeg, dans le dossier de travail de l'export
+    let work_dir = ExportWorkDir::create(&format!("{}-concat", export_id), false)
+        .map_err(|e| format!("Erreur création dossier de travail: {}", e))?;
+    let list_file_path = work_dir.path().join("concat_list.txt");
     
     // Écrire la liste des fichiers à concaténer
     let mut list_content = String::new();
@@ -1470,11 +5629,28 @@ pub async fn concat_videos(
     fs::write(&list_file_path, list_content)
         .map_err(|e| format!("Erreur écriture fichier liste: {}", e))?;
     
-    println!("[concat_videos] Fichier liste créé: {:?}", list_file_path);
+    log_out!("[concat_videos] Fichier liste créé: {:?}", list_file_path);
     
     // Préparer la commande FFmpeg
     let ffmpeg_exe = resolve_ffmpeg_binary().unwrap_or_else(|| "ffmpeg".to_string());
     
+    // Si tous les chunks partagent les mêmes paramètres de codec (même export),
+    // une concaténation en `-c copy` complète est instantanée et sans perte
+    let probes: Vec<Option<MediaInfo>> = normalized_video_paths.iter().map(|p| probe_media(p)).collect();
+    // Fondu audio aux jonctions (anti-clic) : uniquement si chaque chunk a de l'audio
+    let crossfade_s = audio_crossfade_ms.unwrap_or(0) as f64 / 1000.0;
+    // Pistes présentes dans tous les chunks (ex. récitation + traduction) : chacune est fondue
+    let audio_tracks = normalized_video_paths.iter().map(|p| stream_count(p, "a")).min().unwrap_or(0);
+    let crossfade_graph = if crossfade_s > 0.0 && audio_tracks > 0 {
+        Some(concat_audio_crossfade_graph(&ffprobe_durations_sec(&normalized_video_paths), crossfade_s, audio_tracks))
+    } else {
+        None
+    };
+    let can_stream_copy = !reencode_video && crossfade_graph.is_none() && match probes.first() {
+        Some(Some(first)) => probes.iter().all(|p| p.as_ref().map_or(false, |info| info.same_codec_params(first))),
+        _ => false,
+    };
+
     let mut cmd = Command::new(&ffmpeg_exe);
     cmd.args(&[
         "-y",                           // Écraser le fichier de sortie
@@ -1484,20 +5660,51 @@ pub async fn concat_videos(
         "-f", "concat",                 // Format d'entrée concat
         "-safe", "0",                   // Permettre les chemins absolus
         "-i", &list_file_path.to_string_lossy(), // Fichier de liste
+    ]);
+    if crossfade_graph.is_some() {
+        // Chaque chunk en entrée séparée (1..n) pour fondre leurs pistes audio
+        for video_path in &normalized_video_paths {
+            cmd.arg("-i").arg(video_path);
+        }
+    }
+    cmd.args([
         "-avoid_negative_ts", "make_zero", // Normalise les timestamps
         "-map", "0:v",                  // Vidéo
-        "-c:v", "copy",                 // Pas de ré-encodage vidéo
     ]);
 
-    // Ré-encoder l'audio pour lisser les timestamps et éviter les micro-cuts
-    if normalized_video_paths.iter().any(|p| video_has_audio(p)) {
+    if reencode_video {
+        let (vcodec, vparams, vextra) = choose_best_codec(should_prefer_hw_encoding());
+        log_out!("[concat_videos] Ré-encodage final de la vidéo avec {}", vcodec);
+        cmd.arg("-c:v").arg(&vcodec);
+        if let Some(Some(preset)) = vextra.get("preset") {
+            cmd.arg("-preset").arg(preset);
+        }
+        cmd.args(&vparams);
+    } else {
+        cmd.args(&["-c:v", "copy"]);    // Pas de ré-encodage vidéo
+    }
+
+    if let Some((graph, outputs)) = &crossfade_graph {
+        log_out!("[concat_videos] Fondu audio de {:.0} ms entre les chunks ({} piste(s))", crossfade_s * 1000.0, outputs.len());
+        cmd.args(["-filter_complex", graph]);
+        for out in outputs {
+            cmd.arg("-map").arg(format!("[{}]", out));
+        }
+        cmd.args(&audio_codec_args);
+    } else if can_stream_copy {
+        log_out!("[concat_videos] Paramètres identiques sur tous les chunks, concaténation en stream copy");
+        if probes.iter().any(|p| p.as_ref().map_or(false, |info| info.audio.is_some())) {
+            cmd.args(&["-map", "0:a?", "-c:a", "copy"]);
+        } else {
+            cmd.arg("-an");
+        }
+    } else if normalized_video_paths.iter().any(|p| has_stream(p, "a")) {
+        // Ré-encoder l'audio pour lisser les timestamps et éviter les micro-cuts
         cmd.args(&[
             "-map", "0:a?",                          // Map audio si présent (sans échouer si absent)
             "-af", "aresample=async=1:first_pts=0",  // Corrige les horloges audio
-            "-c:a", "aac",
-            "-b:a", "320k",                          // Qualité MAX
-            "-ac", "2",                              // Force stéréo
         ]);
+        cmd.args(&audio_codec_args);                 // Force stéréo (`-ac 2`)
     } else {
         cmd.arg("-an"); // Aucun audio trouvé, on désactive l'audio
     }
@@ -1507,72 +5714,72 @@ pub async fn concat_videos(
     // Configurer la commande pour cacher les fenêtres CMD sur Windows
     configure_command_no_window(&mut cmd);
     
-    println!("[concat_videos] Exécution de FFmpeg...");
+    log_out!("[concat_videos] Exécution de FFmpeg...");
     
     // Lancement du processus en mode Child pour pouvoir l'annuler
     let mut child = cmd.spawn()
         .map_err(|e| format!("Erreur lancement FFmpeg concat: {}", e))?;
+    // Dès le lancement, la sortie est écrasée : supprimée si on ne va pas jusqu'au bout
+    let incomplete_output = IncompleteOutput::new(&output_path_buf);
     
     // Enregistrement dans ACTIVE_EXPORTS
     let process_ref = Arc::new(Mutex::new(Some(child)));
     {
         let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
-        active_exports.insert(export_id.clone(), process_ref.clone());
-        println!("[concat_videos] Process registered in ACTIVE_EXPORTS with ID: {}", export_id);
+        active_exports.insert(export_id.to_string(), ExportHandle::Process(process_ref.clone()));
+        log_out!("[concat_videos] Process registered in ACTIVE_EXPORTS with ID: {}", export_id);
     }
+    track_export_pids(export_id, Some(ExportHandle::Process(process_ref.clone()).pids()));
 
     // Attente de la fin du processus
     let wait_result = {
         // On clone la ref pour attendre sans bloquer le lock global ACTIVE_EXPORTS trop longtemps si on devait le garder
         // Mais ici on a besoin de lock le process_ref specific
-        let mut loop_count = 0;
+        let mut last_log = Instant::now();
         loop {
             // On vérifie si annulé
             {
                 let mut guard = process_ref.lock().unwrap();
                 if guard.is_none() {
-                    println!("[concat_videos] Process cancellation detected for {}", export_id);
-                    // Processus annulé et take() par cancel_export
-                    let _ = fs::remove_file(&list_file_path);
+                    log_out!("[concat_videos] Process cancellation detected for {}", export_id);
+                    // Processus annulé et take() par cancel_export ; la liste part avec le dossier de travail
                     return Err("Concaténation annulée par l'utilisateur".to_string());
                 }
                 
                 // Vérifier si fini sans bloquer indéfiniment (polling)
                 match guard.as_mut().unwrap().try_wait() {
                     Ok(Some(status)) => {
-                        println!("[concat_videos] Process finished with status: {:?}", status);
+                        log_out!("[concat_videos] Process finished with status: {:?}", status);
                         break Ok(status)
                     },
                     Ok(None) => {
-                        loop_count += 1;
-                        if loop_count % 10 == 0 { // Log every 5s
-                             println!("[concat_videos] Still running... ({}s)", (loop_count as f64) * 0.5);
+                        if last_log.elapsed() >= Duration::from_secs(5) { // Log every 5s
+                             log_out!("[concat_videos] Still running... ({:.0}s)", concat_started.elapsed().as_secs_f64());
+                             last_log = Instant::now();
                         }
                     }, 
                     Err(e) => {
-                        println!("[concat_videos] Error polling process: {}", e);
+                        log_out!("[concat_videos] Error polling process: {}", e);
                         break Err(e)
                     },
                 }
             }
             // Petit sleep pour ne pas burn le CPU
-            std::thread::sleep(std::time::Duration::from_millis(500));
+            std::thread::sleep(poll_interval);
         }
     };
 
     // Nettoyage de ACTIVE_EXPORTS
     {
         let mut active_exports = ACTIVE_EXPORTS.lock().unwrap();
-        active_exports.remove(&export_id);
+        active_exports.remove(export_id);
     }
-    
-    // Nettoyer le fichier temporaire
-    let _ = fs::remove_file(&list_file_path);
+    track_export_pids(export_id, None);
     
     match wait_result {
         Ok(status) => {
             if !status.success() {
-                return Err(format!("FFmpeg concat a échoué avec le code {:?}", status.code()));
+                return Err(format!("{}: FFmpeg concat a échoué avec le code {:?}", CONCAT_FAILED_ERROR, status.code()));
             }
         },
         Err(e) => return Err(format!("Erreur attente FFmpeg concat: {}", e)),
@@ -1582,8 +5789,11 @@ pub async fn concat_videos(
     if !Path::new(&output_path_str).exists() {
         return Err("Le fichier de sortie n'a pas été créé".to_string());
     }
+    reject_empty_output(&output_path_str)?;
     
-    println!("[concat_videos] ✅ Concaténation réussie: {}", output_path_str);
+    incomplete_output.complete();
+    log_out!("[concat_videos] ✅ Concaténation réussie: {}", output_path_str);
+    log_out!("[perf] Étape concat : {:.3}s", concat_started.elapsed().as_secs_f64());
     Ok(output_path_str)
 }
 
@@ -1593,37 +5803,120 @@ pub async fn start_streaming_export(
     out_path: String,
     timestamps_ms: Vec<i32>,
     target_size: (i32, i32),
-    fps: i32,
+    fps: FrameRate,
     fade_duration_ms: i32,
     start_time_ms: i32,
     audio_paths: Vec<String>,
     bg_videos: Vec<String>,
     prefer_hw: bool,
     duration_ms: Option<i32>,
-    _chunk_index: Option<i32>,
+    chunk_index: Option<i32>,
     blur: Option<f64>,
     overlay_color: Option<String>,
     overlay_opacity: Option<f64>,
     overlay_enable: Option<bool>,
     is_high_fidelity: bool,
-    _app_handle: tauri::AppHandle,
-) -> Result<(), String> {
+    caption_paths: Option<Vec<String>>,
+    options: Option<ExportOptions>,
+    app_handle: tauri::AppHandle,
+) -> Result<ExportReport, String> {
+    let started = Instant::now();
+    if out_path == crate::renderer::STDOUT_OUTPUT {
+        crate::renderer::reserve_stdout();
+    }
+    let ffmpeg_bin = require_ffmpeg_binary()?;
+    let options = options.unwrap_or_default();
+    // Seul garde-fou pour les appels directs du frontend ; sans effet depuis `export_video`
+    let target_size = check_resolution_cap(&app_handle, &export_id, &options, target_size)?;
     let (w, h) = target_size;
+    // Refuser une fréquence non supportée (ou incompatible avec le codec) avant tout prétraitement
+    let audio_codec_args = resolve_audio_codec_args(&out_path, options.audio_codec, options.audio_channels(), options.audio_sample_rate()?)?;
     
     // 1. Resolve background video
+    let preprocess_started = Instant::now();
     let default_bg = "synthetic:black".to_string();
+    let bg_videos = video_backgrounds(&bg_videos, &options)?;
     let bg_path = bg_videos.get(0).unwrap_or(&default_bg);
+    let fps = resolve_export_fps(&app_handle, &export_id, &options, fps, &bg_videos);
+    // Fonds sondés ici, décodeurs lancés plus bas : les deux comptent dans `preprocess`
+    let mut preprocess_s = preprocess_started.elapsed().as_secs_f64();
     
     // 2. Setup Renderer, Decoder, Encoder
-    let renderer = crate::renderer::Renderer::new(w as u32, h as u32).await.map_err(|e| e.to_string())?;
+    // Manque de VRAM : on relance le rendu à mi-résolution, l'encodeur remettant la taille cible
+    let mut renderer = match crate::renderer::Renderer::new(w as u32, h as u32, options.backend).await {
+        Err(e) if e.starts_with(crate::renderer::GPU_OUT_OF_MEMORY_ERROR) => {
+            let message = format!("Mémoire GPU insuffisante en {}x{}, rendu à mi-résolution puis agrandi", w, h);
+            eprintln!("[start_streaming_export] ⚠️ {} ({})", message, e);
+            let _ = app_handle.emit("export-warning", serde_json::json!({
+                "exportId": export_id,
+                "code": crate::renderer::GPU_OUT_OF_MEMORY_ERROR,
+                "message": message,
+            }));
+            crate::renderer::Renderer::new((w / 2) as u32, (h / 2) as u32, options.backend).await?
+        }
+        r => r?,
+    };
+    if options.blend_mode != crate::renderer::BlendMode::Normal {
+        log_out!("[start_streaming_export] Caption blend mode: {:?}", options.blend_mode);
+        renderer.set_caption_blend(options.blend_mode);
+    }
+    renderer.set_caption_opacity(options.subtitle_opacity() as f32);
+    renderer.set_enter_animation(options.enter_animation);
+    renderer.set_reveal(options.reveal);
+    if let Some(path) = options.reveal_mask.as_deref() {
+        let mask = load_reveal_mask(path)?;
+        renderer.set_reveal_mask(mask.as_raw(), mask.width(), mask.height())?;
+    }
+    if options.subtitle_sampling != crate::renderer::SamplerMode::Linear {
+        log_out!("[start_streaming_export] Caption sampling: {:?}", options.subtitle_sampling);
+        renderer.set_caption_sampler(options.subtitle_sampling);
+    }
+    if let Some(cues) = options.karaoke.as_ref().filter(|c| !c.is_empty()) {
+        log_out!("[start_streaming_export] Karaoke: {} cue(s)", cues.len());
+        let font_size = renderer.height as f32 / 15.0;
+        renderer.set_text_metrics(font_size, font_size * 1.3)?;
+        renderer.set_text_style(crate::renderer::TextStyle {
+            align: Some(glyphon::cosmic_text::Align::Center),
+            ..Default::default()
+        });
+    }
+    let renderer_backend = renderer.backend().to_str();
+    // Le renderer peut avoir arrondi la taille au pair : decoder/encoder suivent ses dimensions
+    let (w, h) = (renderer.width as i32, renderer.height as i32);
+    if let Some((out_w, out_h)) = options.output_size {
+        renderer.set_output_size(out_w, out_h).await?;
+    }
+    // Taille des frames relues, donc reçues par l'encodeur
+    let (enc_w, enc_h) = renderer.output_size();
     
-    let blur_val = blur.unwrap_or(0.0);
-    let color_val = overlay_color.unwrap_or_else(|| "#000000".to_string());
-    let opacity_val = overlay_opacity.unwrap_or(0.0);
+    let blur_val = options.decoder_blur(blur, h);
+    let mut overlay_enable = overlay_enable.unwrap_or(false);
+    let mut color_val = overlay_color.unwrap_or_else(|| "#000000".to_string());
+    let mut opacity_val = overlay_opacity.unwrap_or(0.0);
+
+    // Dim: a black tint at `dim` opacity scales the background by (1 - dim).
+    // An explicit overlay colour takes precedence.
+    if let Some(dim) = options.dim.filter(|d| *d > 0.0) {
+        if !overlay_enable {
+            overlay_enable = true;
+            color_val = "#000000".to_string();
+            opacity_val = dim.min(1.0);
+        }
+    }
 
-    println!("[start_streaming_export] Initializing Decoder...");
+    log_out!("[start_streaming_export] Initializing Decoder...");
+    let decoders_started = Instant::now();
+    // Same orientation as the filter path: the probed rotation is applied explicitly
+    let rotation = if bg_path.starts_with("synthetic:") || is_image_file(bg_path) {
+        None
+    } else {
+        probe_media(bg_path).and_then(|info| info.video).map(|v| v.rotation)
+    };
+    if let Some(r) = rotation.filter(|r| *r != 0) {
+        log_out!("[start_streaming_export] Background rotated {}° clockwise, transposing in the decoder", r);
+    }
     let decoder = crate::renderer::VideoDecoder::new(
-        bg_path, w as u32, h as u32, fps as u32, start_time_ms as u32,
+        &ffmpeg_bin, bg_path, w as u32, h as u32, fps, start_time_ms as u32, rotation,
         blur_val, 
         // We do NOT pass overlay info to Decoder anymore (FFmpeg tint removed)
         // &color_val, opacity_val. 
@@ -1634,63 +5927,400 @@ pub async fn start_streaming_export(
         // I should pass empty/zero to VideoDecoder to disable FFmpeg tint.
         "", 0.0
     ).map_err(|e| e.to_string())?;
+    let motion_decoder = match options.overlay_video.as_ref().filter(|o| !o.video.is_empty()) {
+        Some(ov) => {
+            log_out!("[start_streaming_export] Motion overlay: {} (repeat: {})", ov.video, ov.repeat);
+            Some(crate::renderer::VideoDecoder::new_overlay(&ffmpeg_bin, &ov.path(), w as u32, h as u32, fps, ov.repeat)?)
+        }
+        None => None,
+    };
+    let motion_start_s = options.overlay_video.as_ref().map_or(0.0, |ov| ov.start_ms as f64 / 1000.0);
+    preprocess_s += decoders_started.elapsed().as_secs_f64();
     
     // Setup codec and params based on prefer_hw
-    let ffmpeg_bin = resolve_ffmpeg_binary();
-    let (vcodec, vparams, vpreset) = if prefer_hw && test_nvenc_availability(ffmpeg_bin.as_deref()) {
+    let mut cpu_fallback = false;
+    let video_codec = options.video_codec(&out_path);
+    let (vcodec, mut vparams, vpreset) = if chunk_index.is_some() && options.lossless_chunks {
+        ("libx264", vec!["-qp".to_string(), "0".to_string()], Some("ultrafast".to_string()))
+    } else if options.preview {
+        ("libx264", vec!["-crf".to_string(), "28".to_string()], Some("ultrafast".to_string()))
+    } else if video_codec != VideoCodec::H264 {
+        // Pas d'encodeur matériel pour les autres codecs : encodeur logiciel dédié
+        video_codec.software_encoder()
+    } else if prefer_hw && test_nvenc_availability(Some(&ffmpeg_bin)) {
         ("h264_nvenc", vec!["-rc".to_string(), "vbr".to_string(), "-cq".to_string(), "24".to_string()], Some("p4".to_string()))
     } else {
-        ("libx264", vec!["-crf".to_string(), "23".to_string()], Some("medium".to_string()))
+        cpu_fallback = prefer_hw;
+        VideoCodec::H264.software_encoder()
+    };
+    if cpu_fallback {
+        log_out!("[start_streaming_export] NVENC indisponible, fallback vers {}", vcodec);
+    }
+    emit_codec_selected(&app_handle, &export_id, vcodec, cpu_fallback);
+    vparams.extend(["-g".to_string(), options.gop_size(fps)?.to_string()]);
+    apply_bframes(&mut vparams, options.bframes);
+    apply_tune(vcodec, &mut vparams, options.tune()?);
+    let (out_w, out_h) = match options.output_size {
+        Some(_) => (enc_w as i32, enc_h as i32),
+        None => even_dimensions(target_size.0, target_size.1),
     };
+    let mut video_filters = Vec::new();
+    if (enc_w as i32, enc_h as i32) != (out_w, out_h) {
+        video_filters.push(format!("scale={}:{}:flags=lanczos", out_w, out_h));
+    }
+    if let Some(stamp) = &options.stamp {
+        video_filters.push(stamp.drawtext_filter(out_w, out_h)?);
+    }
+    if !video_filters.is_empty() {
+        vparams.extend(["-vf".to_string(), video_filters.join(",")]);
+    }
 
-    let duration_s = duration_ms.unwrap_or(0) as f64 / 1000.0;
-    let encoder = crate::renderer::VideoEncoder::new(
-        &out_path, w as u32, h as u32, fps as u32, 
-        vcodec, vparams, vpreset, 
-        &audio_paths, 
-        start_time_ms as f64 / 1000.0, 
-        duration_s
-    ).map_err(|e| e.to_string())?;
+    // Same timing model as the FFmpeg path: snapped total duration (explicit, or last
+    // timestamp + tail + end padding) and per-subtitle frame counts
+    let timings = (!timestamps_ms.is_empty()).then(|| {
+        let fade_out_ms = options.caption_fade_ms(fade_duration_ms).1;
+        calculate_export_timings(&timestamps_ms, fps, fade_out_ms, start_time_ms, duration_ms, options.tail_ms, options.end_padding_ms)
+    });
+    let duration_s = match (&timings, duration_ms) {
+        (Some(timings), _) => timings.duration_s,
+        (None, Some(d)) => d as f64 / 1000.0,
+        (None, None) => 0.0,
+    };
+    check_audio_durations(&app_handle, &export_id, &audio_paths, options.fail_on_invalid_audio)?;
+    let coverage = MediaCoverage::probe(&audio_paths, &bg_videos, start_time_ms as f64 / 1000.0, &options);
+    let duration_s = check_media_coverage(&app_handle, &export_id, &coverage, duration_s, options.clamp_to_media);
+    let segment_frames = timings.as_ref().map(|t| streaming_segment_frames(t, fps)).unwrap_or_default();
+    log_out!(
+        "[start_streaming_export] Duration: {:.3}s ({} frames)",
+        duration_s,
+        (duration_s * fps.as_f64()).round() as u64
+    );
+    let mut audio_options = options.audio_options();
+    audio_options.codec_args = audio_codec_args;
+    let sequence_pattern = image_sequence_pattern(&out_path, &options);
+    if sequence_pattern.is_some() && out_path == crate::renderer::STDOUT_OUTPUT {
+        return Err("Image sequence export cannot be written to stdout".to_string());
+    }
+    let encoder = if let Some(pattern) = sequence_pattern.clone() {
+        log_out!("[start_streaming_export] Image sequence output: {}", pattern);
+        if let Some(dir) = Path::new(&pattern).parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Erreur création dossier: {}", e))?;
+        }
+        crate::renderer::VideoEncoder::new_image_sequence(
+            &ffmpeg_bin, &pattern, enc_w, enc_h,
+            &audio_paths,
+            start_time_ms as f64 / 1000.0,
+            duration_s,
+            &audio_options,
+            &image_sequence_audio_path(&pattern).to_string_lossy(),
+        )
+    } else {
+        crate::renderer::VideoEncoder::new(
+            &ffmpeg_bin, &out_path, enc_w, enc_h, fps, 
+            vcodec, vparams, vpreset, 
+            &audio_paths, 
+            start_time_ms as f64 / 1000.0, 
+            duration_s,
+            &audio_options,
+            options.faststart.unwrap_or(true),
+            options.extra_ffmpeg_args.as_deref().unwrap_or(&[]),
+        )
+    }.map_err(|e| e.to_string())?;
+
+    let success_log_command = (options.write_success_log
+        && sequence_pattern.is_none()
+        && out_path != crate::renderer::STDOUT_OUTPUT)
+        .then(|| encoder.command.clone());
+
+    // 3. Register decoder + encoder children so cancel_export can kill them
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let mut children = vec![decoder.child.clone(), encoder.child.clone()];
+    children.extend(motion_decoder.as_ref().map(|d| d.child.clone()));
+    let watchdog = Watchdog::spawn(options.watchdog_timeout(), children.clone());
+    {
+        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+        let handle = ExportHandle::Streaming {
+            children,
+            cancelled: cancelled.clone(),
+        };
+        track_export_pids(&export_id, Some(handle.pids()));
+        active_exports.insert(export_id.clone(), handle);
+    }
+
+    let mut report = ExportReport {
+        output_path: out_path.clone(),
+        codec: vcodec.to_string(),
+        hardware_accelerated: !vcodec.starts_with("lib"),
+        cpu_fallback,
+        gpu_backend: renderer_backend.to_string(),
+        file_size_bytes: None,
+        duration_s,
+        frames: 0,
+        fps: fps.as_f64(),
+        wall_clock_s: 0.0,
+        stage_timings: BTreeMap::new(),
+        integrated_lufs: None,
+        true_peak_dbtp: None,
+        loudness_after_concat: false,
+    };
+    // Renderer et encodeur prêts ; le temps passé sur les fonds est compté à part
+    report.record_duration("preprocess", preprocess_s);
+    report.record_duration("setup", started.elapsed().as_secs_f64() - preprocess_s);
 
-    // 3. Store in session
+    // 4. Store in session
     let session = Arc::new(WgpuStreamingSession {
         renderer: Arc::new(TokioMutex::new(renderer)),
         decoder: Arc::new(TokioMutex::new(decoder)),
         encoder: Arc::new(TokioMutex::new(encoder)),
-        fade_duration_ms: fade_duration_ms as u32,
-        fps: fps as u32,
+        motion_decoder: motion_decoder.map(TokioMutex::new),
+        motion_start_s,
+        karaoke: options.karaoke.clone().unwrap_or_default(),
+        clip_fade: options.clip_fade(fade_duration_ms, fps),
+        fps: fps,
         is_high_fidelity,
-        overlay_enable: overlay_enable.unwrap_or(false),
+        overlay_enable,
         overlay_color: color_val,
         overlay_opacity: opacity_val as f32,
+        cancelled,
+        watchdog,
+        report: Mutex::new(report.clone()),
+        started,
+        encode_started: Instant::now(),
+        prefetch: caption_paths
+            .filter(|paths| !paths.is_empty() && options.prefetch_depth() > 0)
+            .map(|paths| CaptionPrefetcher::start(paths, w as u32, h as u32, options.prefetch_depth())),
+        is_image_sequence: sequence_pattern.is_some(),
+        segment_frames,
+        background_fade_s: options.background_fade_s(),
+        duration_s,
+        app: app_handle.clone(),
+        chunk_index,
+        success_log_command,
+        measure_loudness: options.measure_loudness && sequence_pattern.is_none() && out_path != crate::renderer::STDOUT_OUTPUT,
+        progress_throttle: Mutex::new(ProgressThrottle::new(options.progress_interval())),
     });
 
-    println!("[start_streaming_export] Storing Session...");
+    log_out!("[start_streaming_export] Storing Session...");
     WGPU_STREAMS.lock().unwrap().insert(export_id, session);
-    println!("[start_streaming_export] ✅ Session started successfully.");
+    log_out!("[start_streaming_export] ✅ Session started successfully.");
     
-    Ok(())
+    Ok(report)
+}
+
+// PNG transparent partagé, substitué aux index sans traduction ou aux images corrompues
+// Une couche de sous-titres en un seul passage : chaque PNG est lu une fois, son empreinte md5
+// repère les frames répétées (même contenu que la précédente) et il n'est décodé que si elle change.
+// Absent (`None`), illisible ou corrompu, il est remplacé par la frame transparente `blank`.
+// Renvoie les chemins retenus, les frames répétées et le nombre d'images valides.
+fn scan_caption_frames<'a>(
+    paths: impl Iterator<Item = Option<&'a str>>,
+    warning: &str,
+    blank: impl Fn() -> image::ImageResult<String>,
+) -> image::ImageResult<(Vec<String>, Vec<bool>, usize)> {
+    let (mut resolved, mut repeated, mut valid_count) = (Vec::new(), Vec::new(), 0);
+    let mut blank_path = None;
+    // Empreinte de la dernière frame lue et sa validité, puis clé de la frame retenue (`None` : transparente)
+    let mut last_read: Option<(md5::Digest, bool)> = None;
+    let mut last_key: Option<Option<md5::Digest>> = None;
+    for path in paths {
+        let read = path.and_then(|p| fs::read(p).ok()).map(|bytes| {
+            let digest = md5::compute(&bytes);
+            match last_read {
+                Some((d, valid)) if d == digest => (digest, valid),
+                _ => (digest, image::load_from_memory(&bytes).is_ok()),
+            }
+        });
+        let key = match (path, read) {
+            (Some(p), Some((digest, true))) => {
+                resolved.push(p.to_string());
+                valid_count += 1;
+                Some(digest)
+            }
+            (p, _) => {
+                if let Some(p) = p {
+                    eprintln!("[image] ⚠️ {} (remplacée par une frame transparente): {}", warning, p);
+                }
+                if blank_path.is_none() {
+                    blank_path = Some(blank()?);
+                }
+                resolved.push(blank_path.clone().unwrap_or_default());
+                None
+            }
+        };
+        repeated.push(last_key == Some(key));
+        last_key = Some(key);
+        last_read = read.or(last_read);
+    }
+    Ok((resolved, repeated, valid_count))
+}
+
+fn blank_frame_path(base_dir: &Path, w: i32, h: i32) -> image::ImageResult<String> {
+    let blank_path = base_dir.join(format!("blank-{}x{}.png", w, h));
+    if !blank_path.exists() {
+        image::RgbaImage::new(w as u32, h as u32).save(&blank_path)?;
+    }
+    Ok(blank_path.to_string_lossy().to_string())
+}
+
+/// Contenu d'une couche de sous-titre pour un lot de frames `send_frame`.
+#[derive(Debug)]
+enum CaptionFrame {
+    /// Silence entre deux sous-titres (données vides) : fond seul, sans la couche précédente.
+    Gap,
+    Image(image::RgbaImage),
+}
+
+// Un PNG corrompu est signalé et remplacé par une frame transparente plutôt que de faire échouer l'export
+fn caption_frame(data: &[u8], width: u32, height: u32, layer: &str) -> CaptionFrame {
+    if data.is_empty() {
+        return CaptionFrame::Gap;
+    }
+    CaptionFrame::Image(decode_caption_png(data, width, height).unwrap_or_else(|| {
+        eprintln!("[send_frame] ⚠️ Corrupt {} PNG ({} bytes), using a transparent frame", layer, data.len());
+        image::RgbaImage::new(width, height)
+    }))
+}
+
+// Décode un PNG de sous-titre et le centre dans le canevas du renderer si les tailles diffèrent
+// (ex. frame impaire côté frontend, bordure transparente)
+fn decode_caption_png(data: &[u8], width: u32, height: u32) -> Option<image::RgbaImage> {
+    let mut rgba = decode_caption_srgb(data)?;
+    if rgba.dimensions() == (width, height) {
+        return Some(rgba);
+    }
+    // Canevas réduit (rendu à mi-résolution) : le sous-titre est réduit pour y tenir
+    if rgba.width() > width || rgba.height() > height {
+        let scale = (width as f64 / rgba.width() as f64).min(height as f64 / rgba.height() as f64);
+        let (sw, sh) = ((rgba.width() as f64 * scale).round() as u32, (rgba.height() as f64 * scale).round() as u32);
+        rgba = image::imageops::resize(&rgba, sw.max(1), sh.max(1), image::imageops::FilterType::Triangle);
+    }
+    let mut padded = image::RgbaImage::new(width, height);
+    let x = (width as i64 - rgba.width() as i64) / 2;
+    let y = (height as i64 - rgba.height() as i64) / 2;
+    image::imageops::overlay(&mut padded, &rgba, x, y);
+    Some(padded)
+}
+
+// Gamma PNG équivalent à sRGB (gAMA 1/2.2, ou chunk sRGB)
+const SRGB_PNG_GAMMA: f64 = 0.45455;
+
+static CAPTION_COLOR_PROFILE_WARNED: AtomicBool = AtomicBool::new(false);
+
+// Pixels RGBA8 d'un sous-titre, interprétés comme sRGB (la texture est `Rgba8UnormSrgb`).
+// Le chemin FFmpeg lit les mêmes valeurs sans appliquer gAMA ni profil ICC : on fait de même,
+// explicitement, pour que les deux exports donnent des couleurs identiques. Un PNG 16 bits est
+// arrondi à 8 bits comme le fait `format=rgba` côté FFmpeg.
+fn decode_caption_srgb(data: &[u8]) -> Option<image::RgbaImage> {
+    if image::guess_format(data).ok()? != image::ImageFormat::Png {
+        return image::load_from_memory(data).ok().map(|img| img.to_rgba8());
+    }
+    let mut decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(data)).ok()?;
+    let gamma = decoder.gamma_value().ok().flatten();
+    let has_icc = image::ImageDecoder::icc_profile(&mut decoder).is_some();
+    let non_srgb_gamma = gamma.is_some_and(|g| (g - SRGB_PNG_GAMMA).abs() > 0.01);
+    if (has_icc || non_srgb_gamma) && !CAPTION_COLOR_PROFILE_WARNED.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "[caption] ⚠️ PNG avec profil couleur ({}) : valeurs lues comme sRGB, comme dans le chemin FFmpeg",
+            if has_icc { "ICC".to_string() } else { format!("gamma {:.5}", gamma.unwrap_or_default()) }
+        );
+    }
+    image::DynamicImage::from_decoder(decoder).ok().map(|img| img.to_rgba8())
+}
+
+/// Décode les PNG des prochains sous-titres sur des threads dédiés, pour que le changement
+/// de sous-titre dans `send_frame` ne paie plus la lecture disque et le décodage.
+struct CaptionPrefetcher {
+    paths: Vec<String>,
+    width: u32,
+    height: u32,
+    depth: usize,
+    // Index → image prête (`None` tant que le décodage est en cours)
+    slots: Arc<Mutex<HashMap<usize, Option<image::RgbaImage>>>>,
+}
+
+impl CaptionPrefetcher {
+    fn start(paths: Vec<String>, width: u32, height: u32, depth: usize) -> Self {
+        log_out!("[prefetch] {} sous-titre(s) décodé(s) à l'avance", depth);
+        let prefetcher = Self { paths, width, height, depth, slots: Arc::new(Mutex::new(HashMap::new())) };
+        prefetcher.schedule_from(0);
+        prefetcher
+    }
+
+    // Lance le décodage des `depth` sous-titres à partir de `first` et oublie ceux déjà passés
+    fn schedule_from(&self, first: usize) {
+        let mut slots = self.slots.lock().unwrap();
+        slots.retain(|i, _| *i >= first);
+        for index in first..(first + self.depth).min(self.paths.len()) {
+            if slots.contains_key(&index) {
+                continue;
+            }
+            slots.insert(index, None);
+            let (path, slots, width, height) = (self.paths[index].clone(), self.slots.clone(), self.width, self.height);
+            std::thread::spawn(move || {
+                let decoded = fs::read(&path).ok().and_then(|data| decode_caption_png(&data, width, height));
+                let mut slots = slots.lock().unwrap();
+                match decoded {
+                    // Sous-titre déjà passé entre-temps : inutile de le garder
+                    Some(rgba) if slots.contains_key(&index) => {
+                        slots.insert(index, Some(rgba));
+                    }
+                    Some(_) => {}
+                    None => {
+                        slots.remove(&index);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Image de `index` si elle est prête ; relance l'avance sur les suivants dans tous les cas.
+    fn take(&self, index: usize) -> Option<image::RgbaImage> {
+        let ready = self.slots.lock().unwrap().remove(&index).flatten();
+        self.schedule_from(index + 1);
+        ready
+    }
 }
 
 #[tauri::command]
-pub async fn send_frame(export_id: String, frame_data: Vec<u8>, count: u32) -> Result<(), String> {
+pub async fn send_frame(export_id: String, frame_data: Vec<u8>, count: u32, translation_data: Option<Vec<u8>>, segment_index: Option<usize>) -> Result<(), String> {
     let session = {
         let lock = WGPU_STREAMS.lock().unwrap();
         lock.get(&export_id).cloned()
     }.ok_or("Session not found")?;
 
+    // With a subtitle index, the frame count comes from the shared timing model instead of the caller
+    let count = segment_index
+        .and_then(|i| session.segment_frames.get(i).copied())
+        .unwrap_or(count);
+
     let mut renderer = session.renderer.lock().await;
     let mut decoder = session.decoder.lock().await;
     let mut encoder = session.encoder.lock().await;
+    let mut motion_decoder = match &session.motion_decoder {
+        Some(d) => Some(d.lock().await),
+        None => None,
+    };
 
-    // Upload subtitle PNG data (frame_data is the PNG bytes)
-    // Note: This assumes frame_data is a valid PNG image for the subtitle.
-    // If it's raw RGBA, `upload_subtitle` might need adjustment or a different method.
-    if let Ok(img) = image::load_from_memory(&frame_data) {
-        let rgba = img.to_rgba8();
-        renderer.upload_subtitle(&rgba);
-    } else {
-        return Err("Failed to decode subtitle PNG data".to_string());
+    // Upload subtitle PNG data (frame_data is the PNG bytes); empty data marks a gap,
+    // which clears the layer instead of leaving the previous caption resident
+    let (width, height) = (renderer.width, renderer.height);
+    let prefetched = match (&session.prefetch, segment_index) {
+        (Some(prefetch), Some(index)) if !frame_data.is_empty() => prefetch.take(index),
+        _ => None,
+    };
+    let subtitle = match prefetched {
+        Some(rgba) => CaptionFrame::Image(rgba),
+        None => caption_frame(&frame_data, width, height, "subtitle"),
+    };
+    match subtitle {
+        CaptionFrame::Gap => renderer.clear_subtitle(),
+        CaptionFrame::Image(rgba) => renderer.upload_subtitle(&rgba),
+    }
+
+    // Optional second caption layer (translation), sharing the subtitle timing
+    match translation_data.map(|data| caption_frame(&data, width, height, "translation")) {
+        Some(CaptionFrame::Image(rgba)) => renderer.upload_translation(&rgba),
+        Some(CaptionFrame::Gap) | None => renderer.clear_translation(),
     }
 
     // Optimization: Prepare the tint layer once for this batch of frames
@@ -1699,27 +6329,55 @@ pub async fn send_frame(export_id: String, frame_data: Vec<u8>, count: u32) -> R
         renderer.prepare_tint(&session.overlay_color, session.overlay_opacity);
     }
 
-    let fade_frames = (session.fade_duration_ms as f32 / 1000.0 * session.fps as f32) as u32;
+    let timeout_error = || format!("{}: export {} stalled, decoder/encoder were killed", TIMEOUT_ERROR, export_id);
+
+    // Armed for the duration of this batch only: the frontend may legitimately pause between calls
+    let _armed = session.watchdog.arm();
+    // Frames past the planned duration (e.g. clamped to the media) would only be cut by the encoder
+    let max_frames = (session.duration_s > 0.0).then(|| (session.duration_s * session.fps.as_f64()).round() as u64);
 
     for i in 0..count {
+        if session.watchdog.fired() {
+            return Err(timeout_error());
+        }
+        if session.cancelled.load(Ordering::SeqCst) {
+            return Err(format!("Export {} was cancelled", export_id));
+        }
+        session.watchdog.beat();
+        if max_frames.is_some_and(|max| session.report.lock().unwrap().frames >= max) {
+            break;
+        }
+
         // Read background frame
         let bg_raw = match decoder.read_frame() {
             Ok(f) => f,
+            Err(_) if session.watchdog.fired() => return Err(timeout_error()),
             Err(e) if e == "EOF" => break,
             Err(e) => return Err(e),
         };
 
         renderer.upload_background(&bg_raw);
+        let frame_index = session.report.lock().unwrap().frames;
+        if let Some(motion) = motion_decoder.as_mut() {
+            if frame_index as f64 / session.fps.as_f64() + 1e-9 >= session.motion_start_s {
+                match motion.read_frame() {
+                    Ok(frame) => renderer.upload_motion_overlay(&frame),
+                    // Overlay finished (not repeated): the rest of the export shows without it
+                    Err(_) => renderer.clear_motion_overlay(),
+                }
+            }
+        }
+        renderer.set_background_alpha(background_fade_alpha(
+            frame_index as f64 / session.fps.as_f64(),
+            session.background_fade_s,
+            session.duration_s,
+        ));
         
         // Calculate alpha for fade if not High Fidelity
         let alpha = if session.is_high_fidelity {
             1.0 // In High Fidelity, alpha is already baked into the PNG by the frontend
-        } else if fade_frames > 0 {
-            let fade_in = i as f32 / fade_frames as f32;
-            let fade_out = (count.saturating_sub(1).saturating_sub(i)) as f32 / fade_frames as f32;
-            fade_in.min(fade_out).min(1.0)
         } else {
-            1.0
+            clip_fade_alpha(i, count, session.fps, session.clip_fade)
         };
 
         // 3. Composite everything (3-Layer "Sandwich")
@@ -1728,30 +6386,871 @@ pub async fn send_frame(export_id: String, frame_data: Vec<u8>, count: u32) -> R
         // Layer 3: Subtitles (Handled by render_image second pass with dynamic alpha)
         renderer.render_image(
             alpha, 
+            alpha,
             session.overlay_enable, 
             session.overlay_opacity
-        );
+        ).map_err(|e| session.emit_error(&export_id, e))?;
+        // Karaoke text over the composite, advanced with the frame's time within the caption
+        if let Some(cue) = segment_index.and_then(|index| session.karaoke.get(index)) {
+            let time_ms = (i as f64 * 1000.0 / session.fps.as_f64()).round() as u32;
+            renderer.render_karaoke(cue, time_ms).map_err(|e| session.emit_error(&export_id, e))?;
+        }
 
         // Readback
-        let frame_out = renderer.read_frame().await.map_err(|e| e.to_string())?;
+        let frame_out = renderer.read_frame().await.map_err(|e| session.emit_error(&export_id, e.to_string()))?;
 
         // Encode
-        encoder.write_frame(&frame_out).map_err(|e| e.to_string())?;
+        encoder.write_frame(frame_out).map_err(|e| {
+            if session.watchdog.fired() { timeout_error() } else { e.to_string() }
+        })?;
+        session.report.lock().unwrap().frames += 1;
+        // Throttled by time, so long captions still report steadily whatever the fps
+        session.emit_progress(&export_id, max_frames);
+    }
+
+    if session.watchdog.fired() {
+        return Err(timeout_error());
     }
 
     Ok(())
 }
 
 #[tauri::command]
-pub async fn finish_streaming_export(export_id: String) -> Result<(), String> {
+pub async fn finish_streaming_export(export_id: String, app_handle: tauri::AppHandle) -> Result<ExportReport, String> {
+    // Déclaré en premier : relâché après la fermeture du décodeur et de l'encodeur, même en cas d'erreur.
+    // Un chunk laisse les médias aux suivants : ils sont libérés après la concaténation.
+    let mut _staged = None;
     let session = {
         let mut lock = WGPU_STREAMS.lock().unwrap();
         lock.remove(&export_id)
     }.ok_or("Session not found")?;
+    if session.chunk_index.is_none() {
+        _staged = Some(StagedMediaRelease(&export_id));
+    }
+
+    {
+        let mut active_exports = ACTIVE_EXPORTS.lock().map_err(|_| "Failed to lock active exports")?;
+        active_exports.remove(&export_id);
+    }
+    track_export_pids(&export_id, None);
 
     let session = Arc::try_unwrap(session).map_err(|_| "Session still in use")?;
+    let mut report = session.report.into_inner().unwrap();
+    report.record_stage("encode", session.encode_started);
+    let finalize_started = Instant::now();
     let encoder = Arc::try_unwrap(session.encoder).map_err(|_| "Encoder still in use")?.into_inner();
     encoder.finish().map_err(|e| e.to_string())?;
 
-    Ok(())
+    // Le scan d'`export_video` précède la session : il compte dans la durée totale
+    report.wall_clock_s = session.started.elapsed().as_secs_f64() + report.stage_timings.get("scan").copied().unwrap_or(0.0);
+    if !session.is_image_sequence && report.output_path != crate::renderer::STDOUT_OUTPUT {
+        // The encoder stops at the planned duration; fewer frames sent means a shorter file
+        let sent_s = report.frames as f64 / session.fps.as_f64();
+        let expected_s = if report.duration_s > 0.0 { report.duration_s.min(sent_s) } else { sent_s };
+        if let Err(e) = verify_output(&report.output_path, expected_s) {
+            emit_empty_output(&app_handle, &export_id, &e);
+            return Err(e);
+        }
+    }
+    report.file_size_bytes = fs::metadata(&report.output_path).ok().map(|m| m.len());
+    report.record_stage("finalize", finalize_started);
+    if session.measure_loudness {
+        // Un chunk n'est qu'une partie de l'audio : mesure reportée après la concaténation
+        if session.chunk_index.is_some() {
+            report.loudness_after_concat = true;
+        } else {
+            report.record_loudness();
+        }
+    }
+    if let Some(cmd) = &session.success_log_command {
+        write_success_log(&report.output_path, &export_id, cmd, Some(&report));
+    }
+    emit_export_complete(&app_handle, &export_id, &report, session.chunk_index);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_ms_defaults_to_fade_or_one_second() {
+        assert_eq!(resolve_tail_ms(500, None), 1000);
+        assert_eq!(resolve_tail_ms(1500, None), 1500);
+        assert_eq!(resolve_tail_ms(500, Some(0)), 1);
+    }
+
+    #[test]
+    fn tail_ms_override_drives_total_duration() {
+        let timings = calculate_export_timings(&[0, 1000, 2000], 25.into(), 500, 0, None, Some(200), None);
+        assert!((timings.duration_s - 2.2).abs() < 1e-9);
+        assert!((timings.durations_s[2] - 0.2).abs() < 1e-9);
+
+        let default = calculate_export_timings(&[0, 1000, 2000], 25.into(), 500, 0, None, None, None);
+        assert!((default.duration_s - 3.0).abs() < 1e-9);
+    }
+
+    fn assert_on_frame_grid(s: f64, fps: i32) {
+        let frames = s * fps as f64;
+        assert!((frames - frames.round()).abs() < 1e-6, "{} s n'est pas un multiple de 1/{}", s, fps);
+    }
+
+    #[test]
+    fn timings_snap_to_the_frame_grid_at_common_frame_rates() {
+        let ts = [0, 1017, 2533, 4001, 6789];
+        for fps in [24, 25, 30, 50, 60] {
+            let timings = calculate_export_timings(&ts, fps.into(), 500, 120, None, None, None);
+            assert_on_frame_grid(timings.start_s, fps);
+            assert_on_frame_grid(timings.duration_s, fps);
+            for d in &timings.durations_s {
+                assert_on_frame_grid(*d, fps);
+            }
+            // Les durées s'enchaînent sans dérive : leur somme tombe sur le dernier timestamp + tail
+            let end = timings.durations_s.iter().sum::<f64>();
+            assert!((end - (7.789 * fps as f64).round() / fps as f64).abs() < 1e-9, "fps {}", fps);
+        }
+
+        // 1017 ms à 30 fps : frame 30.51 → 31 (1.0333 s)
+        let timings = calculate_export_timings(&[0, 1017], 30.into(), 0, 0, None, Some(1000), None);
+        assert!((timings.durations_s[0] - 31.0 / 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tail_follows_the_fade_unless_overridden() {
+        // Sans tail_ms, le dernier sous-titre dure max(fondu, 1 s)
+        let short_fade = calculate_export_timings(&[0, 1000], 25.into(), 400, 0, None, None, None);
+        assert!((short_fade.durations_s[1] - 1.0).abs() < 1e-9);
+        let long_fade = calculate_export_timings(&[0, 1000], 25.into(), 1800, 0, None, None, None);
+        assert!((long_fade.durations_s[1] - 1.8).abs() < 1e-9);
+        assert!((long_fade.duration_s - 2.8).abs() < 1e-9);
+
+        // tail_ms l'emporte, même plus court que le fondu ; jamais moins d'une frame de durée
+        let overridden = calculate_export_timings(&[0, 1000], 25.into(), 1800, 0, None, Some(400), None);
+        assert!((overridden.durations_s[1] - 0.4).abs() < 1e-9);
+        let zero = calculate_export_timings(&[0, 1000], 25.into(), 1800, 0, None, Some(0), None);
+        assert!((zero.duration_s - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn explicit_duration_overrides_the_timeline_end() {
+        let timings = calculate_export_timings(&[0, 1000, 2000], 30.into(), 500, 1000, Some(1500), None, None);
+        assert!((timings.start_s - 1.0).abs() < 1e-9);
+        assert!((timings.duration_s - 1.5).abs() < 1e-9);
+        // Les durées des sous-titres ne dépendent pas de la durée explicite
+        assert_eq!(timings.durations_s.len(), 3);
+        assert!((timings.durations_s[2] - 1.0).abs() < 1e-9);
+
+        // Une durée nulle garde au moins une frame
+        let empty = calculate_export_timings(&[0], 30.into(), 500, 0, Some(0), None, None);
+        assert!((empty.duration_s - 1.0 / 30.0).abs() < 1e-9);
+    }
+
+    // Graphe de sous-titres en 1080p avec des valeurs neutres : chaque test ne fixe que ce qu'il vérifie
+    struct FilterArgs<'a> {
+        fps: FrameRate,
+        durations_s: &'a [f64],
+        repeated: &'a [bool],
+        start_s: f64,
+        duration_s: f64,
+        is_high_fidelity: bool,
+        translation: Option<(i32, &'a [bool])>,
+    }
+
+    impl Default for FilterArgs<'_> {
+        fn default() -> Self {
+            FilterArgs {
+                fps: 25.into(),
+                durations_s: &[],
+                repeated: &[],
+                start_s: 0.0,
+                duration_s: 0.0,
+                is_high_fidelity: false,
+                translation: None,
+            }
+        }
+    }
+
+    impl FilterArgs<'_> {
+        fn build(&self) -> FilterContext {
+            build_filter_complex_content(
+                1920, 1080, self.fps, ClipFade::new((0.5, 0.5), self.fps, None), self.durations_s.len(), self.durations_s,
+                self.repeated, self.start_s, self.duration_s, &[], 0.0, &[], 1, 1, 1, false, self.is_high_fidelity, self.translation,
+                BackgroundMode::Pad, None, &[], &Default::default(), &CaptionStyle::from_options(&ExportOptions::default(), None),
+            )
+        }
+    }
+
+    #[test]
+    fn single_subtitle_fades_in_holds_and_fades_out() {
+        let timings = calculate_export_timings(&[0], 25.into(), 500, 0, None, Some(2000), None);
+        assert_eq!(timings.durations_s.len(), 1);
+        assert!((timings.durations_s[0] - 2.0).abs() < 1e-9);
+        assert!((timings.duration_s - 2.0).abs() < 1e-9);
+
+        let ctx = FilterArgs {
+            durations_s: &timings.durations_s,
+            start_s: timings.start_s,
+            duration_s: timings.duration_s,
+            ..Default::default()
+        }
+        .build();
+        assert!(!ctx.filter_complex.contains("split="));
+        assert!(!ctx.filter_complex.contains("concat="));
+        assert!(ctx.filter_complex.contains("trim=start=0:end=2.000000"));
+        assert!(ctx.filter_complex.contains("fade=t=in:st=0:d=0.500000:alpha=1"));
+        assert!(ctx.filter_complex.contains("fade=t=out:st=1.500000:d=0.500000:alpha=1[comp_overlay]"));
+    }
+
+    #[test]
+    fn repeated_frames_share_one_clip_in_fast_mode() {
+        let groups = group_clips(&[1.0, 0.5, 0.5, 2.0], &[false, true, true, false]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].input_indices, vec![0, 1, 2]);
+        assert!((groups[0].pure_duration - 2.0).abs() < 1e-9);
+        assert!((groups[1].pipe_start - 2.0).abs() < 1e-9);
+
+        let durations = [1.0, 1.0, 1.0];
+        let repeated = [false, true, false];
+        let fast = FilterArgs { durations_s: &durations, repeated: &repeated, duration_s: 3.0, ..Default::default() }.build();
+        assert!(fast.filter_complex.contains("split=2[b0][b1]"));
+        assert!(fast.filter_complex.contains("[b0]trim=start=0.000000:end=2.000000"));
+        assert!(fast.filter_complex.contains("[b1]trim=start=2.000000:end=3.000000"));
+        assert!(fast.filter_complex.contains("concat=n=2:v=1:a=0[comp_overlay]"));
+
+        // En haute fidélité, chaque sous-titre garde son propre clip
+        let hifi = FilterArgs {
+            durations_s: &durations,
+            repeated: &repeated,
+            duration_s: 3.0,
+            is_high_fidelity: true,
+            ..Default::default()
+        }
+        .build();
+        assert!(hifi.filter_complex.contains("split=3[b0][b1][b2]"));
+    }
+
+    #[test]
+    fn translation_layer_is_grouped_by_its_own_repeats() {
+        // Même sous-titre sur trois frames, mais la traduction change à la troisième
+        let durations = [1.0, 1.0, 1.0];
+        let ctx = FilterArgs {
+            durations_s: &durations,
+            repeated: &[false, true, true],
+            duration_s: 3.0,
+            translation: Some((1, &[false, true, false])),
+            ..Default::default()
+        }
+        .build();
+        assert!(ctx.filter_complex.contains("split=1[b0]"));
+        assert!(ctx.filter_complex.contains("split=2[t_b0][t_b1]"));
+        assert!(ctx.filter_complex.contains("[t_b1]trim=start=2.000000:end=3.000000"));
+    }
+
+    #[test]
+    fn end_padding_extends_background_after_last_subtitle() {
+        let timings = calculate_export_timings(&[0, 1000], 25.into(), 500, 0, None, Some(1000), Some(3000));
+        assert!((timings.duration_s - 5.0).abs() < 1e-9);
+        assert!((timings.end_padding_s - 3.0).abs() < 1e-9);
+        // Le dernier sous-titre garde sa durée : il s'efface avant le rembourrage
+        assert!((timings.durations_s[1] - 1.0).abs() < 1e-9);
+
+        let track = image_track_durations(&timings.durations_s, timings.duration_s - timings.end_padding_s);
+        assert!((track.iter().sum::<f64>() - 2.0).abs() < 1e-6);
+        let ctx = FilterArgs {
+            durations_s: &track,
+            start_s: timings.start_s,
+            duration_s: timings.duration_s,
+            ..Default::default()
+        }
+        .build();
+        assert!(ctx.filter_complex.contains("[comp_overlay]tpad=stop_mode=add:stop_duration=3.000000:color=black@0[comp_overlay_pad]"));
+
+        // Une durée explicite l'emporte sur le rembourrage
+        let explicit = calculate_export_timings(&[0, 1000], 25.into(), 500, 0, Some(2000), Some(1000), Some(3000));
+        assert!((explicit.duration_s - 2.0).abs() < 1e-9);
+        assert_eq!(explicit.end_padding_s, 0.0);
+    }
+
+    #[test]
+    fn streaming_and_ffmpeg_paths_share_segment_boundaries() {
+        let fps = 30;
+        let ts = [0, 1517, 3203, 4999, 7350];
+        let timings = calculate_export_timings(&ts, fps.into(), 500, 0, None, None, None);
+        let track = image_track_durations(&timings.durations_s, timings.duration_s - timings.end_padding_s);
+        let ctx = FilterArgs {
+            fps: fps.into(),
+            durations_s: &track,
+            start_s: timings.start_s,
+            duration_s: timings.duration_s,
+            ..Default::default()
+        }
+        .build();
+
+        // Fin de chaque clip dans le graphe FFmpeg, en frames
+        let ffmpeg_ends: Vec<u64> = ctx
+            .filter_complex
+            .split("trim=start=")
+            .skip(1)
+            .map(|s| {
+                let end = s.split(":end=").nth(1).unwrap().split(',').next().unwrap();
+                (end.parse::<f64>().unwrap() * fps as f64).round() as u64
+            })
+            .collect();
+        let streaming_ends: Vec<u64> = streaming_segment_frames(&timings, fps.into())
+            .iter()
+            .scan(0u64, |end, &n| {
+                *end += n as u64;
+                Some(*end)
+            })
+            .collect();
+
+        assert_eq!(ffmpeg_ends, streaming_ends);
+        assert_eq!(*streaming_ends.last().unwrap(), (timings.duration_s * fps as f64).round() as u64);
+    }
+
+    #[test]
+    fn image_track_duration_matches_export_duration() {
+        // Timestamps non alignés sur les frames, nombreux clips : aucune dérive cumulée
+        let ts: Vec<i32> = (0..300).map(|i| i * 3217 + (i % 7) * 13).collect();
+        let timings = calculate_export_timings(&ts, 30.into(), 500, 0, None, None, None);
+        let track = image_track_durations(&timings.durations_s, timings.duration_s);
+        assert_eq!(track.len(), ts.len());
+        assert!((track.iter().sum::<f64>() - timings.duration_s).abs() < 1e-6);
+
+        // Export plus long que la timeline (durée explicite) : le dernier clip est prolongé
+        let timings = calculate_export_timings(&[0, 1500, 3200], 30.into(), 500, 0, Some(6000), None, None);
+        let track = image_track_durations(&timings.durations_s, timings.duration_s);
+        assert!((track.iter().sum::<f64>() - 6.0).abs() < 1e-6);
+        assert!((track[0] - timings.durations_s[0]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn accurate_seek_lands_within_one_frame_of_offset() {
+        let Some(exe) = resolve_ffmpeg_binary() else {
+            eprintln!("ffmpeg introuvable, test ignoré");
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("qv-seek-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.mp4").to_string_lossy().to_string();
+        let dst = dir.join("dst.mp4").to_string_lossy().to_string();
+
+        // Luminance = 4 × numéro d'image, GOP de 10 s : un seek par keyframe retomberait sur l'image 0
+        let status = Command::new(&exe)
+            .args(["-y", "-loglevel", "error", "-f", "lavfi", "-i", "color=c=black:s=64x64:r=25:d=3"])
+            .args(["-vf", "geq=lum='4*N':cb=128:cr=128", "-c:v", "libx264", "-g", "250", "-pix_fmt", "yuv420p", &src])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let options = ExportOptions { accurate_seek: true, ..Default::default() };
+        let offset_ms = 2040; // image 51
+        ffmpeg_preprocess_video(&src, &dst, 64, 64, 25.into(), false, Some(offset_ms), Some(400), None, &options).unwrap();
+
+        let out = Command::new(&exe)
+            .args(["-loglevel", "error", "-i", &dst, "-frames:v", "1", "-f", "rawvideo", "-pix_fmt", "yuv420p", "-"])
+            .output()
+            .unwrap();
+        // Plan Y brut, sans conversion de plage
+        let frame = out.stdout[32 * 64 + 32] as f64 / 4.0;
+        let expected = offset_ms as f64 * 25.0 / 1000.0;
+        let _ = fs::remove_dir_all(&dir);
+        assert!((frame - expected).abs() <= 1.0, "première image {:.1}, attendue {:.1}", frame, expected);
+    }
+
+    #[test]
+    fn thousands_of_captions_split_into_bounded_sub_graphs() {
+        let (n, fps) = (2000, 25);
+        let durations = vec![1.0 / fps as f64; n];
+        let mut lines = Vec::new();
+        let label = push_caption_chain(&mut lines, "0:v", "", 64, 64, fps.into(), ClipFade::new((0.0, 0.0), fps.into(), None), n, &durations, &[], false, false);
+        assert_eq!(label, "comp_overlay");
+
+        // Aucun split ni concat au-delà de la limite, et un trim par sous-titre
+        for line in &lines {
+            for node in ["split=", "concat=n="] {
+                if let Some(pos) = line.find(node) {
+                    let count: usize = line[pos + node.len()..]
+                        .split(|c: char| !c.is_ascii_digit())
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap();
+                    assert!(count <= CAPTION_SPLIT_MAX_OUTPUTS, "{} avec {} sorties", node, count);
+                }
+            }
+        }
+        assert_eq!(lines.iter().filter(|l| l.contains("trim=start=") && !l.contains("split=")).count(), n);
+
+        let Some(exe) = resolve_ffmpeg_binary() else {
+            eprintln!("ffmpeg introuvable, exécution du graphe ignorée");
+            return;
+        };
+        let status = Command::new(&exe)
+            .args(["-y", "-loglevel", "error", "-f", "lavfi", "-i", "color=c=white:s=64x64:r=25:d=80,format=rgba"])
+            .args(["-filter_complex", &lines.join(";"), "-map", "[comp_overlay]", "-f", "null", "-"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn burst_of_short_clips_gets_hard_cuts_instead_of_sub_frame_fades() {
+        let (n, fps) = (40, 30);
+        let durations = vec![0.05; n];
+        for min_clip_ms in [None, Some(100)] {
+            let fade = ClipFade::new((0.5, 0.5), fps.into(), min_clip_ms);
+            let mut lines = Vec::new();
+            push_caption_chain(&mut lines, "0:v", "", 64, 64, fps.into(), fade, n, &durations, &[], false, true);
+            let trims = lines.iter().filter(|l| l.contains("trim=start=")).count();
+            assert_eq!(trims, n);
+            assert!(lines.iter().all(|l| !l.contains("fade=")), "fondu émis pour un clip de 50 ms");
+            assert_eq!(caption_overlay_xy(crate::renderer::EnterAnimation::SlideUp, &durations, fade, 64, 64), "x=0:y=0");
+        }
+
+        // Sous le minimum configuré, coupe franche même si le fondu tiendrait sur plusieurs images
+        let fade = ClipFade::new((0.5, 0.5), fps.into(), Some(400));
+        assert_eq!(fade.for_clip(0.3), (0.0, 0.0));
+        assert!((fade.for_clip(1.0).0 - 0.5).abs() < 1e-9);
+        assert!((0..9).all(|i| clip_fade_alpha(i, 9, fps.into(), fade) == 1.0));
+    }
+
+    #[test]
+    fn anamorphic_source_is_stretched_before_fit() {
+        let Some(exe) = resolve_ffmpeg_binary() else {
+            eprintln!("ffmpeg introuvable, test ignoré");
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("qv-sar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("src.mp4").to_string_lossy().to_string();
+        let dst = dir.join("dst.mp4").to_string_lossy().to_string();
+
+        // 64x64 stocké avec un SAR 2:1 : s'affiche en 128x64
+        let status = Command::new(&exe)
+            .args(["-y", "-loglevel", "error", "-f", "lavfi", "-i", "color=c=white:s=64x64:r=25:d=1"])
+            .args(["-vf", "setsar=2", "-c:v", "libx264", "-pix_fmt", "yuv420p", &src])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        ffmpeg_preprocess_video(&src, &dst, 128, 64, 25.into(), false, None, None, None, &ExportOptions::default()).unwrap();
+
+        let out = Command::new(&exe)
+            .args(["-loglevel", "error", "-i", &dst, "-frames:v", "1", "-f", "rawvideo", "-pix_fmt", "yuv420p", "-"])
+            .output()
+            .unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        // Sans correction, le carré 64x64 serait centré entre deux bandes noires de 32 px
+        let edge = out.stdout[32 * 128 + 4];
+        assert!(edge > 200, "bord gauche {} : source anamorphique non corrigée", edge);
+    }
+
+    #[test]
+    fn timeline_gap_clears_the_previous_caption() {
+        let png = |rgba: [u8; 4]| {
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            image::RgbaImage::from_pixel(4, 4, image::Rgba(rgba))
+                .write_to(&mut bytes, image::ImageOutputFormat::Png)
+                .unwrap();
+            bytes.into_inner()
+        };
+        // Ayah, silence, ayah : le silence ne doit rien garder de la première image
+        let timeline = [png([255, 255, 255, 255]), Vec::new(), png([200, 200, 200, 255])];
+        let frames: Vec<CaptionFrame> = timeline.iter().map(|data| caption_frame(data, 4, 4, "subtitle")).collect();
+
+        assert!(matches!(&frames[0], CaptionFrame::Image(img) if img.get_pixel(0, 0)[0] == 255));
+        assert!(matches!(frames[1], CaptionFrame::Gap));
+        assert!(matches!(&frames[2], CaptionFrame::Image(img) if img.get_pixel(0, 0)[0] == 200));
+    }
+
+    #[test]
+    fn ebur128_summary_gives_integrated_loudness_and_true_peak() {
+        let stderr = "[Parsed_ebur128_0 @ 0x1] t: 9.9 TARGET:-23 LUFS M: -16.2 S: -16.0 I: -16.1 LUFS\n\
+[Parsed_ebur128_0 @ 0x1] Summary:\n\
+\n  Integrated loudness:\n    I:         -15.9 LUFS\n    Threshold: -26.2 LUFS\n\
+\n  Loudness range:\n    LRA:         3.1 LU\n\
+\n  True peak:\n    Peak:       -1.4 dBFS\n";
+        assert_eq!(parse_ebur128_summary(stderr), Some((-15.9, -1.4)));
+
+        // Piste silencieuse : -inf, sans faire échouer l'analyse
+        let silent = "Summary:\n  Integrated loudness:\n    I:         -70.0 LUFS\n  True peak:\n    Peak:       -inf dBFS\n";
+        assert_eq!(parse_ebur128_summary(silent), Some((-70.0, f64::NEG_INFINITY)));
+
+        assert_eq!(parse_ebur128_summary("pas de résumé"), None);
+    }
+
+    #[test]
+    fn supplied_background_durations_skip_probing_and_must_match() {
+        let videos = vec!["/absent/a.mp4".to_string(), "/absent/b.mp4".to_string()];
+        let options = ExportOptions { background_durations_ms: Some(vec![4000, 2500]), ..Default::default() };
+        // Fichiers absents : aucun ffprobe, les fonds et leurs durées sont repris tels quels
+        assert_eq!(video_backgrounds(&videos, &options).unwrap(), videos);
+        assert_eq!(options.background_durations_s(&videos), vec![4.0, 2.5]);
+        let coverage = MediaCoverage::probe(&[], &videos, 1.0, &options);
+        assert_eq!(coverage.background_s, Some(5.5));
+
+        let short = ExportOptions { background_durations_ms: Some(vec![4000]), ..Default::default() };
+        let err = video_backgrounds(&videos, &short).unwrap_err();
+        assert!(err.starts_with(INVALID_BACKGROUND_DURATIONS_ERROR), "{}", err);
+    }
+
+    #[test]
+    fn phone_rotation_is_read_from_tag_or_display_matrix() {
+        let stream = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+        assert_eq!(stream_rotation_cw(&stream(r#"{"tags": {"rotate": "90"}}"#)), 90);
+        // La matrice d'affichage compte dans le sens antihoraire
+        assert_eq!(stream_rotation_cw(&stream(r#"{"side_data_list": [{"side_data_type": "Display Matrix", "rotation": -90}]}"#)), 90);
+        assert_eq!(stream_rotation_cw(&stream(r#"{"side_data_list": [{"rotation": 90}]}"#)), 270);
+        assert_eq!(stream_rotation_cw(&stream(r#"{"side_data_list": [{"rotation": 180}]}"#)), 180);
+        assert_eq!(stream_rotation_cw(&stream(r#"{"codec_type": "video"}"#)), 0);
+
+        assert_eq!(crate::renderer::rotation_filters(90), "transpose=clock");
+        assert_eq!(crate::renderer::rotation_filters(-90), "transpose=cclock");
+        assert_eq!(crate::renderer::rotation_filters(0), "");
+    }
+
+    #[test]
+    fn incomplete_concat_output_is_removed_unless_completed() {
+        let dir = std::env::temp_dir().join(format!("qv_concat_partial_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cancelled = dir.join("cancelled.mp4");
+        let finished = dir.join("finished.mp4");
+        fs::write(&cancelled, b"partial").unwrap();
+        fs::write(&finished, b"complete").unwrap();
+
+        drop(IncompleteOutput::new(&cancelled));
+        IncompleteOutput::new(&finished).complete();
+
+        assert!(!cancelled.exists());
+        assert!(finished.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn image_quality_maps_to_the_encoder_scale_of_the_extension() {
+        let args = |out: &str, q: Option<u8>| image_quality_args(out, q).unwrap().join(" ");
+        assert_eq!(args("sheet.jpg", None), "-q:v 2");
+        assert_eq!(args("sheet.JPEG", Some(100)), "-q:v 2");
+        assert_eq!(args("sheet.jpg", Some(1)), "-q:v 31");
+        assert_eq!(args("wave.webp", None), "-quality 90");
+        assert_eq!(args("wave.webp", Some(40)), "-quality 40");
+        assert_eq!(args("wave.png", Some(10)), "");
+        assert!(image_quality_args("wave.webp", Some(0)).unwrap_err().starts_with(INVALID_IMAGE_OUTPUT_ERROR));
+        assert!(image_quality_args("wave.gif", None).unwrap_err().starts_with(INVALID_IMAGE_OUTPUT_ERROR));
+    }
+
+    #[test]
+    fn zero_or_unparsed_audio_durations_are_reported_by_path() {
+        let paths = vec!["a.mp3".to_string(), "empty.mp3".to_string(), "broken.mp3".to_string()];
+        assert_eq!(unreadable_audio(&paths, &[12.5, 0.0, f64::NAN]), vec!["empty.mp3", "broken.mp3"]);
+        assert!(unreadable_audio(&paths[..1], &[3.0]).is_empty());
+    }
+
+    #[test]
+    fn oversized_target_is_rejected_or_downscaled_to_the_cap() {
+        let mut options = ExportOptions { max_dimension: Some(3840), ..Default::default() };
+        assert_eq!(options.capped_size((1920, 1080)).unwrap(), (1920, 1080));
+        let err = options.capped_size((7680, 4320)).unwrap_err();
+        assert!(err.starts_with(crate::renderer::RESOLUTION_TOO_LARGE_ERROR));
+
+        options.downscale_to_max_dimension = true;
+        assert_eq!(options.capped_size((7680, 4320)).unwrap(), (3840, 2160));
+        // Portrait, résultat impair : côtés pairs et sous le plafond
+        let (w, h) = options.capped_size((4321, 8000)).unwrap();
+        assert!(h <= 3840 && w % 2 == 0 && h % 2 == 0);
+        assert_eq!(ExportOptions::default().capped_size((7680, 4320)).unwrap(), (7680, 4320));
+    }
+
+    #[test]
+    fn concat_diagnosis_names_chunks_that_differ_from_the_majority() {
+        let video = |codec: &str, width: i64, height: i64| VideoStreamInfo {
+            codec: codec.to_string(),
+            width,
+            height,
+            pix_fmt: "yuv420p".to_string(),
+            time_base: "1/30".to_string(),
+            frame_rate: "30/1".to_string(),
+            avg_frame_rate: "30/1".to_string(),
+            field_order: String::new(),
+            sample_aspect_ratio: String::new(),
+            rotation: 0,
+        };
+        let info = |v: Option<VideoStreamInfo>| Some(MediaInfo { duration_s: 10.0, video: v, audio: None });
+        // Le premier chunk est le fautif : la référence reste celle de la majorité
+        let infos = vec![
+            info(Some(video("hevc", 1280, 720))),
+            info(Some(video("h264", 1920, 1080))),
+            info(Some(video("h264", 1920, 1080))),
+            info(None),
+            None,
+        ];
+        let reference = majority_video(&infos);
+        assert_eq!(reference.map(|v| v.width), Some(1920));
+        let issues: Vec<Option<String>> = infos.iter().map(|i| concat_input_issue(i.as_ref(), reference)).collect();
+        assert_eq!(issues[0].as_deref(), Some("hevc 1280x720 au lieu de h264 1920x1080"));
+        assert!(issues[1].is_none() && issues[2].is_none());
+        assert_eq!(issues[3].as_deref(), Some("aucun flux vidéo"));
+        assert_eq!(issues[4].as_deref(), Some("illisible par ffprobe"));
+    }
+
+    #[test]
+    fn source_fps_is_kept_rational_within_a_sane_range() {
+        assert_eq!(source_export_fps("30000/1001"), Ok(FrameRate { num: 30000, den: 1001 }));
+        assert_eq!(source_export_fps("24000/1001"), Ok(FrameRate { num: 24000, den: 1001 }));
+        assert_eq!(source_export_fps("60/1"), Ok(60.into()));
+        assert_eq!(source_export_fps("50/2"), Ok(25.into()));
+        assert_eq!(source_export_fps("30000/1001").unwrap().to_string(), "30000/1001");
+        assert_eq!(serde_json::from_str::<FrameRate>(r#""30000/1001""#).unwrap().as_f64(), 30000.0 / 1001.0);
+        assert_eq!(serde_json::from_str::<FrameRate>("30").unwrap(), 30.into());
+        // Timelapse à 12 fps, ralenti à 120 fps, cadence absente (`0/0`)
+        assert!(source_export_fps("12/1").is_err());
+        assert!(source_export_fps("120/1").is_err());
+        assert!(source_export_fps("0/0").is_err());
+    }
+
+    #[test]
+    fn fade_in_and_out_durations_are_independent() {
+        let (fps, count) = (10, 30);
+        let options = ExportOptions { fade_in_ms: Some(200), fade_out_ms: Some(1500), ..Default::default() };
+        assert_eq!(options.caption_fade_ms(500), (200, 1500));
+        assert_eq!(ExportOptions { fade_in_ms: Some(0), ..Default::default() }.caption_fade_ms(500), (0, 500));
+        let fade = options.clip_fade(500, fps.into());
+
+        assert_eq!(
+            clip_fade_filters(fade, 3.0),
+            ",fade=t=in:st=0:d=0.200000:alpha=1,fade=t=out:st=1.500000:d=1.500000:alpha=1"
+        );
+        // Même rampe côté WGPU : opaque après 0.2 s, puis sortie lente sur les 1.5 dernières secondes
+        let alpha = |i| clip_fade_alpha(i, count, fps.into(), fade);
+        assert_eq!(alpha(0), 0.0);
+        assert_eq!(alpha(2), 1.0);
+        assert!((alpha(20) - 10.0 / 15.0).abs() < 1e-6);
+        // Clip trop court pour les deux : réduits en proportion, sans se chevaucher
+        let (in_s, out_s) = fade.for_clip(0.85);
+        assert!((in_s - 0.1).abs() < 1e-9 && (out_s - 0.75).abs() < 1e-9);
+        // Le maintien du dernier sous-titre suit le fondu de sortie
+        let timings = calculate_export_timings(&[0], fps.into(), options.caption_fade_ms(500).1, 0, None, None, None);
+        assert!((timings.durations_s[0] - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn output_name_template_fills_variables_and_strips_invalid_characters() {
+        // 2024-05-01 12:00 UTC
+        let unix_s = 1_714_564_800;
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(unix_s), "2024-05-01");
+        assert_eq!(utc_date(951_782_400), "2000-02-29");
+
+        let name = render_output_name("Surah_{title}_{resolution}_{date}.mp4", "Fatiha", (1920, 1080), VideoCodec::H264, unix_s);
+        assert_eq!(name, "Surah_Fatiha_1080p_2024-05-01.mp4");
+        let portrait = render_output_name("{title}-{resolution}-{codec}", "Al-Kahf: 1/10?", (1080, 1920), VideoCodec::Vp9, unix_s);
+        assert_eq!(portrait, "Al-Kahf_ 1_10_-1080p-vp9.mp4");
+        assert_eq!(sanitize_file_name(" <>. "), "__");
+        assert_eq!(sanitize_file_name("..."), "export");
+    }
+
+    #[test]
+    fn height_fraction_blur_scales_with_resolution() {
+        let sigma = ExportOptions::default();
+        assert_eq!(sigma.blur_sigma(Some(8.0), 1080), Some(8.0));
+        assert_eq!(sigma.decoder_blur(Some(8.0), 1080), 8.0);
+
+        let options: ExportOptions = serde_json::from_str(r#"{"blurUnit":"height"}"#).unwrap();
+        assert_eq!(options.blur_unit, BlurUnit::Height);
+        assert_eq!(options.blur_sigma(Some(0.01), 1080), Some(10.8));
+        assert_eq!(options.blur_sigma(Some(0.01), 2160), Some(21.6));
+        assert_eq!(options.blur_sigma(None, 1080), None);
+        // Même écart-type côté boxblur : rayon = sigma * √3
+        let radius = options.decoder_blur(Some(0.01), 1080) * 10.0;
+        assert!((radius / 3f64.sqrt() - 10.8).abs() < 1e-9);
+        // export_video transmet la valeur brute : un sigma déjà converti serait reconverti
+        assert!(radius <= 540.0);
+        let reconverted = options.decoder_blur(options.blur_sigma(Some(0.01), 1080), 1080) * 10.0;
+        assert!(reconverted > 540.0);
+    }
+
+    #[test]
+    fn empty_output_is_deleted_and_reported() {
+        let dir = std::env::temp_dir().join(format!("qv-empty-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let empty = dir.join("empty.mp4");
+        let full = dir.join("full.mp4");
+        std::fs::write(&empty, b"").unwrap();
+        std::fs::write(&full, vec![0u8; MIN_OUTPUT_BYTES as usize]).unwrap();
+
+        let err = reject_empty_output(&empty.to_string_lossy()).unwrap_err();
+        assert!(err.starts_with(EMPTY_OUTPUT_ERROR), "{}", err);
+        assert!(!empty.exists());
+        assert!(reject_empty_output(&full.to_string_lossy()).is_ok());
+        assert!(full.exists());
+        // Fichier absent : laissé aux autres vérifications
+        assert!(reject_empty_output(&dir.join("missing.mp4").to_string_lossy()).is_ok());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn chunk_reports_add_up_stage_by_stage() {
+        let chunk = |encode_s: f64| ExportReport {
+            duration_s: 10.0,
+            frames: 250,
+            wall_clock_s: encode_s + 1.0,
+            stage_timings: [("setup".to_string(), 1.0), ("encode".to_string(), encode_s)].into_iter().collect(),
+            integrated_lufs: Some(-14.0),
+            ..Default::default()
+        };
+        let mut total = ExportReport { integrated_lufs: None, ..chunk(4.0) };
+        total.accumulate(&chunk(6.0));
+        assert_eq!(total.frames, 500);
+        assert!((total.duration_s - 20.0).abs() < 1e-9);
+        assert!((total.stage_timings["encode"] - 10.0).abs() < 1e-9);
+        assert!((total.stage_timings["setup"] - 2.0).abs() < 1e-9);
+        // La sonie d'un chunk n'est pas reportée sur la vidéo finale : elle est mesurée après concaténation
+        assert_eq!(total.integrated_lufs, None);
+        assert!(!total.loudness_after_concat);
+        total.accumulate(&ExportReport { loudness_after_concat: true, ..chunk(2.0) });
+        assert!(total.loudness_after_concat);
+    }
+
+    #[test]
+    fn concat_crossfade_keeps_every_audio_track() {
+        let (graph, outputs) = concat_audio_crossfade_graph(&[10.0, 8.0], 0.05, 2);
+        assert_eq!(outputs, vec!["t0_aout".to_string(), "t1_aout".to_string()]);
+        assert!(graph.contains("[1:a:1]"));
+        assert!(graph.contains("[2:a:1]"));
+        assert!(graph.contains("[t1_ca0][t1_ca1]acrossfade=d=0.050000:c1=tri:c2=tri[t1_aout]"));
+
+        let (single, outputs) = concat_audio_crossfade_graph(&[10.0], 0.05, 1);
+        assert_eq!(outputs, vec!["t0_aout".to_string()]);
+        assert!(single.ends_with("[t0_ca0]anull[t0_aout]"));
+    }
+
+    #[test]
+    fn index_range_reindexes_ayah_backgrounds() {
+        let map: HashMap<usize, String> =
+            [(2, "before.mp4"), (5, "first.mp4"), (7, "inside.png"), (11, "after.mp4")]
+                .into_iter()
+                .map(|(i, p)| (i, p.to_string()))
+                .collect();
+        let selected = select_ayah_backgrounds(map, 5, 10);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[&0], "first.mp4");
+        assert_eq!(selected[&2], "inside.png");
+    }
+
+    #[test]
+    fn karaoke_cues_are_read_from_the_options() {
+        let options: ExportOptions = serde_json::from_value(serde_json::json!({
+            "karaoke": [{
+                "text": "In the name of God",
+                "words": [
+                    { "end": 2, "startMs": 0 },
+                    { "end": 6, "startMs": 400 },
+                    { "end": 11, "startMs": 700 },
+                ],
+            }],
+        }))
+        .unwrap();
+        let cue = &options.karaoke.unwrap()[0];
+        // À 500 ms dans le sous-titre, « In the » est prononcé ; tout l'est après le dernier mot
+        assert_eq!(&cue.text[..cue.spoken_until(500)], "In the");
+        assert_eq!(cue.spoken_until(0), 2);
+        assert_eq!(cue.spoken_until(10_000), 11);
+    }
+
+    #[test]
+    fn only_matching_h264_sources_skip_preprocessing() {
+        let stream = |codec: &str| VideoStreamInfo {
+            codec: codec.to_string(),
+            width: 1920,
+            height: 1080,
+            pix_fmt: "yuv420p".to_string(),
+            time_base: "1/12800".to_string(),
+            frame_rate: "25/1".to_string(),
+            avg_frame_rate: "25/1".to_string(),
+            field_order: "progressive".to_string(),
+            sample_aspect_ratio: "1:1".to_string(),
+            rotation: 0,
+        };
+        let info = |codec: &str| MediaInfo { duration_s: 10.0, video: Some(stream(codec)), audio: None };
+        assert!(source_matches_target(&info("h264"), 1920, 1080, 25.into(), None, None));
+        // Même taille et cadence, mais le pipeline attend du H.264 : réencodage
+        for codec in ["hevc", "vp9", "prores"] {
+            assert!(!source_matches_target(&info(codec), 1920, 1080, 25.into(), None, None), "{}", codec);
+        }
+    }
+
+    #[test]
+    fn leading_silence_shifts_the_whole_timeline() {
+        // Le début recule jusqu'à la parole, la fin reste en place
+        assert_eq!(skip_leading_silence(0, Some(10_000), 1.5, 0), (1500, Some(8500)));
+        assert_eq!(skip_leading_silence(0, None, 1.5, 500), (2000, None));
+        // Export qui commence déjà après le silence, ou pas de silence : rien ne bouge
+        assert_eq!(skip_leading_silence(3000, Some(4000), 1.5, 0), (3000, Some(4000)));
+        assert_eq!(skip_leading_silence(0, Some(4000), 0.0, 500), (0, Some(4000)));
+    }
+
+    #[test]
+    fn opus_refuses_44100_hz() {
+        assert!(resolve_audio_codec_args("out.webm", None, 2, 48000).is_ok());
+        let err = resolve_audio_codec_args("out.webm", None, 2, 44100).unwrap_err();
+        assert!(err.starts_with(UNSUPPORTED_SAMPLE_RATE_ERROR), "{}", err);
+        // AAC accepte 44100
+        assert!(resolve_audio_codec_args("out.mp4", Some(AudioCodec::Aac), 2, 44100).is_ok());
+    }
+
+    #[test]
+    fn empty_translation_suffix_disables_the_layer() {
+        let with = |suffix: Option<&str>| ExportOptions { translation_suffix: suffix.map(str::to_string), ..Default::default() };
+        assert_eq!(with(None).translation_suffix(), Some("_t"));
+        assert_eq!(with(Some("_en")).translation_suffix(), Some("_en"));
+        assert_eq!(with(Some("")).translation_suffix(), None);
+    }
+
+    #[test]
+    fn tune_is_only_passed_to_encoders_that_know_it() {
+        let tuned = |codec: &str, tune: &str| {
+            let mut params = Vec::new();
+            apply_tune(codec, &mut params, Some(tune.to_string()));
+            params
+        };
+        assert_eq!(tuned("libx264", "stillimage"), ["-tune", "stillimage"]);
+        assert_eq!(tuned("libx265", "grain"), ["-tune", "grain"]);
+        // x265 ne connaît ni `stillimage` ni `film` ; les encodeurs HEVC matériels aucun réglage
+        assert!(tuned("libx265", "stillimage").is_empty());
+        assert!(tuned("hevc_nvenc", "grain").is_empty());
+    }
+
+    #[test]
+    fn caption_frames_are_read_once_and_grouped_by_content() {
+        let dir = std::env::temp_dir().join(format!("qv-scan-frames-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(2, 2).write_to(&mut png, image::ImageOutputFormat::Png).unwrap();
+        let (a, a_again, broken) = (file("a.png", png.get_ref()), file("b.png", png.get_ref()), file("c.png", b"not a png"));
+        let blank = dir.join("blank.png").to_string_lossy().to_string();
+
+        let paths = [Some(a.as_str()), Some(a_again.as_str()), Some(broken.as_str()), None];
+        let (resolved, repeated, valid) = scan_caption_frames(paths.into_iter(), "test", || Ok(blank.clone())).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(resolved, [a, a_again, blank.clone(), blank]);
+        // Même contenu que la précédente : b après a, puis deux frames transparentes de suite
+        assert_eq!(repeated, [false, true, false, true]);
+        assert_eq!(valid, 2);
+    }
+
+    #[test]
+    fn concat_audio_codec_follows_the_container_table() {
+        // WebM : Opus par défaut, AAC refusé avant de lancer la concaténation
+        let webm = resolve_audio_codec_args("final.webm", None, 2, 48000).unwrap();
+        assert_eq!(&webm[..2], ["-c:a", "libopus"]);
+        assert!(resolve_audio_codec_args("final.webm", Some(AudioCodec::Aac), 2, 48000).is_err());
+        // M4V accepte les codecs sans perte comme MP4
+        assert_eq!(&resolve_audio_codec_args("final.m4v", Some(AudioCodec::Flac), 2, 48000).unwrap()[..2], ["-c:a", "flac"]);
+        assert_eq!(&resolve_audio_codec_args("final.m4v", None, 2, 48000).unwrap()[..4], ["-c:a", "aac", "-b:a", "320k"]);
+    }
 }
@@ -4,6 +4,20 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
+
+// Logs de l'export et du rendu : sur stdout, sauf une fois qu'un export y écrit sa vidéo (`-`),
+// où ils passent sur stderr pour ne pas se mêler aux octets du MP4. Défini avant les modules
+// pour être utilisable dans chacun.
+macro_rules! log_out {
+    ($($arg:tt)*) => {
+        if crate::renderer::stdout_reserved() {
+            eprintln!($($arg)*)
+        } else {
+            std::println!($($arg)*)
+        }
+    };
+}
+
 mod exporter;
 mod renderer;
 mod binaries;
@@ -2003,11 +2017,25 @@ pub fn run() {
 
             get_video_dimensions,
             exporter::export_video,
+            exporter::export_preview,
             exporter::cancel_export,
+            exporter::cancel_all_exports,
+            exporter::validate_filter,
+            exporter::stage_media_buffer,
+            exporter::set_force_cpu_encoding,
+            exporter::warm_background_cache,
             exporter::concat_videos,
             exporter::start_streaming_export,
             exporter::send_frame,
             exporter::finish_streaming_export,
+            exporter::estimate_output_size,
+            exporter::render_waveform,
+            exporter::contact_sheet,
+            exporter::render_intro_card,
+            exporter::benchmark_encoders,
+            exporter::set_chunk_plan,
+            exporter::set_nvenc_test_resolutions,
+            exporter::reset_nvenc_cache,
             convert_audio_to_cbr,
             cut_audio,
             cut_video,
@@ -2030,6 +2058,13 @@ pub fn run() {
                         .build(),
                 )?;
             }
+            // Un plantage pendant un export peut laisser des ffmpeg actifs (fichiers verrouillés)
+            if let Ok(data_dir) = app.path().app_data_dir() {
+                let reaped = exporter::reap_orphaned_ffmpeg(&data_dir);
+                if reaped > 0 {
+                    println!("[startup] {} processus ffmpeg orphelin(s) arrêté(s)", reaped);
+                }
+            }
             Ok(())
         })
         .run(tauri::generate_context!())
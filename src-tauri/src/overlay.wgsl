@@ -3,6 +3,21 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
 }
 
+struct Layer {
+    alpha: f32,
+    // Wipe/mask reveal progress (0 = hidden, 1 = shown), ignored when mask_mode is 0
+    reveal: f32,
+    // Translation in frame fractions (x right, y down)
+    offset: vec2<f32>,
+    // 0 = uniform alpha, 1-4 = wipe up/down/left/right, 5 = mask texture
+    mask_mode: u32,
+    // Width of the reveal's soft edge, as a fraction of the reveal
+    softness: f32,
+}
+
+@group(1) @binding(0) var<uniform> layer: Layer;
+@group(1) @binding(1) var t_mask: texture_2d<f32>;
+
 @vertex
 fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     var pos = array<vec2<f32>, 4>(
@@ -18,17 +33,51 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
         vec2<f32>(1.0, 1.0)
     );
     var out: VertexOutput;
-    out.position = vec4<f32>(pos[vertex_index], 0.0, 1.0);
+    let shift = vec2<f32>(layer.offset.x * 2.0, -layer.offset.y * 2.0);
+    out.position = vec4<f32>(pos[vertex_index] + shift, 0.0, 1.0);
     out.tex_coords = tex[vertex_index];
     return out;
 }
 
 @group(0) @binding(0) var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1) var s_diffuse: sampler;
-@group(1) @binding(0) var<uniform> alpha: f32;
+
+// Layer alpha at `uv`: a texel is uncovered once the reveal passes its threshold
+// (position along the wipe, or mask value: dark areas first)
+fn layer_alpha(uv: vec2<f32>) -> f32 {
+    // Sampled unconditionally: textureSample requires uniform control flow
+    let mask = textureSample(t_mask, s_diffuse, uv).r;
+    var threshold: f32;
+    switch layer.mask_mode {
+        case 1u: { threshold = 1.0 - uv.y; }
+        case 2u: { threshold = uv.y; }
+        case 3u: { threshold = 1.0 - uv.x; }
+        case 4u: { threshold = uv.x; }
+        case 5u: { threshold = mask; }
+        default: { return layer.alpha; }
+    }
+    let s = max(layer.softness, 0.001);
+    return layer.alpha * clamp((layer.reveal * (1.0 + s) - threshold) / s, 0.0, 1.0);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    return vec4<f32>(color.rgb, color.a * alpha);
+    return vec4<f32>(color.rgb, color.a * layer_alpha(in.tex_coords));
+}
+
+// Caption textures are premultiplied on upload, so filtering never bleeds the colour of
+// transparent texels into anti-aliased edges; the layer alpha scales all four channels
+@fragment
+fn fs_premultiplied_source(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_diffuse, s_diffuse, in.tex_coords) * layer_alpha(in.tex_coords);
+}
+
+// Premultiplied output for the screen/multiply/add blend modes, whose blend equations
+// need transparent texels to contribute nothing
+@fragment
+fn fs_premultiplied(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let a = color.a * layer_alpha(in.tex_coords);
+    return vec4<f32>(color.rgb * a, a);
 }
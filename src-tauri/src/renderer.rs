@@ -1,20 +1,54 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Mutex as TokioMutex;
 use std::process::{Child, Command, Stdio, ChildStdout};
 use std::io::{Read, Write};
 use wgpu::util::DeviceExt;
 use glyphon::{Attrs, Buffer, Cache, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache, TextAtlas, TextArea, TextBounds, Weight, cosmic_text::Align};
+use glyphon::cosmic_text::{Fallback, PlatformFallback};
+use unicode_script::Script;
+
+/// Prefix of the errors returned when the GPU runs out of memory, so callers can retry smaller.
+pub const GPU_OUT_OF_MEMORY_ERROR: &str = "GPU_OUT_OF_MEMORY";
+/// Prefix of the errors raised when the device fails mid-render (lost device, failed poll or map).
+pub const GPU_ERROR: &str = "GPU_ERROR";
+/// Prefix of the errors returned when the requested size exceeds what the device can allocate.
+pub const RESOLUTION_TOO_LARGE_ERROR: &str = "RESOLUTION_TOO_LARGE";
 
 pub struct WgpuContext {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub texture_format: wgpu::TextureFormat,
+    /// Backend of the adapter wgpu actually picked.
+    pub backend: wgpu::Backend,
+}
+
+/// Graphics API forced for the export, to work around driver bugs in one of them.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuBackend {
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl GpuBackend {
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            GpuBackend::Vulkan => wgpu::Backends::VULKAN,
+            GpuBackend::Dx12 => wgpu::Backends::DX12,
+            GpuBackend::Metal => wgpu::Backends::METAL,
+            GpuBackend::Gl => wgpu::Backends::GL,
+        }
+    }
 }
 
 impl WgpuContext {
-    pub async fn new() -> Result<Self, String> {
+    /// `backend: None` lets wgpu choose among all available backends.
+    pub async fn new(backend: Option<GpuBackend>) -> Result<Self, String> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
+            backends: backend.map_or(wgpu::Backends::all(), GpuBackend::backends),
             ..Default::default()
         });
 
@@ -26,6 +60,8 @@ impl WgpuContext {
             })
             .await
             .map_err(|e| format!("Failed to find an appropriate adapter: {:?}", e))?;
+        let backend = adapter.get_info().backend;
+        log_out!("[WgpuContext] Using {} backend", backend.to_str());
 
         let (device, queue): (wgpu::Device, wgpu::Queue) = adapter
             .request_device(
@@ -45,13 +81,18 @@ impl WgpuContext {
             device,
             queue,
             texture_format: wgpu::TextureFormat::Rgba8UnormSrgb, // Standard format for compatibility
+            backend,
         })
     }
 }
 
 
+/// Child process shared with `ACTIVE_EXPORTS` so that `cancel_export` can kill it
+/// while the render loop still owns the decoder/encoder.
+pub type SharedChild = Arc<Mutex<Option<Child>>>;
+
 pub struct VideoDecoder {
-    pub child: Child,
+    pub child: SharedChild,
     pub width: u32,
     pub height: u32,
     pub reader: std::io::BufReader<ChildStdout>,
@@ -59,17 +100,17 @@ pub struct VideoDecoder {
 
 impl VideoDecoder {
     pub fn new(
+        ffmpeg_exe: &str,
         path: &str, 
         width: u32, 
         height: u32, 
-        fps: u32, 
+        fps: FrameRate, 
         start_time_ms: u32,
+        rotation_cw: Option<i32>,
         blur: f64,
         overlay_color: &str, 
         overlay_opacity: f64
     ) -> Result<Self, String> {
-        let ffmpeg_exe = "ffmpeg"; 
-        
         let mut cmd = Command::new(ffmpeg_exe);
         
         if path.starts_with("synthetic:") {
@@ -94,14 +135,23 @@ impl VideoDecoder {
             if !is_image && start_time_ms > 0 {
                 cmd.arg("-ss").arg(format!("{:.3}", start_time_ms as f64 / 1000.0));
             }
+            // Known rotation: applied explicitly below rather than left to FFmpeg's autorotate,
+            // so the pipe matches the filter path whatever the decoder does with the side data
+            if rotation_cw.is_some() {
+                cmd.arg("-noautorotate");
+            }
             cmd.args(&["-i", path]);
         }
 
-        // 1. Scale and Pad (Letterboxing)
-        let mut filters = format!(
+        // 1. Orientation, then Scale and Pad (Letterboxing)
+        let mut filters = rotation_filters(rotation_cw.unwrap_or(0)).to_string();
+        if !filters.is_empty() {
+            filters.push(',');
+        }
+        filters.push_str(&format!(
             "scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2", 
             width, height, width, height
-        );
+        ));
 
         // 2. Blur if needed
         if blur > 0.1 {
@@ -125,7 +175,39 @@ impl VideoDecoder {
             "-r", &fps.to_string(), 
             "-",
         ]);
-        
+        Self::spawn(cmd, width, height)
+    }
+
+    /// Decodes a motion overlay (e.g. an animated lower-third) to straight-alpha RGBA frames.
+    /// Alpha comes from the source pixel format (ProRes 4444, QuickTime RLE, VP9 with alpha...);
+    /// sources without alpha decode opaque. Letterboxing is transparent instead of black.
+    pub fn new_overlay(ffmpeg_exe: &str, path: &str, width: u32, height: u32, fps: FrameRate, repeat: bool) -> Result<Self, String> {
+        let mut cmd = Command::new(ffmpeg_exe);
+        if repeat {
+            cmd.args(["-stream_loop", "-1"]);
+        }
+        // FFmpeg's native VP9 decoder drops the alpha side data; libvpx keeps it
+        if path.to_lowercase().ends_with(".webm") {
+            cmd.args(["-c:v", "libvpx-vp9"]);
+        }
+        cmd.args(["-i", path]);
+        let filters = format!(
+            "scale={}:{}:force_original_aspect_ratio=decrease,format=rgba,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black@0",
+            width, height, width, height
+        );
+        cmd.args([
+            "-vf", &filters,
+            "-an",
+            "-f", "image2pipe",
+            "-pix_fmt", "rgba",
+            "-vcodec", "rawvideo",
+            "-r", &fps.to_string(),
+            "-",
+        ]);
+        Self::spawn(cmd, width, height)
+    }
+
+    fn spawn(mut cmd: Command, width: u32, height: u32) -> Result<Self, String> {
         // Hide window on Windows
         #[cfg(target_os = "windows")]
         {
@@ -142,7 +224,7 @@ impl VideoDecoder {
         let stdout = child.stdout.take().ok_or("Failed to open stdout")?;
         
         Ok(Self {
-            child,
+            child: Arc::new(Mutex::new(Some(child))),
             width,
             height,
             reader: std::io::BufReader::new(stdout),
@@ -165,27 +247,372 @@ impl VideoDecoder {
     }
 }
 
+/// Filters that turn a frame stored with a `rotation_cw` display rotation (clockwise degrees, as
+/// from the container's rotate tag) upright, the same ones FFmpeg's autorotate inserts.
+pub fn rotation_filters(rotation_cw: i32) -> &'static str {
+    match rotation_cw.rem_euclid(360) {
+        90 => "transpose=clock",
+        180 => "hflip,vflip",
+        270 => "transpose=cclock",
+        _ => "",
+    }
+}
+
+/// Premultiplies straight-alpha RGBA8 for an sRGB texture. The product is taken in linear light
+/// (the space the sampler filters in) and re-encoded; opaque and transparent texels are exact.
+pub fn premultiply_srgb(data: &[u8]) -> Vec<u8> {
+    let to_linear: Vec<f32> = (0..=255u8)
+        .map(|c| {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        })
+        .collect();
+    let to_srgb = |l: f32| {
+        let c = if l <= 0.0031308 { l * 12.92 } else { 1.055 * l.powf(1.0 / 2.4) - 0.055 };
+        (c * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+    let mut out = data.to_vec();
+    for px in out.chunks_exact_mut(4) {
+        match px[3] {
+            255 => {}
+            0 => px[..3].fill(0),
+            a => {
+                let a = a as f32 / 255.0;
+                for c in &mut px[..3] {
+                    *c = to_srgb(to_linear[*c as usize] * a);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Export frame rate, kept as a ratio so NTSC rates (`30000/1001`) are never rounded.
+/// Deserializes from a whole number (`30`) or an FFmpeg ratio string (`"30000/1001"`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl FrameRate {
+    pub fn new(num: u32, den: u32) -> Option<Self> {
+        if num == 0 || den == 0 {
+            return None;
+        }
+        let (mut a, mut b) = (num, den);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        Some(Self { num: num / a, den: den / a })
+    }
+
+    /// Parses `30`, `25/1` or `30000/1001` (ffprobe's `avg_frame_rate` format).
+    pub fn parse(rate: &str) -> Option<Self> {
+        match rate.trim().split_once('/') {
+            Some((n, d)) => Self::new(n.trim().parse().ok()?, d.trim().parse().ok()?),
+            None => Self::new(rate.trim().parse().ok()?, 1),
+        }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Nearest whole rate, for settings that need an integer (e.g. a 2 s GOP).
+    pub fn rounded(self) -> u32 {
+        self.as_f64().round() as u32
+    }
+
+    /// File-name-safe form for cache keys: `30` or `30000_1001`.
+    pub fn tag(self) -> String {
+        self.to_string().replace('/', "_")
+    }
+}
+
+impl From<i32> for FrameRate {
+    fn from(fps: i32) -> Self {
+        Self { num: fps.max(1) as u32, den: 1 }
+    }
+}
+
+/// FFmpeg form, usable as `-r`, `-framerate`, `fps=` or lavfi `r=`.
+impl std::fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for FrameRate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Whole(u32),
+            Ratio(String),
+        }
+        let rate = match Raw::deserialize(deserializer)? {
+            Raw::Whole(fps) => Self::new(fps, 1),
+            Raw::Ratio(rate) => Self::parse(&rate),
+        };
+        rate.ok_or_else(|| serde::de::Error::custom("invalid frame rate"))
+    }
+}
+
+impl serde::Serialize for FrameRate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Output path that makes `VideoEncoder` write a fragmented MP4 to the process stdout
+/// (e.g. piped to an uploader). The app's own logs must then be kept off stdout.
+pub const STDOUT_OUTPUT: &str = "-";
+
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Hands stdout over to `STDOUT_OUTPUT` exports for the rest of the process: from then on
+/// the app's `log_out!` logs (defined in lib.rs) go to stderr instead of the video bytes.
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::SeqCst);
+}
+
+pub fn stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::SeqCst)
+}
+
 pub struct VideoEncoder {
-    pub child: Child,
+    pub child: SharedChild,
     pub width: u32,
     pub height: u32,
-    pub writer: std::io::BufWriter<std::process::ChildStdin>,
+    /// Raw frame pipe to ffmpeg; `None` in image-sequence mode.
+    pub writer: Option<FrameWriter>,
+    /// `printf`-style PNG path (e.g. `frames/%06d.png`) when exporting an image sequence.
+    frame_pattern: Option<String>,
+    frame_index: u32,
+    has_process: bool,
+    /// Program and arguments of the video encoder (empty in image-sequence mode).
+    pub command: Vec<String>,
+}
+
+/// Frames allowed in flight between the render loop and ffmpeg before `write_frame` blocks.
+const ENCODER_QUEUE_FRAMES: usize = 4;
+/// The pipe is flushed every N frames so ffmpeg never waits on a half-filled buffer.
+const ENCODER_FLUSH_INTERVAL: u32 = 30;
+
+/// Bounded queue feeding ffmpeg's stdin from a dedicated thread.
+///
+/// When the encoder lags, `send` blocks once `ENCODER_QUEUE_FRAMES` frames are pending,
+/// so the render loop is throttled to the encoder's pace instead of buffering without bound.
+pub struct FrameWriter {
+    sender: Option<std::sync::mpsc::SyncSender<Vec<u8>>>,
+    thread: Option<std::thread::JoinHandle<Result<(), String>>>,
+}
+
+impl FrameWriter {
+    fn spawn(stdin: std::process::ChildStdin) -> Self {
+        let (sender, receiver) = std::sync::mpsc::sync_channel::<Vec<u8>>(ENCODER_QUEUE_FRAMES);
+        let thread = std::thread::spawn(move || {
+            let mut writer = std::io::BufWriter::new(stdin);
+            let mut written = 0u32;
+            for frame in receiver {
+                writer.write_all(&frame).map_err(|e| format!("Failed to write frame: {}", e))?;
+                written += 1;
+                if written == ENCODER_FLUSH_INTERVAL {
+                    writer.flush().map_err(|e| format!("Failed to flush encoder input: {}", e))?;
+                    written = 0;
+                }
+            }
+            writer.flush().map_err(|e| format!("Failed to flush encoder input: {}", e))
+        });
+        Self { sender: Some(sender), thread: Some(thread) }
+    }
+
+    fn send(&mut self, frame: Vec<u8>) -> Result<(), String> {
+        let sender = self.sender.as_ref().ok_or("Encoder input already closed")?;
+        if sender.send(frame).is_ok() {
+            return Ok(());
+        }
+        // The writer thread only hangs up on error: surface it
+        self.sender = None;
+        match self.thread.take().map(|t| t.join()) {
+            Some(Ok(Err(e))) => Err(e),
+            Some(Err(_)) => Err("Encoder writer thread panicked".to_string()),
+            _ => Err("Encoder input already closed".to_string()),
+        }
+    }
+
+    /// Drains pending frames and closes stdin, signalling EOF to ffmpeg.
+    fn close(mut self) -> Result<(), String> {
+        self.sender = None;
+        match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| "Encoder writer thread panicked".to_string())?,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Per-export audio processing, applied to each track before they are concatenated.
+#[derive(Clone, Debug, Default)]
+pub struct AudioOptions {
+    /// Loop the concatenated audio until the export duration is filled (background music).
+    pub loop_audio: bool,
+    /// Silence (ms) inserted before each track (same order as the audio paths).
+    pub track_delay_ms: Vec<u32>,
+    /// Audio encoder arguments (`-c:a ...`). Empty means AAC 320k.
+    pub codec_args: Vec<String>,
+    /// Output channel count: 1 for mono, anything else means stereo.
+    pub channels: u32,
+    /// Resampling target and output rate in Hz; 0 means 48000.
+    pub sample_rate: u32,
+    /// Separate output audio streams. Empty (or no valid index) means a single stream concatenating every input.
+    pub output_tracks: Vec<AudioTrack>,
+}
+
+/// One selectable audio stream of the output file (e.g. recitation, translation voiceover).
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AudioTrack {
+    /// Indexes into the audio paths, concatenated in this order. Out-of-range indexes are ignored.
+    pub inputs: Vec<usize>,
+    /// ISO 639 language code written as stream metadata, e.g. `ara`, `eng`.
+    pub language: Option<String>,
+}
+
+impl AudioOptions {
+    fn channel_arg(&self) -> &'static str {
+        if self.channels == 1 { "1" } else { "2" }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        if self.sample_rate == 0 { 48000 } else { self.sample_rate }
+    }
+
+    /// Input indexes of each output stream, with its label prefix and language.
+    fn streams(&self, input_count: usize) -> Vec<(String, Vec<usize>, Option<&str>)> {
+        let streams: Vec<_> = self
+            .output_tracks
+            .iter()
+            .map(|t| t.inputs.iter().copied().filter(|&j| j < input_count).collect::<Vec<_>>())
+            .zip(&self.output_tracks)
+            .filter(|(inputs, _)| !inputs.is_empty())
+            .enumerate()
+            .map(|(n, (inputs, t))| (format!("t{}_", n), inputs, t.language.as_deref()))
+            .collect();
+        if streams.is_empty() {
+            return vec![(String::new(), (0..input_count).collect(), None)];
+        }
+        streams
+    }
+
+    /// `-map` (and language metadata) arguments for the audio streams built by `audio_filter_complex`.
+    pub fn map_args(&self, input_count: usize) -> Vec<String> {
+        let mut args = Vec::new();
+        for (n, (prefix, _, language)) in self.streams(input_count).into_iter().enumerate() {
+            args.extend(["-map".to_string(), format!("[{}aout]", prefix)]);
+            if let Some(language) = language {
+                args.extend([format!("-metadata:s:a:{}", n), format!("language={}", language)]);
+            }
+        }
+        args
+    }
+}
+
+/// Builds the audio part of the filter graph: resample, concat, then trim to the export range.
+/// Audio inputs start at `first_input`. Each output stream ends in `[aout]`, or `[tN_aout]`
+/// with `output_tracks`. Returns `None` when there is no audio.
+pub(crate) fn audio_filter_complex(audio_paths: &[String], first_input: usize, start_s: f64, duration_s: f64, audio: &AudioOptions) -> Option<String> {
+    if audio_paths.is_empty() {
+        return None;
+    }
+    let chains: Vec<String> = audio
+        .streams(audio_paths.len())
+        .iter()
+        .map(|(prefix, inputs, _)| audio_stream_chain(inputs, prefix, first_input, start_s, duration_s, audio))
+        .collect();
+    Some(chains.join(";"))
+}
+
+// One output stream: the listed inputs, concatenated then trimmed; labels carry `prefix`
+fn audio_stream_chain(inputs: &[usize], prefix: &str, first_input: usize, start_s: f64, duration_s: f64, audio: &AudioOptions) -> String {
+    let mut filter_complex = String::new();
+    for (k, &j) in inputs.iter().enumerate() {
+        let mut chain = Vec::new();
+        chain.push(format!("aresample={}", audio.sample_rate()));
+        let delay = audio.track_delay_ms.get(j).copied().unwrap_or(0);
+        if delay > 0 {
+            chain.push(format!("adelay=delays={}:all=1", delay));
+        }
+        filter_complex.push_str(&format!("[{}:a]{}[{}aa{}];", j + first_input, chain.join(","), prefix, k));
+    }
+
+    let mut ins = String::new();
+    for k in 0..inputs.len() {
+        ins.push_str(&format!("[{}aa{}]", prefix, k));
+    }
+
+    let mut mixed = if inputs.len() > 1 {
+        filter_complex.push_str(&format!("{}concat=n={}:v=0:a=1[{}aacat];", ins, inputs.len(), prefix));
+        format!("{}aacat", prefix)
+    } else {
+        format!("{}aa0", prefix)
+    };
+
+    if audio.loop_audio {
+        // aloop keeps the whole stream in memory: fine for music beds, the trim below ends it
+        filter_complex.push_str(&format!("[{}]aloop=loop=-1:size=2147483647[{}aloop];", mixed, prefix));
+        mixed = format!("{}aloop", prefix);
+    }
+
+    filter_complex.push_str(&format!("[{}]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[{}aout]", mixed, start_s, duration_s, prefix));
+    filter_complex
+}
+
+/// Expands the first `%0Nd` / `%d` placeholder of an image-sequence pattern.
+pub fn sequence_frame_path(pattern: &str, index: u32) -> String {
+    let Some(start) = pattern.find('%') else {
+        return pattern.to_string();
+    };
+    let rest = &pattern[start + 1..];
+    let Some(d_pos) = rest.find('d') else {
+        return pattern.to_string();
+    };
+    let width: usize = rest[..d_pos].trim_start_matches('0').parse().unwrap_or(0);
+    format!("{}{:0width$}{}", &pattern[..start], index, &rest[d_pos + 1..], width = width)
+}
+
+/// Containers where `+faststart` applies (the MP4 family); other muxers reject or ignore it.
+fn is_faststart_container(path: &str) -> bool {
+    let ext = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    matches!(ext.as_str(), "mp4" | "mov" | "m4v")
 }
 
 impl VideoEncoder {
     pub fn new(
+        ffmpeg_exe: &str,
         path: &str, 
         w: u32, 
         h: u32, 
-        fps: u32, 
+        fps: FrameRate, 
         vcodec: &str, 
         vparams: Vec<String>, 
         vpreset: Option<String>,
         audio_paths: &[String],
         start_s: f64,
-        duration_s: f64
+        duration_s: f64,
+        audio: &AudioOptions,
+        faststart: bool,
+        extra_args: &[String],
     ) -> Result<Self, String> {
-        let mut command = Command::new("ffmpeg");
+        let mut command = Command::new(ffmpeg_exe);
         command.args(&[
             "-y",
             "-f", "rawvideo",
@@ -201,34 +628,14 @@ impl VideoEncoder {
             command.arg("-i").arg(p);
         }
 
-        // Setup filter complex for audio
-        let mut filter_complex = String::new();
-        let mut have_audio = false;
-        
-        if !audio_paths.is_empty() {
-             let a = audio_paths.len();
-             have_audio = true;
-             // Audio indices start at 1 (0 is video pipe)
-             for j in 0..a {
-                 filter_complex.push_str(&format!("[{}:a]aresample=48000[aa{}];", j + 1, j));
-             }
-             
-             let mut ins = String::new();
-             for j in 0..a {
-                 ins.push_str(&format!("[aa{}]", j));
-             }
-             
-             if a > 1 {
-                 filter_complex.push_str(&format!("{}concat=n={}:v=0:a=1[aacat];", ins, a));
-                 filter_complex.push_str(&format!("[aacat]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[aout]", start_s, duration_s));
-             } else {
-                 filter_complex.push_str(&format!("[aa0]atrim=start={:.6},asetpts=PTS-STARTPTS,atrim=end={:.6}[aout]", start_s, duration_s));
-             }
-        }
+        // Setup filter complex for audio (indices start at 1, 0 is the video pipe)
+        let audio_filter = audio_filter_complex(audio_paths, 1, start_s, duration_s, audio);
+        let have_audio = audio_filter.is_some();
 
-        if have_audio {
-            command.args(&["-filter_complex", &filter_complex]);
-            command.args(&["-map", "0:v", "-map", "[aout]"]);
+        if let Some(filter_complex) = &audio_filter {
+            command.args(&["-filter_complex", filter_complex]);
+            command.args(&["-map", "0:v"]);
+            command.args(audio.map_args(audio_paths.len()));
         } else {
             command.args(&["-map", "0:v"]);
         }
@@ -244,11 +651,30 @@ impl VideoEncoder {
 
         // Audio codec
         if have_audio {
-            command.args(&["-c:a", "aac", "-b:a", "320k", "-ac", "2"]);
+            if audio.codec_args.is_empty() {
+                command.args(&["-c:a", "aac", "-b:a", "320k"]);
+            } else {
+                command.args(&audio.codec_args);
+            }
+            command.args(&["-ac", audio.channel_arg()]);
+            command.args(&["-ar", &audio.sample_rate().to_string()]);
         }
 
         command.arg("-t").arg(format!("{:.6}", duration_s));
+        // A pipe cannot be seeked back to write the moov atom: fragmented MP4 instead
+        let to_stdout = path == STDOUT_OUTPUT;
+        if to_stdout {
+            command.args(["-f", "mp4", "-movflags", "+frag_keyframe+empty_moov"]);
+        } else if faststart && is_faststart_container(path) {
+            // Same as the filter path: moov moved to the front (second pass) for web playback
+            command.args(["-movflags", "+faststart"]);
+        }
+        // User-supplied, unvalidated arguments go right before the output path
+        command.args(extra_args);
         command.arg(path);
+        if !extra_args.is_empty() {
+            log_out!("[VideoEncoder] Custom ffmpeg args, full command: {:?}", command);
+        }
 
         // Hide window on Windows
         #[cfg(target_os = "windows")]
@@ -258,46 +684,267 @@ impl VideoEncoder {
         }
 
         command.stdin(Stdio::piped())
-               .stdout(Stdio::null())
+               .stdout(if to_stdout { Stdio::inherit() } else { Stdio::null() })
                .stderr(Stdio::null()); // Nullify stderr to prevent stalling when buffer is full
 
+        let command_line = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|a| a.to_string_lossy().to_string())
+            .collect();
         let mut child = command.spawn().map_err(|e| format!("Failed to spawn encoder: {}", e))?;
         let stdin = child.stdin.take().ok_or("Failed to capture encoder stdin")?;
 
         Ok(Self {
-            child,
+            child: Arc::new(Mutex::new(Some(child))),
+            width: w,
+            height: h,
+            writer: Some(FrameWriter::spawn(stdin)),
+            frame_pattern: None,
+            frame_index: 0,
+            has_process: true,
+            command: command_line,
+        })
+    }
+
+    /// Image-sequence variant: frames are written as PNG files following `pattern`,
+    /// and the audio (if any) is rendered to the `audio_out` sidecar by a separate ffmpeg.
+    pub fn new_image_sequence(
+        ffmpeg_exe: &str,
+        pattern: &str,
+        w: u32,
+        h: u32,
+        audio_paths: &[String],
+        start_s: f64,
+        duration_s: f64,
+        audio: &AudioOptions,
+        audio_out: &str,
+    ) -> Result<Self, String> {
+        let child = match audio_filter_complex(audio_paths, 0, start_s, duration_s, audio) {
+            Some(filter_complex) => {
+                let mut command = Command::new(ffmpeg_exe);
+                command.arg("-y");
+                for p in audio_paths {
+                    command.arg("-i").arg(p);
+                }
+                command.args(&["-filter_complex", &filter_complex]);
+                command.args(audio.map_args(audio_paths.len()));
+                let bitrate = if audio.channels == 1 { "160k" } else { "320k" };
+                command.args(&["-c:a", "aac", "-b:a", bitrate, "-ac", audio.channel_arg()]);
+                command.args(&["-ar", &audio.sample_rate().to_string()]);
+                command.arg("-t").arg(format!("{:.6}", duration_s));
+                command.arg(audio_out);
+
+                #[cfg(target_os = "windows")]
+                {
+                    use std::os::windows::process::CommandExt;
+                    command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+                }
+
+                command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+                Some(command.spawn().map_err(|e| format!("Failed to spawn audio sidecar encoder: {}", e))?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            has_process: child.is_some(),
+            child: Arc::new(Mutex::new(child)),
             width: w,
             height: h,
-            writer: std::io::BufWriter::new(stdin),
+            writer: None,
+            frame_pattern: Some(pattern.to_string()),
+            frame_index: 0,
+            command: Vec::new(),
         })
     }
     
-    pub fn write_frame(&mut self, buffer: &[u8]) -> Result<(), String> {
-        self.writer.write_all(buffer).map_err(|e| format!("Failed to write frame: {}", e))
+    /// Queues a frame for the encoder; blocks while the encoder is `ENCODER_QUEUE_FRAMES` frames behind.
+    pub fn write_frame(&mut self, buffer: Vec<u8>) -> Result<(), String> {
+        if let Some(pattern) = &self.frame_pattern {
+            let path = sequence_frame_path(pattern, self.frame_index);
+            self.frame_index += 1;
+            return image::save_buffer(&path, &buffer, self.width, self.height, image::ColorType::Rgba8)
+                .map_err(|e| format!("Failed to write frame {}: {}", path, e));
+        }
+        match self.writer.as_mut() {
+            Some(writer) => writer.send(buffer),
+            None => Err("Encoder input already closed".to_string()),
+        }
     }
     
-    pub fn finish(mut self) -> Result<(), String> {
-        // Drop writer to close stdin and signal EOF to ffmpeg
-        drop(self.writer);
-        let status = self.child.wait().map_err(|e| format!("Failed to wait on ffmpeg: {}", e))?;
+    pub fn finish(self) -> Result<(), String> {
+        // Drain queued frames and close stdin to signal EOF to ffmpeg
+        let write_result = self.writer.map(FrameWriter::close).unwrap_or(Ok(()));
+        if !self.has_process {
+            return write_result;
+        }
+        // If the child was taken, the export has been cancelled and killed already
+        let child = self.child.lock().map_err(|_| "Failed to lock encoder process")?.take();
+        let mut child = child.ok_or("Export was cancelled")?;
+        let status = child.wait().map_err(|e| format!("Failed to wait on ffmpeg: {}", e))?;
         if status.success() {
-            Ok(())
+            write_result
         } else {
             Err(format!("FFmpeg exited with error: {}", status))
         }
     }
 }
 
+/// How captions appear over the fade window; slides move the layer into place while it fades in
+/// and back out while it fades out.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnterAnimation {
+    #[default]
+    Fade,
+    SlideUp,
+    SlideLeft,
+}
+
+/// Distance travelled by a sliding caption, as a fraction of the frame.
+pub const SLIDE_DISTANCE: f32 = 0.08;
+
+impl EnterAnimation {
+    /// Layer offset (frame fractions, y down) for a caption whose fade alpha is `alpha`.
+    pub fn offset(self, alpha: f32) -> [f32; 2] {
+        let travel = (1.0 - alpha.clamp(0.0, 1.0)) * SLIDE_DISTANCE;
+        match self {
+            EnterAnimation::Fade => [0.0, 0.0],
+            EnterAnimation::SlideUp => [0.0, travel],
+            EnterAnimation::SlideLeft => [travel, 0.0],
+        }
+    }
+}
+
+/// How captions are uncovered over the fade window. Wipes keep the caption at full opacity and
+/// reveal it progressively instead of fading it uniformly, then cover it again as it fades out.
+/// A custom mask (`Renderer::set_reveal_mask`) takes precedence.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reveal {
+    #[default]
+    Fade,
+    /// Bottom to top.
+    WipeUp,
+    /// Top to bottom.
+    WipeDown,
+    /// Right to left, in reading order for Arabic.
+    WipeLeft,
+    /// Left to right.
+    WipeRight,
+}
+
+/// Width of the soft edge of a wipe or mask reveal, as a fraction of the reveal.
+pub const REVEAL_SOFTNESS: f32 = 0.15;
+
+// `Layer.mask_mode` for a mask texture (see overlay.wgsl)
+const MASK_MODE_TEXTURE: u32 = 5;
+
+impl Reveal {
+    fn mask_mode(self) -> u32 {
+        match self {
+            Reveal::Fade => 0,
+            Reveal::WipeUp => 1,
+            Reveal::WipeDown => 2,
+            Reveal::WipeLeft => 3,
+            Reveal::WipeRight => 4,
+        }
+    }
+}
+
+/// How an `ImageRenderer` layer is combined with what is already in the target.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// Regular alpha compositing.
+    #[default]
+    Normal,
+    Screen,
+    Multiply,
+    Add,
+}
+
+impl BlendMode {
+    fn fragment_entry(self, premultiplied_source: bool) -> &'static str {
+        match self {
+            _ if premultiplied_source => "fs_premultiplied_source",
+            BlendMode::Normal => "fs_main",
+            _ => "fs_premultiplied",
+        }
+    }
+
+    /// Blend equations on premultiplied source colour `s` (alpha `a`) and target `d`:
+    /// screen `s + d(1 - s)`, multiply `d(s + 1 - a)`, add `s + d`.
+    fn blend_state(self, premultiplied_source: bool) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor, BlendOperation};
+        let color = |src_factor, dst_factor| BlendComponent { src_factor, dst_factor, operation: BlendOperation::Add };
+        let alpha = BlendComponent::OVER;
+        match self {
+            BlendMode::Normal if premultiplied_source => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Normal => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Screen => wgpu::BlendState { color: color(BlendFactor::One, BlendFactor::OneMinusSrc), alpha },
+            BlendMode::Multiply => wgpu::BlendState { color: color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha), alpha },
+            BlendMode::Add => wgpu::BlendState { color: color(BlendFactor::One, BlendFactor::One), alpha },
+        }
+    }
+}
+
+/// Texture filtering of an `ImageRenderer` layer.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplerMode {
+    /// Bilinear filtering, smooth when the layer is scaled or shifted.
+    #[default]
+    Linear,
+    /// Nearest texel: pixel-exact for captions pre-rendered at the frame size.
+    Nearest,
+}
+
+impl SamplerMode {
+    fn filter(self) -> wgpu::FilterMode {
+        match self {
+            SamplerMode::Linear => wgpu::FilterMode::Linear,
+            SamplerMode::Nearest => wgpu::FilterMode::Nearest,
+        }
+    }
+}
+
+fn create_sampler(device: &wgpu::Device, mode: SamplerMode) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: mode.filter(),
+        min_filter: mode.filter(),
+        ..Default::default()
+    })
+}
+
 pub struct ImageRenderer {
     pipeline: wgpu::RenderPipeline,
     bind_group_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
     alpha_buffer: wgpu::Buffer,
+    alpha_layout: wgpu::BindGroupLayout,
     alpha_bind_group: wgpu::BindGroup,
+    // Bound as the reveal mask until `set_mask_texture` provides one
+    mask_placeholder: wgpu::TextureView,
 }
 
 impl ImageRenderer {
     pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        Self::with_blend(device, format, BlendMode::Normal)
+    }
+
+    pub fn with_blend(device: &wgpu::Device, format: wgpu::TextureFormat, blend: BlendMode) -> Self {
+        Self::build(device, format, blend, false)
+    }
+
+    /// Renderer for textures uploaded premultiplied (see `premultiply_srgb`), e.g. captions.
+    pub fn premultiplied(device: &wgpu::Device, format: wgpu::TextureFormat, blend: BlendMode) -> Self {
+        Self::build(device, format, blend, true)
+    }
+
+    fn build(device: &wgpu::Device, format: wgpu::TextureFormat, blend: BlendMode, premultiplied_source: bool) -> Self {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Overlay Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("overlay.wgsl").into()),
@@ -327,16 +974,28 @@ impl ImageRenderer {
 
         let alpha_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Alpha Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
         });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -356,10 +1015,10 @@ impl ImageRenderer {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
-                entry_point: Some("fs_main"),
+                entry_point: Some(blend.fragment_entry(premultiplied_source)),
                 targets: &[Some(wgpu::ColorTargetState {
                     format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    blend: Some(blend.blend_state(premultiplied_source)),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: Default::default(),
@@ -374,43 +1033,95 @@ impl ImageRenderer {
             cache: None,
         });
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+        let sampler = create_sampler(device, SamplerMode::default());
 
         let alpha_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Alpha Buffer"),
-            contents: bytemuck::cast_slice(&[1.0f32]),
+            // alpha, reveal, offset.xy, mask mode (u32, 0 = none), softness
+            // (vec2 is 8-byte aligned in the uniform layout)
+            contents: bytemuck::cast_slice(&[1.0f32, 0.0, 0.0, 0.0, 0.0, 0.0]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let alpha_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Alpha Bind Group"),
-            layout: &alpha_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: alpha_buffer.as_entire_binding(),
-            }],
-        });
+        let mask_placeholder = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Mask Placeholder"),
+                size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let alpha_bind_group = Self::alpha_bind_group(device, &alpha_layout, &alpha_buffer, &mask_placeholder);
 
         Self {
             pipeline,
             bind_group_layout,
             sampler,
             alpha_buffer,
+            alpha_layout,
             alpha_bind_group,
+            mask_placeholder,
         }
     }
 
+    fn alpha_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer, mask: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Alpha Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(mask),
+                },
+            ],
+        })
+    }
+
     pub fn set_alpha(&self, queue: &wgpu::Queue, alpha: f32) {
         queue.write_buffer(&self.alpha_buffer, 0, bytemuck::cast_slice(&[alpha]));
     }
 
-    pub fn render(&self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, sub_view: &wgpu::TextureView) {
+    /// Shifts the layer by `offset` frame fractions (x right, y down).
+    pub fn set_offset(&self, queue: &wgpu::Queue, offset: [f32; 2]) {
+        queue.write_buffer(&self.alpha_buffer, 8, bytemuck::cast_slice(&offset));
+    }
+
+    /// Reveal progress of a wipe or mask (0 = hidden, 1 = fully shown); unused by the uniform fade.
+    pub fn set_reveal(&self, queue: &wgpu::Queue, progress: f32) {
+        queue.write_buffer(&self.alpha_buffer, 4, bytemuck::cast_slice(&[progress]));
+    }
+
+    /// Selects how `set_reveal` uncovers the layer (0 = not at all, see `Layer.mask_mode`).
+    fn set_mask_mode(&self, queue: &wgpu::Queue, mode: u32, softness: f32) {
+        queue.write_buffer(&self.alpha_buffer, 16, bytemuck::cast_slice(&[mode]));
+        queue.write_buffer(&self.alpha_buffer, 20, bytemuck::cast_slice(&[softness]));
+    }
+
+    /// Binds `mask` (red channel) as the reveal mask texture, or the placeholder with `None`.
+    fn set_mask_texture(&mut self, device: &wgpu::Device, mask: Option<&wgpu::TextureView>) {
+        let mask = mask.unwrap_or(&self.mask_placeholder);
+        self.alpha_bind_group = Self::alpha_bind_group(device, &self.alpha_layout, &self.alpha_buffer, mask);
+    }
+
+    pub fn render(&self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, sub_view: &wgpu::TextureView) -> Result<(), String> {
+        self.render_clipped(device, queue, view, sub_view, None)
+    }
+
+    /// Switches texture filtering (linear by default); the layout binds a filtering sampler either way.
+    pub fn set_sampler_mode(&mut self, device: &wgpu::Device, mode: SamplerMode) {
+        self.sampler = create_sampler(device, mode);
+    }
+
+    /// Same as `render`, restricted to the `(x, y, width, height)` pixel rectangle of the target.
+    pub fn render_clipped(&self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, sub_view: &wgpu::TextureView, rect: Option<(u32, u32, u32, u32)>) -> Result<(), String> {
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Overlay Bind Group"),
             layout: &self.bind_group_layout,
@@ -445,39 +1156,238 @@ impl ImageRenderer {
                 multiview_mask: None,
             });
             render_pass.set_pipeline(&self.pipeline);
+            if let Some((x, y, w, h)) = rect {
+                render_pass.set_scissor_rect(x, y, w, h);
+            }
             render_pass.set_bind_group(0, &bind_group, &[]);
             render_pass.set_bind_group(1, &self.alpha_bind_group, &[]);
             render_pass.draw(0..4, 0..1);
         }
-        device.poll(wgpu::PollType::Wait { submission_index: Some(queue.submit(Some(encoder.finish()))), timeout: None }).unwrap();
+        device.poll(wgpu::PollType::Wait { submission_index: Some(queue.submit(Some(encoder.finish()))), timeout: None })
+            .map_err(|e| format!("{}: overlay pass: {}", GPU_ERROR, e))?;
+        Ok(())
     }
 }
 
 
 
-pub struct TextRenderer {
-    pub font_system: FontSystem,
-    pub swash_cache: SwashCache,
-    pub cache: Cache,
-    pub viewport: glyphon::Viewport,
-    pub atlas: TextAtlas,
-    pub text_renderer: glyphon::TextRenderer,
-    pub buffer: Buffer,
+/// Word timing within a karaoke cue: the word ending at byte `end` of the text is spoken from `start_ms` on.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WordTiming {
+    pub end: usize,
+    pub start_ms: u32,
 }
 
-impl TextRenderer {
-    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
-        let mut font_system = FontSystem::new();
-        let swash_cache = SwashCache::new();
-        let cache = Cache::new(device);
-        let mut atlas = TextAtlas::new(device, queue, &cache, format);
-        let text_renderer = glyphon::TextRenderer::new(&mut atlas, device, wgpu::MultisampleState::default(), None);
-        let mut buffer = Buffer::new(&mut font_system, Metrics::new(32.0, 42.0));
+/// A caption whose words switch from the upcoming to the spoken color as they are recited.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KaraokeCue {
+    pub text: String,
+    pub words: Vec<WordTiming>,
+}
 
-        buffer.set_size(&mut font_system, Some(width as f32), Some(height as f32));
-        buffer.shape_until_scroll(&mut font_system, false);
+impl KaraokeCue {
+    /// Byte offset in `text` up to which words have been spoken at `time_ms`.
+    pub fn spoken_until(&self, time_ms: u32) -> usize {
+        let mut end = self
+            .words
+            .iter()
+            .filter(|w| w.start_ms <= time_ms)
+            .map(|w| w.end.min(self.text.len()))
+            .max()
+            .unwrap_or(0);
+        while !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+        end
+    }
+}
 
-        let viewport = glyphon::Viewport::new(device, &cache);
+/// Appearance of text drawn by `TextRenderer`.
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    pub color: Color,
+    /// Box drawn behind the text (alpha is honoured), if any.
+    pub bg_color: Option<Color>,
+    /// Space between the text bounds and the edge of the background box, in pixels.
+    pub padding: f32,
+    /// Horizontal alignment of each (wrapped) line; `None` follows the script direction.
+    pub align: Option<Align>,
+    /// Centers the text block vertically instead of pinning it below the top margin.
+    pub center_vertically: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self { color: Color::rgb(255, 255, 255), bg_color: None, padding: 8.0, align: None, center_vertically: false }
+    }
+}
+
+/// Offset of the text area from the top-left corner of the target.
+const TEXT_MARGIN: f32 = 10.0;
+
+/// Minimum line height as a multiple of the font size. Vowelled Arabic stacks tashkeel
+/// (shadda + fatha, kasra) well beyond the Latin ascent/descent: at 1.3× the marks spill
+/// above the line top and get clipped by the bounds or overlap the previous line.
+const TASHKEEL_LINE_HEIGHT_FACTOR: f32 = 1.6;
+
+/// Text metrics with enough line height for Arabic diacritics above and below the baseline.
+fn line_metrics(font_size: f32, line_height: f32) -> Metrics {
+    Metrics::new(font_size, line_height.max(font_size * TASHKEEL_LINE_HEIGHT_FACTOR))
+}
+
+/// Color emoji fonts tried when the system font database has none (e.g. a minimal Linux install).
+const EMOJI_FONT_CANDIDATES: [&str; 4] = [
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "C:\\Windows\\Fonts\\seguiemj.ttf",
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+];
+
+fn has_emoji_font(font_system: &FontSystem) -> bool {
+    font_system
+        .db()
+        .faces()
+        .any(|face| face.families.iter().any(|(name, _)| name.contains("Emoji")))
+}
+
+// cosmic-text wants `'static` fallback names and lists: each distinct one is allocated once per
+// process and shared, so rebuilding the chain on every export does not grow memory.
+static INTERNED_FAMILIES: std::sync::LazyLock<Mutex<std::collections::HashSet<&'static str>>> =
+    std::sync::LazyLock::new(|| Mutex::new(std::collections::HashSet::new()));
+static INTERNED_LISTS: std::sync::LazyLock<Mutex<std::collections::HashSet<&'static [&'static str]>>> =
+    std::sync::LazyLock::new(|| Mutex::new(std::collections::HashSet::new()));
+
+fn intern_family(name: &str) -> &'static str {
+    let mut names = INTERNED_FAMILIES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(&interned) = names.get(name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.into());
+    names.insert(interned);
+    interned
+}
+
+fn intern_fallback_list(list: Vec<&'static str>) -> &'static [&'static str] {
+    let mut lists = INTERNED_LISTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(&interned) = lists.get(list.as_slice()) {
+        return interned;
+    }
+    let interned: &'static [&'static str] = Box::leak(list.into_boxed_slice());
+    lists.insert(interned);
+    interned
+}
+
+/// Ordered font families tried per glyph before the platform fallback lists, whatever the
+/// script, so mixed Arabic/Latin/Urdu captions pick the project's fonts instead of tofu.
+struct FontFallbackChain {
+    families: Vec<&'static str>,
+    platform: PlatformFallback,
+    // Chain followed by the platform list, built once per script
+    per_script: Mutex<std::collections::HashMap<Script, &'static [&'static str]>>,
+}
+
+impl FontFallbackChain {
+    fn new(families: Vec<String>) -> Self {
+        Self {
+            families: families.iter().map(|f| intern_family(f)).collect(),
+            platform: PlatformFallback,
+            per_script: Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+}
+
+impl Fallback for FontFallbackChain {
+    fn common_fallback(&self) -> &[&'static str] {
+        self.platform.common_fallback()
+    }
+
+    fn forbidden_fallback(&self) -> &[&'static str] {
+        self.platform.forbidden_fallback()
+    }
+
+    fn script_fallback(&self, script: Script, locale: &str) -> &[&'static str] {
+        let mut per_script = self.per_script.lock().unwrap_or_else(|e| e.into_inner());
+        per_script.entry(script).or_insert_with(|| {
+            let mut list = self.families.clone();
+            list.extend(self.platform.script_fallback(script, locale).iter().filter(|f| !self.families.contains(f)));
+            intern_fallback_list(list)
+        })
+    }
+}
+
+/// Left edge of the text area so that a layout `layout_width` wide sits at `align` in the frame.
+fn text_area_left(align: Option<Align>, frame_width: f32, layout_width: f32) -> f32 {
+    match align {
+        Some(Align::Center) | Some(Align::Justified) => ((frame_width - layout_width) / 2.0).max(0.0),
+        Some(Align::Right) | Some(Align::End) => (frame_width - TEXT_MARGIN - layout_width).max(0.0),
+        _ => TEXT_MARGIN,
+    }
+}
+
+/// Horizontal `(start, end)` of the glyphs on each laid-out line, relative to the buffer.
+fn line_extents(buffer: &Buffer) -> Vec<(f32, f32, f32)> {
+    buffer
+        .layout_runs()
+        .filter_map(|run| {
+            let start = run.glyphs.iter().map(|g| g.x).fold(f32::INFINITY, f32::min);
+            let end = run.glyphs.iter().map(|g| g.x + g.w).fold(f32::NEG_INFINITY, f32::max);
+            (start < end).then_some((start, end, run.line_top + run.line_height))
+        })
+        .collect()
+}
+
+pub struct TextRenderer {
+    pub font_system: FontSystem,
+    pub swash_cache: SwashCache,
+    pub cache: Cache,
+    pub viewport: glyphon::Viewport,
+    pub atlas: TextAtlas,
+    pub text_renderer: glyphon::TextRenderer,
+    pub buffer: Buffer,
+    pub style: TextStyle,
+    // 1x1 texture holding the background box color, stretched over the box by a scissored quad
+    box_renderer: ImageRenderer,
+    box_texture: wgpu::Texture,
+    box_view: wgpu::TextureView,
+}
+
+impl TextRenderer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, width: u32, height: u32) -> Self {
+        let mut font_system = FontSystem::new();
+        // Color glyphs (COLR/CBDT/sbix) are rasterized by swash into glyphon's color atlas;
+        // they only need a font that has them, otherwise emoji fall back to tofu
+        if !has_emoji_font(&font_system) {
+            if let Some(path) = EMOJI_FONT_CANDIDATES.iter().find(|p| std::path::Path::new(p).exists()) {
+                log_out!("[TextRenderer] No emoji font registered, loading {}", path);
+                let _ = font_system.db_mut().load_font_file(path);
+            }
+        }
+        let swash_cache = SwashCache::new();
+        let cache = Cache::new(device);
+        let mut atlas = TextAtlas::new(device, queue, &cache, format);
+        let text_renderer = glyphon::TextRenderer::new(&mut atlas, device, wgpu::MultisampleState::default(), None);
+        let mut buffer = Buffer::new(&mut font_system, line_metrics(32.0, 42.0));
+
+        // Lines wrap inside the frame minus a margin on both sides, so aligned lines are symmetric
+        buffer.set_size(&mut font_system, Some((width as f32 - 2.0 * TEXT_MARGIN).max(1.0)), Some(height as f32));
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let viewport = glyphon::Viewport::new(device, &cache);
+
+        let box_renderer = ImageRenderer::new(device, format);
+        let box_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text Box Texture"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let box_view = box_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         Self {
             font_system,
@@ -487,13 +1397,157 @@ impl TextRenderer {
             atlas,
             text_renderer,
             buffer,
+            style: TextStyle::default(),
+            box_renderer,
+            box_texture,
+            box_view,
         }
     }
 
+    pub fn set_style(&mut self, style: TextStyle) {
+        self.style = style;
+    }
+
+    /// Sets the ordered fallback chain used for glyphs missing from the primary family.
+    /// Each entry is a font file (loaded, then referenced by its family) or an installed family name,
+    /// e.g. a color emoji font for symbols missing from the caption font.
+    pub fn set_font_fallback(&mut self, fonts: Vec<String>) -> Result<(), String> {
+        let mut families: Vec<String> = Vec::new();
+        for font in fonts {
+            let names: Vec<String> = if std::path::Path::new(&font).is_file() {
+                let data = std::fs::read(&font).map_err(|e| format!("Failed to read font {}: {}", font, e))?;
+                let db = self.font_system.db_mut();
+                let ids = db.load_font_source(glyphon::fontdb::Source::Binary(Arc::new(data)));
+                if ids.is_empty() {
+                    return Err(format!("No font face found in {}", font));
+                }
+                ids.iter()
+                    .filter_map(|id| db.face(*id).and_then(|face| face.families.first()).map(|(name, _)| name.clone()))
+                    .collect()
+            } else if self.font_system.db().faces().any(|face| face.families.iter().any(|(name, _)| *name == font)) {
+                vec![font]
+            } else {
+                log_out!("[TextRenderer] Fallback font not found, skipped: {}", font);
+                Vec::new()
+            };
+            for name in names {
+                if !families.contains(&name) {
+                    families.push(name);
+                }
+            }
+        }
+        log_out!("[TextRenderer] Font fallback chain: {}", families.join(" > "));
+
+        // The fallback lists are fixed at construction: rebuild the system around the same database
+        let placeholder = FontSystem::new_with_locale_and_db(String::new(), glyphon::fontdb::Database::new());
+        let (locale, db) = std::mem::replace(&mut self.font_system, placeholder).into_locale_and_db();
+        self.font_system = FontSystem::new_with_locale_and_db_and_fallback(locale, db, FontFallbackChain::new(families));
+        self.buffer.shape_until_scroll(&mut self.font_system, false);
+        Ok(())
+    }
+
+    /// Changes font size and line height, then reshapes so the layout (and the
+    /// background box measured from it) reflects the new metrics immediately.
+    pub fn set_metrics(&mut self, font_size: f32, line_height: f32) -> Result<(), String> {
+        if !(font_size > 0.0 && line_height > 0.0) {
+            return Err(format!("Invalid text metrics: font size {}, line height {}", font_size, line_height));
+        }
+        self.buffer.set_metrics(&mut self.font_system, line_metrics(font_size, line_height));
+        self.buffer.shape_until_scroll(&mut self.font_system, false);
+        Ok(())
+    }
+
+    /// Width the lines are aligned within: the wrap width, or the widest line when unbounded.
+    fn layout_width(&self) -> f32 {
+        self.buffer.size().0.unwrap_or_else(|| {
+            line_extents(&self.buffer).iter().map(|(_, end, _)| *end).fold(0.0, f32::max)
+        })
+    }
+
+    fn text_left(&self, width: u32) -> f32 {
+        text_area_left(self.style.align, width as f32, self.layout_width())
+    }
+
+    fn text_top(&self, height: u32) -> f32 {
+        if !self.style.center_vertically {
+            return TEXT_MARGIN;
+        }
+        let text_height = line_extents(&self.buffer).iter().map(|e| e.2).fold(0.0, f32::max);
+        ((height as f32 - text_height) / 2.0).max(TEXT_MARGIN)
+    }
+
+    /// Pixel rectangle `(x, y, w, h)` covered by the shaped text plus padding, clamped to the target.
+    fn background_rect(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let extents = line_extents(&self.buffer);
+        let start = extents.iter().map(|e| e.0).fold(f32::INFINITY, f32::min);
+        let end = extents.iter().map(|e| e.1).fold(f32::NEG_INFINITY, f32::max);
+        let bottom = extents.iter().map(|e| e.2).fold(0.0, f32::max);
+        if extents.is_empty() || bottom <= 0.0 {
+            return None;
+        }
+        let left = self.text_left(width);
+        let top = self.text_top(height);
+        let pad = self.style.padding.max(0.0);
+        let x0 = (left + start - pad).max(0.0) as u32;
+        let y0 = (top - pad).max(0.0) as u32;
+        let x1 = ((left + end + pad).ceil() as u32).min(width);
+        let y1 = ((top + bottom + pad).ceil() as u32).min(height);
+        (x1 > x0 && y1 > y0).then(|| (x0, y0, x1 - x0, y1 - y0))
+    }
+
+    fn draw_background(&self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, width: u32, height: u32) -> Result<(), String> {
+        let (Some(bg), Some(rect)) = (self.style.bg_color, self.background_rect(width, height)) else {
+            return Ok(());
+        };
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.box_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &bg.as_rgba(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: Some(1),
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        self.box_renderer.set_alpha(queue, 1.0);
+        self.box_renderer.render_clipped(device, queue, view, &self.box_view, Some(rect))
+    }
+
     pub fn render(&mut self, text: &str, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, width: u32, height: u32) -> Result<(), String> {
-        self.buffer.set_text(&mut self.font_system, text, &Attrs::new().family(Family::SansSerif), Shaping::Advanced, None);
+        self.buffer.set_text(&mut self.font_system, text, &Attrs::new().family(Family::SansSerif), Shaping::Advanced, self.style.align);
+        self.buffer.shape_until_scroll(&mut self.font_system, false);
+        self.draw(device, queue, view, width, height)
+    }
+
+    /// Renders `cue` with the words spoken by `time_ms` in `spoken` and the rest in `upcoming`.
+    /// Call once per frame with the frame's time to advance the highlight.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_karaoke(&mut self, cue: &KaraokeCue, time_ms: u32, spoken: Color, upcoming: Color, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, width: u32, height: u32) -> Result<(), String> {
+        let split = cue.spoken_until(time_ms);
+        let attrs = Attrs::new().family(Family::SansSerif);
+        let spans = [
+            (&cue.text[..split], attrs.clone().color(spoken)),
+            (&cue.text[split..], attrs.clone().color(upcoming)),
+        ];
+        self.buffer.set_rich_text(
+            &mut self.font_system,
+            spans.into_iter().filter(|(s, _)| !s.is_empty()),
+            &attrs,
+            Shaping::Advanced,
+            self.style.align,
+        );
         self.buffer.shape_until_scroll(&mut self.font_system, false);
+        self.draw(device, queue, view, width, height)
+    }
 
+    fn prepare_text(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) -> Result<(), glyphon::PrepareError> {
+        let left = self.text_left(width);
+        let top = self.text_top(height);
         self.text_renderer.prepare(
             device,
             queue,
@@ -502,8 +1556,8 @@ impl TextRenderer {
             &self.viewport,
             [TextArea {
                 buffer: &self.buffer,
-                left: 10.0,
-                top: 10.0,
+                left,
+                top,
                 scale: 1.0,
                 bounds: TextBounds {
                     left: 0,
@@ -511,11 +1565,29 @@ impl TextRenderer {
                     right: width as i32,
                     bottom: height as i32,
                 },
-                default_color: Color::rgb(255, 255, 255),
+                default_color: self.style.color,
                 custom_glyphs: &[],
             }],
             &mut self.swash_cache,
-        ).map_err(|e| format!("Prepare error: {:?}", e))?;
+        )
+    }
+
+    fn draw(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView, width: u32, height: u32) -> Result<(), String> {
+        self.draw_background(device, queue, view, width, height)?;
+        // Glyph positions are mapped to clip space through the viewport: an unset one draws nothing
+        self.viewport.update(queue, Resolution { width, height });
+
+        let prepared = match self.prepare_text(device, queue, width, height) {
+            // Glyphs stay pinned until trimmed: release the previous frames' and retry once
+            Err(glyphon::PrepareError::AtlasFull) => {
+                log_out!("[TextRenderer] Glyph atlas full, trimming and retrying");
+                self.atlas.trim();
+                self.prepare_text(device, queue, width, height)
+            }
+            other => other,
+        };
+        // Still full: this frame alone needs more glyphs than the largest atlas holds
+        prepared.map_err(|e| format!("Prepare error: {:?} (text too large for one frame)", e))?;
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         {
@@ -540,10 +1612,36 @@ impl TextRenderer {
         }
         
         queue.submit(Some(encoder.finish()));
+        // Unpin this frame's glyphs so the atlas can evict them when later verses need room
+        self.atlas.trim();
         Ok(())
     }
 }
 
+/// Row stride of a `width`-pixel RGBA8 readback, padded to wgpu's copy alignment.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * 4).div_ceil(align) * align
+}
+
+fn create_readback_buffer(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        size: (padded_bytes_per_row(width) * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        label: Some("Output Buffer"),
+        mapped_at_creation: false,
+    })
+}
+
+/// Texture the composite is scaled into before readback when the output size differs from the render size.
+struct OutputTarget {
+    width: u32,
+    height: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    renderer: ImageRenderer,
+}
+
 pub struct Renderer {
     ctx: WgpuContext,
     pub width: u32,
@@ -555,14 +1653,55 @@ pub struct Renderer {
     pub tint_renderer: ImageRenderer, // Renamed/Added for clarity
     pub tint_texture: wgpu::Texture,
     pub tint_view: wgpu::TextureView,
+    // Opaque black drawn over the background to fade it independently of the captions
+    fade_renderer: ImageRenderer,
+    fade_view: wgpu::TextureView,
+    background_alpha: f32,
     pub sub_renderer: ImageRenderer,  // Renamed from image_renderer
     pub sub_texture: wgpu::Texture,
     pub sub_view: wgpu::TextureView,
+    // Second caption layer (e.g. translation), composited above the first with its own alpha
+    pub trans_renderer: ImageRenderer,
+    pub trans_texture: wgpu::Texture,
+    pub trans_view: wgpu::TextureView,
+    has_translation: bool,
+    // Motion overlay (video frames with alpha), composited above both caption layers
+    motion_renderer: ImageRenderer,
+    motion_texture: wgpu::Texture,
+    motion_view: wgpu::TextureView,
+    has_motion: bool,
+    // False during gaps between captions: the subtitle pass is skipped so the background stays clean
+    has_subtitle: bool,
+    // Sustained opacity of both caption layers; the per-frame fade alpha is multiplied on top
+    caption_opacity: f32,
+    enter_animation: EnterAnimation,
+    reveal: Reveal,
+    // Custom reveal mask (R8), kept alive while bound to the caption layers
+    reveal_mask: Option<(wgpu::Texture, wgpu::TextureView)>,
+    output_target: Option<OutputTarget>,
+    caption_sampler: SamplerMode,
 }
 
 impl Renderer {
-    pub async fn new(width: u32, height: u32) -> Result<Self, String> {
-        let ctx = WgpuContext::new().await?;
+    pub async fn new(width: u32, height: u32, backend: Option<GpuBackend>) -> Result<Self, String> {
+        // The encoder outputs yuv420p, which needs even dimensions
+        let (even_w, even_h) = ((width + 1) & !1, (height + 1) & !1);
+        if (even_w, even_h) != (width, height) {
+            log_out!("[Renderer] Odd size {}x{} rounded up to {}x{} for yuv420p", width, height, even_w, even_h);
+        }
+        let (width, height) = (even_w, even_h);
+
+        let ctx = WgpuContext::new(backend).await?;
+        // Beyond the texture limit wgpu panics on creation instead of reporting an error
+        let max_dimension = ctx.device.limits().max_texture_dimension_2d;
+        if width.max(height) > max_dimension {
+            return Err(format!(
+                "{}: {}x{} exceeds the GPU texture limit of {} px",
+                RESOLUTION_TOO_LARGE_ERROR, width, height, max_dimension
+            ));
+        }
+        // Allocation failures (e.g. 4K targets on low-VRAM GPUs) are returned instead of panicking
+        let oom_scope = ctx.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
         
         let texture_size = wgpu::Extent3d {
             width,
@@ -599,6 +1738,30 @@ impl Renderer {
         });
         let sub_view = sub_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let trans_texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Translation Texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.texture_format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let trans_view = trans_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let motion_texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Motion Overlay Texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.texture_format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let motion_view = motion_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
         let tint_texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Tint Texture"),
             size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
@@ -611,19 +1774,46 @@ impl Renderer {
         });
         let tint_view = tint_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
+        let fade_texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Background Fade Texture"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.texture_format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        ctx.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &fade_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &[0, 0, 0, 255],
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: Some(1),
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+        let fade_view = fade_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let fade_renderer = ImageRenderer::new(&ctx.device, ctx.texture_format);
+
         let text_renderer = TextRenderer::new(&ctx.device, &ctx.queue, ctx.texture_format, width, height);
         let tint_renderer = ImageRenderer::new(&ctx.device, ctx.texture_format);
-        let sub_renderer = ImageRenderer::new(&ctx.device, ctx.texture_format);
+        let sub_renderer = ImageRenderer::premultiplied(&ctx.device, ctx.texture_format, BlendMode::Normal);
+        let trans_renderer = ImageRenderer::premultiplied(&ctx.device, ctx.texture_format, BlendMode::Normal);
+        let motion_renderer = ImageRenderer::premultiplied(&ctx.device, ctx.texture_format, BlendMode::Normal);
 
         // Buffer for reading back data
-        let output_buffer_size = (width * height * 4) as wgpu::BufferAddress;
-        let output_buffer_desc = wgpu::BufferDescriptor {
-            size: output_buffer_size,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            label: Some("Output Buffer"),
-            mapped_at_creation: false,
-        };
-        let output_buffer = ctx.device.create_buffer(&output_buffer_desc);
+        let output_buffer = create_readback_buffer(&ctx.device, width, height);
+
+        if let Some(e) = oom_scope.pop().await {
+            return Err(format!("{}: {}x{} render targets: {}", GPU_OUT_OF_MEMORY_ERROR, width, height, e));
+        }
 
         Ok(Self {
             ctx,
@@ -636,11 +1826,70 @@ impl Renderer {
             tint_renderer,
             tint_texture,
             tint_view,
+            fade_renderer,
+            fade_view,
+            background_alpha: 1.0,
             sub_renderer,
             sub_texture,
             sub_view,
+            trans_renderer,
+            trans_texture,
+            trans_view,
+            has_translation: false,
+            motion_renderer,
+            motion_texture,
+            motion_view,
+            has_motion: false,
+            has_subtitle: false,
+            caption_opacity: 1.0,
+            enter_animation: EnterAnimation::Fade,
+            reveal: Reveal::Fade,
+            reveal_mask: None,
+            output_target: None,
+            caption_sampler: SamplerMode::Linear,
         })
     }
+
+    /// Size of the frames returned by `read_frame`: the render size unless `set_output_size` changed it.
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_target.as_ref().map_or((self.width, self.height), |t| (t.width, t.height))
+    }
+
+    /// Composites at the render size but reads frames back at `width`x`height` (rounded up to even),
+    /// scaled on the GPU. Smaller outputs also shrink the readback and what the encoder receives.
+    pub async fn set_output_size(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid output size {}x{}", width, height));
+        }
+        let (width, height) = ((width + 1) & !1, (height + 1) & !1);
+        if (width, height) == self.output_size() {
+            return Ok(());
+        }
+        let oom_scope = self.ctx.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        if (width, height) == (self.width, self.height) {
+            self.output_target = None;
+        } else {
+            log_out!("[Renderer] Rendering at {}x{}, reading back at {}x{}", self.width, self.height, width, height);
+            let texture = self.ctx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Output Texture"),
+                size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.ctx.texture_format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let renderer = ImageRenderer::new(&self.ctx.device, self.ctx.texture_format);
+            self.output_target = Some(OutputTarget { width, height, texture, view, renderer });
+        }
+        self.output_buffer = create_readback_buffer(&self.ctx.device, width, height);
+        if let Some(e) = oom_scope.pop().await {
+            return Err(format!("{}: {}x{} output target: {}", GPU_OUT_OF_MEMORY_ERROR, width, height, e));
+        }
+        Ok(())
+    }
     
     pub fn update_tint(&self, color_hex: &str) {
          let r = u8::from_str_radix(&color_hex[1..3], 16).unwrap_or(0);
@@ -691,18 +1940,68 @@ impl Renderer {
         );
     }
 
-    pub fn render_image(&mut self, alpha: f32, overlay_enable: bool, overlay_opacity: f32) {
+    /// Fills the background with a vertical gradient from `top` to `bottom` (equal colors give a solid card).
+    pub fn fill_gradient(&self, top: [u8; 3], bottom: [u8; 3]) {
+        let mut data = Vec::with_capacity((self.width * self.height * 4) as usize);
+        let last_row = self.height.saturating_sub(1).max(1) as f32;
+        for y in 0..self.height {
+            let t = y as f32 / last_row;
+            let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+            let row = [mix(top[0], bottom[0]), mix(top[1], bottom[1]), mix(top[2], bottom[2]), 255];
+            for _ in 0..self.width {
+                data.extend_from_slice(&row);
+            }
+        }
+        self.upload_background(&data);
+    }
+
+    /// Background opacity for the next frames (1 = unchanged, 0 = black); captions are not affected.
+    pub fn set_background_alpha(&mut self, alpha: f32) {
+        self.background_alpha = alpha.clamp(0.0, 1.0);
+    }
+
+    pub fn render_image(&mut self, alpha: f32, translation_alpha: f32, overlay_enable: bool, overlay_opacity: f32) -> Result<(), String> {
         // 1. (Optional) Global Tint Layer
         if overlay_enable && overlay_opacity > 0.001 {
-            self.tint_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.tint_view);
+            self.tint_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.tint_view)?;
+        }
+
+        // 1b. (Optional) Background fade to black, below the captions
+        if self.background_alpha < 1.0 {
+            self.fade_renderer.set_alpha(&self.ctx.queue, 1.0 - self.background_alpha);
+            self.fade_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.fade_view)?;
         }
 
-        // 2. Subtitle Layer
-        self.sub_renderer.set_alpha(&self.ctx.queue, alpha);
-        self.sub_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.sub_view);
+        // Wipes and masks reveal the captions at full opacity instead of fading them
+        let masked = self.reveal_mode() != 0;
+        let fade_alpha = |a: f32| if masked { 1.0 } else { a };
+
+        // 2. (Optional) Subtitle Layer
+        if self.has_subtitle {
+            self.sub_renderer.set_alpha(&self.ctx.queue, fade_alpha(alpha) * self.caption_opacity);
+            self.sub_renderer.set_reveal(&self.ctx.queue, alpha);
+            self.sub_renderer.set_offset(&self.ctx.queue, self.enter_animation.offset(alpha));
+            self.sub_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.sub_view)?;
+        }
+
+        // 3. (Optional) Translation Layer
+        if self.has_translation {
+            self.trans_renderer.set_alpha(&self.ctx.queue, fade_alpha(translation_alpha) * self.caption_opacity);
+            self.trans_renderer.set_reveal(&self.ctx.queue, translation_alpha);
+            self.trans_renderer.set_offset(&self.ctx.queue, self.enter_animation.offset(translation_alpha));
+            self.trans_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.trans_view)?;
+        }
+
+        // 4. (Optional) Motion overlay, on top of everything
+        if self.has_motion {
+            self.motion_renderer.render(&self.ctx.device, &self.ctx.queue, &self.bg_view, &self.motion_view)?;
+        }
+        Ok(())
     }
 
-    pub fn clear_subtitle(&self) {
+    /// Empties the subtitle layer (gap between captions); it stays hidden until the next upload.
+    pub fn clear_subtitle(&mut self) {
+        self.has_subtitle = false;
         let mut encoder = self.ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Clear Subtitle") });
         {
             let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -725,10 +2024,35 @@ impl Renderer {
         self.ctx.queue.submit(Some(encoder.finish()));
     }
 
-    pub fn upload_subtitle(&self, data: &[u8]) {
+    pub fn upload_subtitle(&mut self, data: &[u8]) {
+        self.write_layer(&self.sub_texture, &premultiply_srgb(data));
+        self.has_subtitle = true;
+    }
+
+    /// Uploads the second caption layer. It is drawn until `clear_translation` is called.
+    pub fn upload_translation(&mut self, data: &[u8]) {
+        self.write_layer(&self.trans_texture, &premultiply_srgb(data));
+        self.has_translation = true;
+    }
+
+    pub fn clear_translation(&mut self) {
+        self.has_translation = false;
+    }
+
+    /// Uploads the current frame of the motion overlay (straight-alpha RGBA from `VideoDecoder::new_overlay`).
+    pub fn upload_motion_overlay(&mut self, data: &[u8]) {
+        self.write_layer(&self.motion_texture, &premultiply_srgb(data));
+        self.has_motion = true;
+    }
+
+    pub fn clear_motion_overlay(&mut self) {
+        self.has_motion = false;
+    }
+
+    fn write_layer(&self, texture: &wgpu::Texture, data: &[u8]) {
         self.ctx.queue.write_texture(
             wgpu::TexelCopyTextureInfo {
-                texture: &self.sub_texture,
+                texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
@@ -747,16 +2071,135 @@ impl Renderer {
         );
     }
 
+    pub fn set_caption_opacity(&mut self, opacity: f32) {
+        self.caption_opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    pub fn backend(&self) -> wgpu::Backend {
+        self.ctx.backend
+    }
+
+    pub fn set_enter_animation(&mut self, animation: EnterAnimation) {
+        self.enter_animation = animation;
+    }
+
+    /// Reveals both caption layers with a wipe instead of the uniform fade (`Reveal::Fade` restores it).
+    pub fn set_reveal(&mut self, reveal: Reveal) {
+        self.reveal = reveal;
+        self.apply_reveal();
+    }
+
+    /// Reveals both caption layers through a grayscale mask (`width`x`height` luma, stretched to
+    /// the frame): dark areas appear first, bright ones last. Takes precedence over `set_reveal`.
+    pub fn set_reveal_mask(&mut self, luma: &[u8], width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 || luma.len() != (width * height) as usize {
+            return Err(format!("Invalid reveal mask: {} bytes for {}x{}", luma.len(), width, height));
+        }
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let texture = self.ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reveal Mask"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        self.ctx.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            luma,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.reveal_mask = Some((texture, view));
+        self.apply_reveal();
+        Ok(())
+    }
+
+    fn reveal_mode(&self) -> u32 {
+        if self.reveal_mask.is_some() {
+            MASK_MODE_TEXTURE
+        } else {
+            self.reveal.mask_mode()
+        }
+    }
+
+    fn apply_reveal(&mut self) {
+        let mode = self.reveal_mode();
+        let mask = self.reveal_mask.as_ref().map(|(_, view)| view);
+        for layer in [&mut self.sub_renderer, &mut self.trans_renderer] {
+            layer.set_mask_texture(&self.ctx.device, mask);
+            layer.set_mask_mode(&self.ctx.queue, mode, REVEAL_SOFTNESS);
+        }
+    }
+
+    /// Rebuilds both caption layers' pipelines with `mode`; the tint layer keeps normal blending.
+    pub fn set_caption_blend(&mut self, mode: BlendMode) {
+        self.sub_renderer = ImageRenderer::premultiplied(&self.ctx.device, self.ctx.texture_format, mode);
+        self.trans_renderer = ImageRenderer::premultiplied(&self.ctx.device, self.ctx.texture_format, mode);
+        self.set_caption_sampler(self.caption_sampler);
+        self.apply_reveal();
+    }
+
+    /// Texture filtering of both caption layers; `Nearest` keeps unscaled captions pixel-exact.
+    pub fn set_caption_sampler(&mut self, mode: SamplerMode) {
+        self.caption_sampler = mode;
+        self.sub_renderer.set_sampler_mode(&self.ctx.device, mode);
+        self.trans_renderer.set_sampler_mode(&self.ctx.device, mode);
+    }
+
+    pub fn set_text_style(&mut self, style: TextStyle) {
+        self.text_renderer.set_style(style);
+    }
+
+    pub fn set_text_metrics(&mut self, font_size: f32, line_height: f32) -> Result<(), String> {
+        self.text_renderer.set_metrics(font_size, line_height)
+    }
+
+    pub fn set_font_fallback(&mut self, fonts: Vec<String>) -> Result<(), String> {
+        self.text_renderer.set_font_fallback(fonts)
+    }
+
     pub fn render_text(&mut self, text: &str) -> Result<(), String> {
         self.text_renderer.render(text, &self.ctx.device, &self.ctx.queue, &self.bg_view, self.width, self.height)
     }
 
+    /// Karaoke variant of `render_text`: spoken words are drawn in gold, upcoming ones in white.
+    pub fn render_karaoke(&mut self, cue: &KaraokeCue, time_ms: u32) -> Result<(), String> {
+        self.text_renderer.render_karaoke(
+            cue, time_ms,
+            Color::rgb(255, 215, 0), Color::rgb(255, 255, 255),
+            &self.ctx.device, &self.ctx.queue, &self.bg_view, self.width, self.height,
+        )
+    }
+
     pub async fn read_frame(&self) -> Result<Vec<u8>, String> {
+        let oom_scope = self.ctx.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        // Scaled output: the (opaque) composite is drawn over the whole output texture first
+        let (width, height, source) = match &self.output_target {
+            Some(target) => {
+                target.renderer.render(&self.ctx.device, &self.ctx.queue, &target.view, &self.bg_view)?;
+                (target.width, target.height, &target.texture)
+            }
+            None => (self.width, self.height, &self.bg_texture),
+        };
+        let stride = padded_bytes_per_row(width);
         let mut encoder = self.ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         
         encoder.copy_texture_to_buffer(
             wgpu::TexelCopyTextureInfo {
-                texture: &self.bg_texture,
+                texture: source,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
@@ -765,13 +2208,13 @@ impl Renderer {
                 buffer: &self.output_buffer,
                 layout: wgpu::TexelCopyBufferLayout {
                     offset: 0,
-                    bytes_per_row: Some(self.width * 4),
-                    rows_per_image: Some(self.height),
+                    bytes_per_row: Some(stride),
+                    rows_per_image: Some(height),
                 },
             },
             wgpu::Extent3d {
-                width: self.width,
-                height: self.height,
+                width,
+                height,
                 depth_or_array_layers: 1,
             }
         );
@@ -782,16 +2225,27 @@ impl Renderer {
         let (tx, rx) = tokio::sync::oneshot::channel();
         
         buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-            tx.send(result).unwrap();
+            // The receiver is only gone if read_frame was abandoned; nothing left to report to
+            let _ = tx.send(result);
         });
         
-        self.ctx.device.poll(wgpu::PollType::Wait { submission_index: Some(index), timeout: None }).unwrap();
+        let polled = self.ctx.device.poll(wgpu::PollType::Wait { submission_index: Some(index), timeout: None });
+        if let Some(e) = oom_scope.pop().await {
+            return Err(format!("{}: frame readback: {}", GPU_OUT_OF_MEMORY_ERROR, e));
+        }
+        polled.map_err(|e| format!("{}: frame readback poll: {}", GPU_ERROR, e))?;
         
-        rx.await.map_err(|e| format!("Map async error: {}", e))?
-          .map_err(|e| format!("Buffer map error: {}", e))?;
+        rx.await.map_err(|e| format!("{}: map callback dropped: {}", GPU_ERROR, e))?
+          .map_err(|e| format!("{}: buffer map: {}", GPU_ERROR, e))?;
         
         let data = buffer_slice.get_mapped_range();
-        let result = data.to_vec();
+        // Rows are padded to the copy alignment unless width × 4 is already a multiple of it
+        let row_bytes = (width * 4) as usize;
+        let result = if stride as usize == row_bytes {
+            data.to_vec()
+        } else {
+            data.chunks(stride as usize).flat_map(|row| &row[..row_bytes]).copied().collect()
+        };
         
         drop(data);
         self.output_buffer.unmap();
@@ -799,3 +2253,197 @@ impl Renderer {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hafs_font_system() -> FontSystem {
+        let mut db = glyphon::fontdb::Database::new();
+        db.load_font_data(include_bytes!("../../static/Hafs.ttf").to_vec());
+        FontSystem::new_with_locale_and_db("ar".to_string(), db)
+    }
+
+    fn shaped(font_system: &mut FontSystem, text: &str, wrap_width: f32, align: Option<Align>) -> Buffer {
+        let family = font_system.db().faces().next().unwrap().families[0].0.clone();
+        let mut buffer = Buffer::new(font_system, line_metrics(32.0, 42.0));
+        buffer.set_size(font_system, Some(wrap_width), None);
+        buffer.set_text(font_system, text, &Attrs::new().family(Family::Name(&family)), Shaping::Advanced, align);
+        buffer.shape_until_scroll(font_system, false);
+        buffer
+    }
+
+    #[test]
+    fn three_line_verse_is_centered_and_right_aligned() {
+        let verse = "بِسْمِ ٱللَّهِ\nٱلرَّحْمَٰنِ ٱلرَّحِيمِ\nمَٰلِكِ يَوْمِ ٱلدِّينِ";
+        let mut fs = hafs_font_system();
+        let frame_width = 640.0;
+        let wrap = frame_width - 2.0 * TEXT_MARGIN;
+
+        let centered = shaped(&mut fs, verse, wrap, Some(Align::Center));
+        let lines = line_extents(&centered);
+        assert_eq!(lines.len(), 3);
+        let left = text_area_left(Some(Align::Center), frame_width, wrap);
+        for (start, end, _) in &lines {
+            let mid = left + (start + end) / 2.0;
+            assert!((mid - frame_width / 2.0).abs() < 2.0, "line centered at {} in a {} frame", mid, frame_width);
+        }
+
+        let right = shaped(&mut fs, verse, wrap, Some(Align::Right));
+        let left = text_area_left(Some(Align::Right), frame_width, wrap);
+        for (_, end, _) in line_extents(&right) {
+            assert!((left + end - (frame_width - TEXT_MARGIN)).abs() < 2.0);
+        }
+    }
+
+    #[test]
+    fn tashkeel_is_not_clipped_above_the_line() {
+        let mut fs = hafs_font_system();
+        let mut swash = SwashCache::new();
+        // Requested line height equal to the font size: too tight for stacked marks without headroom
+        let metrics = line_metrics(32.0, 32.0);
+        let (width, rows) = (600usize, metrics.line_height.ceil() as usize);
+        let family = fs.db().faces().next().unwrap().families[0].0.clone();
+        let mut buffer = Buffer::new(&mut fs, metrics);
+        buffer.set_size(&mut fs, Some(width as f32), None);
+        buffer.set_text(&mut fs, "بِسْمِ ٱللَّهِ", &Attrs::new().family(Family::Name(&family)), Shaping::Advanced, None);
+        buffer.shape_until_scroll(&mut fs, false);
+
+        let run = buffer.layout_runs().next().unwrap();
+        let baseline = (run.line_y - run.line_top) as i32;
+        let glyphs: Vec<_> = run.glyphs.iter().map(|g| g.physical((0.0, 0.0), 1.0)).collect();
+
+        // Rasterize the line's alpha masks into a bitmap one line tall
+        let mut bitmap = vec![0u8; width * rows];
+        let mut clipped = 0;
+        for glyph in glyphs {
+            let Some(image) = swash.get_image_uncached(&mut fs, glyph.cache_key) else { continue };
+            let p = image.placement;
+            for y in 0..p.height as i32 {
+                for x in 0..p.width as i32 {
+                    let a = image.data[(y * p.width as i32 + x) as usize];
+                    if a == 0 {
+                        continue;
+                    }
+                    let (px, py) = (glyph.x + p.left + x, baseline + glyph.y - p.top + y);
+                    if py < 0 || py >= rows as i32 || px < 0 || px >= width as i32 {
+                        clipped += 1;
+                    } else {
+                        bitmap[py as usize * width + px as usize] = a;
+                    }
+                }
+            }
+        }
+
+        assert_eq!(clipped, 0, "diacritic pixels fell outside the line box");
+        let ink_above_baseline = bitmap[..baseline as usize * width].iter().filter(|a| **a > 0).count();
+        assert!(ink_above_baseline > 0);
+        // The top row stays clear: marks have headroom rather than touching the bounds
+        assert!(bitmap[..width].iter().all(|a| *a == 0));
+    }
+
+    #[test]
+    fn long_unique_arabic_passages_render_without_atlas_errors() {
+        let Ok(ctx) = pollster::block_on(WgpuContext::new(None)) else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let (width, height) = (4096, 4096);
+        let mut text = TextRenderer::new(&ctx.device, &ctx.queue, ctx.texture_format, width, height);
+        text.font_system.db_mut().load_font_data(include_bytes!("../../static/Hafs.ttf").to_vec());
+        let target = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Atlas Stress Target"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ctx.texture_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Every letter in its initial, medial and final forms, at a new size each pass: glyphs are
+        // cached per size, so the passes together need far more room than the largest atlas
+        let passage: String = ('\u{0628}'..='\u{064A}')
+            .filter(|c| c.is_alphabetic())
+            .map(|c| format!("{}{}{} {} ", c, c, c, c))
+            .collect();
+        for size in (100..=600).step_by(10) {
+            text.set_metrics(size as f32, size as f32 * 1.6).unwrap();
+            text.render(&passage, &ctx.device, &ctx.queue, &view, width, height)
+                .unwrap_or_else(|e| panic!("font size {}: {}", size, e));
+        }
+    }
+
+    #[test]
+    fn wipe_up_reveals_the_bottom_of_the_caption_first() {
+        let (w, h) = (64usize, 64usize);
+        let Ok(mut renderer) = pollster::block_on(Renderer::new(w as u32, h as u32, None)) else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let black = [0u8, 0, 0, 255].repeat(w * h);
+        renderer.set_reveal(Reveal::WipeUp);
+        renderer.upload_subtitle(&vec![255u8; w * h * 4]);
+        let mut render = |progress: f32| {
+            renderer.upload_background(&black);
+            renderer.render_image(progress, 0.0, false, 0.0).unwrap();
+            pollster::block_on(renderer.read_frame()).unwrap()
+        };
+        let red = |frame: &[u8], y: usize| frame[(y * w + w / 2) * 4];
+
+        // Halfway through the fade-in the bottom is fully shown and the top still hidden,
+        // rather than the whole caption at half opacity
+        let half = render(0.5);
+        assert_eq!(red(&half, h - 1), 255);
+        assert_eq!(red(&half, 0), 0);
+
+        let shown = render(1.0);
+        assert!(shown.chunks(4).all(|px| px[0] == 255));
+        let hidden = render(0.0);
+        assert!(hidden.chunks(4).all(|px| px[0] == 0));
+    }
+
+    #[test]
+    fn intro_card_title_is_centered_on_the_gradient() {
+        let (w, h) = (128usize, 128usize);
+        let Ok(mut renderer) = pollster::block_on(Renderer::new(w as u32, h as u32, None)) else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        renderer.set_text_metrics(24.0, 24.0).unwrap();
+        renderer.set_text_style(TextStyle {
+            color: Color::rgb(255, 255, 255),
+            align: Some(Align::Center),
+            center_vertically: true,
+            ..Default::default()
+        });
+        renderer.fill_gradient([0, 0, 200], [0, 200, 0]);
+        renderer.render_text("III").unwrap();
+        let frame = pollster::block_on(renderer.read_frame()).unwrap();
+        let px = |x: usize, y: usize| &frame[(y * w + x) * 4..(y * w + x) * 4 + 3];
+
+        assert_eq!(px(0, 0), [0, 0, 200]);
+        assert_eq!(px(0, h - 1), [0, 200, 0]);
+        // Text pixels (red channel lit) only in the middle band, around the center column
+        let lit: Vec<(usize, usize)> = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .filter(|&(x, y)| px(x, y)[0] > 128)
+            .collect();
+        assert!(!lit.is_empty());
+        assert!(lit.iter().all(|&(_, y)| y > h / 4 && y < 3 * h / 4));
+        let mean_x = lit.iter().map(|&(x, _)| x).sum::<usize>() as f32 / lit.len() as f32;
+        assert!((mean_x - w as f32 / 2.0).abs() < 8.0);
+    }
+
+    #[test]
+    fn fallback_chains_share_interned_names_and_lists() {
+        let a = FontFallbackChain::new(vec!["Amiri".to_string(), "Noto Sans".to_string()]);
+        let b = FontFallbackChain::new(vec!["Amiri".to_string(), "Noto Sans".to_string()]);
+        assert!(std::ptr::eq(a.families[0], b.families[0]));
+        let (la, lb) = (a.script_fallback(Script::Arabic, "en"), b.script_fallback(Script::Arabic, "en"));
+        assert_eq!(&la[..2], ["Amiri", "Noto Sans"]);
+        assert!(std::ptr::eq(la, lb));
+    }
+}